@@ -0,0 +1,355 @@
+use std::borrow::Borrow;
+use std::collections::BTreeMap;
+use std::fmt::Write as _;
+use std::io::{self, Write};
+use std::time::Duration;
+
+use crate::html::json_string;
+use crate::value::{Action, Color, EndReason, GameRecord};
+
+/// Lower bounds in seconds of the buckets of [`GameStats::move_times`].
+pub const MOVE_TIME_BUCKETS: [u64; 9] = [0, 1, 2, 5, 10, 30, 60, 120, 300];
+
+/// Results and time usage of a player.
+#[derive(Debug, PartialEq, Eq, Clone, Default)]
+pub struct PlayerStats {
+    pub games: u32,
+    pub wins: u32,
+    pub losses: u32,
+    pub draws: u32,
+    /// Number of moves with a recorded time.
+    pub timed_moves: u32,
+    pub total_time: Duration,
+}
+
+impl PlayerStats {
+    /// Returns the wins divided by the games with a result, or `None` without any.
+    pub fn win_rate(&self) -> Option<f64> {
+        let decided = self.wins + self.losses + self.draws;
+        (decided > 0).then(|| self.wins as f64 / decided as f64)
+    }
+
+    pub fn average_move_time(&self) -> Option<Duration> {
+        (self.timed_moves > 0).then(|| self.total_time / self.timed_moves)
+    }
+}
+
+/// Aggregates over a collection of games.
+#[derive(Debug, PartialEq, Eq, Clone, Default)]
+pub struct GameStats {
+    pub games: u32,
+    /// Moves played on the board, excluding the special move ending the game.
+    pub total_moves: u64,
+    pub players: BTreeMap<String, PlayerStats>,
+    /// Games by the reason they ended, leaving out games without a result.
+    pub endings: BTreeMap<EndReason, u32>,
+    /// Games by `$OPENING`, leaving out games without one.
+    pub openings: BTreeMap<String, u32>,
+    /// Number of moves by thinking time, in the buckets of [`MOVE_TIME_BUCKETS`].
+    pub move_times: [u32; MOVE_TIME_BUCKETS.len()],
+}
+
+impl GameStats {
+    pub fn from_records<I>(records: I) -> GameStats
+    where
+        I: IntoIterator,
+        I::Item: Borrow<GameRecord>,
+    {
+        let mut stats = GameStats::default();
+        for record in records {
+            stats.add(record.borrow());
+        }
+        stats
+    }
+
+    pub fn add(&mut self, record: &GameRecord) {
+        self.games += 1;
+        if let Some(ref opening) = record.opening {
+            *self.openings.entry(opening.clone()).or_default() += 1;
+        }
+
+        let result = record.result();
+        if let Some(result) = result {
+            *self.endings.entry(result.reason).or_default() += 1;
+        }
+
+        let players = [&record.black_player, &record.white_player];
+        for (color, name) in [Color::Black, Color::White].into_iter().zip(players) {
+            let Some(name) = name else {
+                continue;
+            };
+            let player = self.players.entry(name.clone()).or_default();
+            player.games += 1;
+            match result.map(|r| r.winner) {
+                Some(Some(winner)) if winner == color => player.wins += 1,
+                Some(Some(_)) => player.losses += 1,
+                Some(None) => player.draws += 1,
+                None => {}
+            }
+        }
+
+        let mut color = record.start_pos.side_to_move;
+        for mv in &record.moves {
+            let Action::Move(..) = mv.action else {
+                break;
+            };
+            self.total_moves += 1;
+            if let Some(time) = mv.time {
+                let bucket = MOVE_TIME_BUCKETS
+                    .iter()
+                    .rposition(|&secs| time.as_secs() >= secs)
+                    .unwrap_or(0);
+                self.move_times[bucket] += 1;
+
+                let name = match color {
+                    Color::Black => &record.black_player,
+                    Color::White => &record.white_player,
+                };
+                if let Some(player) = name.as_ref().and_then(|n| self.players.get_mut(n)) {
+                    player.timed_moves += 1;
+                    player.total_time += time;
+                }
+            }
+            color = color.flip();
+        }
+    }
+
+    /// Returns the average number of moves per game.
+    pub fn average_length(&self) -> Option<f64> {
+        (self.games > 0).then(|| self.total_moves as f64 / self.games as f64)
+    }
+
+    /// Writes the aggregates as CSV rows of `category,key,metric,value`.
+    pub fn write_csv<W: Write>(&self, mut writer: W) -> io::Result<()> {
+        writeln!(writer, "category,key,metric,value")?;
+        for (category, key, metric, value) in self.rows() {
+            writeln!(writer, "{category},{},{metric},{value}", csv_field(&key))?;
+        }
+        Ok(())
+    }
+
+    pub fn to_json(&self) -> String {
+        let mut json = format!(
+            "{{\"games\":{},\"total_moves\":{},\"average_length\":{}",
+            self.games,
+            self.total_moves,
+            json_number(self.average_length())
+        );
+
+        let players = self.players.iter().map(|(name, p)| {
+            format!(
+                "{}:{{\"games\":{},\"wins\":{},\"losses\":{},\"draws\":{},\"win_rate\":{},\"average_move_time\":{}}}",
+                json_string(name),
+                p.games,
+                p.wins,
+                p.losses,
+                p.draws,
+                json_number(p.win_rate()),
+                json_number(p.average_move_time().map(|t| t.as_secs_f64()))
+            )
+        });
+        write!(json, ",\"players\":{{{}}}", join(players)).unwrap();
+
+        let endings = self
+            .endings
+            .iter()
+            .map(|(&reason, n)| format!("\"{}\":{n}", reason_name(reason)));
+        write!(json, ",\"endings\":{{{}}}", join(endings)).unwrap();
+
+        let openings = self
+            .openings
+            .iter()
+            .map(|(opening, n)| format!("{}:{n}", json_string(opening)));
+        write!(json, ",\"openings\":{{{}}}", join(openings)).unwrap();
+
+        let move_times = self
+            .move_times
+            .iter()
+            .enumerate()
+            .map(|(i, n)| format!("{{\"bucket\":\"{}\",\"moves\":{n}}}", bucket_label(i)));
+        write!(json, ",\"move_times\":[{}]}}", join(move_times)).unwrap();
+
+        json
+    }
+
+    /// Returns the aggregates as `(category, key, metric, value)`.
+    fn rows(&self) -> Vec<(&'static str, String, &'static str, String)> {
+        let mut rows = vec![
+            ("summary", String::new(), "games", self.games.to_string()),
+            (
+                "summary",
+                String::new(),
+                "total_moves",
+                self.total_moves.to_string(),
+            ),
+            (
+                "summary",
+                String::new(),
+                "average_length",
+                csv_number(self.average_length()),
+            ),
+        ];
+
+        for (name, p) in &self.players {
+            let metrics = [
+                ("games", p.games.to_string()),
+                ("wins", p.wins.to_string()),
+                ("losses", p.losses.to_string()),
+                ("draws", p.draws.to_string()),
+                ("win_rate", csv_number(p.win_rate())),
+                (
+                    "average_move_time",
+                    csv_number(p.average_move_time().map(|t| t.as_secs_f64())),
+                ),
+            ];
+            for (metric, value) in metrics {
+                rows.push(("player", name.clone(), metric, value));
+            }
+        }
+        for (&reason, n) in &self.endings {
+            rows.push((
+                "ending",
+                reason_name(reason).to_string(),
+                "games",
+                n.to_string(),
+            ));
+        }
+        for (opening, n) in &self.openings {
+            rows.push(("opening", opening.clone(), "games", n.to_string()));
+        }
+        for (i, n) in self.move_times.iter().enumerate() {
+            rows.push(("move_time", bucket_label(i), "moves", n.to_string()));
+        }
+
+        rows
+    }
+}
+
+fn reason_name(reason: EndReason) -> &'static str {
+    match reason {
+        EndReason::Resign => "toryo",
+        EndReason::Checkmate => "tsumi",
+        EndReason::TimeUp => "time_up",
+        EndReason::IllegalMove => "illegal_move",
+        EndReason::IllegalAction => "illegal_action",
+        EndReason::Sennichite => "sennichite",
+        EndReason::Jishogi => "jishogi",
+        EndReason::Kachi => "kachi",
+        EndReason::Hikiwake => "hikiwake",
+        EndReason::Chudan => "chudan",
+        EndReason::Fuzumi => "fuzumi",
+        EndReason::Error => "error",
+    }
+}
+
+/// Labels a bucket of [`MOVE_TIME_BUCKETS`], e.g. `5-9s` or `300s+`.
+fn bucket_label(index: usize) -> String {
+    let low = MOVE_TIME_BUCKETS[index];
+    match MOVE_TIME_BUCKETS.get(index + 1) {
+        Some(&high) if high == low + 1 => format!("{low}s"),
+        Some(&high) => format!("{low}-{}s", high - 1),
+        None => format!("{low}s+"),
+    }
+}
+
+fn join(items: impl Iterator<Item = String>) -> String {
+    items.collect::<Vec<_>>().join(",")
+}
+
+fn json_number(value: Option<f64>) -> String {
+    value.map_or("null".to_string(), |v| format!("{v:.3}"))
+}
+
+fn csv_number(value: Option<f64>) -> String {
+    value.map_or(String::new(), |v| format!("{v:.3}"))
+}
+
+/// Quotes a CSV field if it contains a separator, a quote or a line break.
+fn csv_field(s: &str) -> String {
+    if s.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", s.replace('"', "\"\""))
+    } else {
+        s.to_string()
+    }
+}
+
+////////////////////////////////////////////////////////////////////////////////
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::parse_csa;
+
+    fn stats() -> GameStats {
+        let games = [
+            "V2.2\nN+alice\nN-bob\n$OPENING:YAGURA\nPI\n+\n+7776FU,T3\n-3334FU,T12\n%TORYO,T1\n",
+            "V2.2\nN+bob\nN-alice\n$OPENING:YAGURA\nPI\n+\n+2726FU,T0\n-8384FU,T400\n%TIME_UP\n",
+            "V2.2\nN+carol\nN-dave \"jr\"\nPI\n+\n+2726FU,T5\n%SENNICHITE\n",
+            "V2.2\nN+alice\nN-carol\n$OPENING:SHIKENBISHA\nPI\n+\n",
+        ];
+        GameStats::from_records(games.iter().map(|csa| parse_csa(csa).unwrap()))
+    }
+
+    #[test]
+    fn aggregate() {
+        let stats = stats();
+        assert_eq!(stats.games, 4);
+        assert_eq!(stats.average_length(), Some(1.25));
+
+        let alice = &stats.players["alice"];
+        assert_eq!(
+            (alice.games, alice.wins, alice.losses, alice.draws),
+            (3, 1, 1, 0)
+        );
+        assert_eq!(alice.win_rate(), Some(0.5));
+        assert_eq!(
+            alice.average_move_time(),
+            Some(Duration::from_millis(201_500))
+        );
+        assert_eq!(stats.players["carol"].draws, 1);
+        assert_eq!(stats.players["carol"].win_rate(), Some(0.0));
+
+        assert_eq!(
+            stats.endings.into_iter().collect::<Vec<_>>(),
+            [
+                (EndReason::Resign, 1),
+                (EndReason::TimeUp, 1),
+                (EndReason::Sennichite, 1)
+            ]
+        );
+        assert_eq!(stats.openings["YAGURA"], 2);
+        assert_eq!(stats.openings["SHIKENBISHA"], 1);
+        assert_eq!(stats.move_times, [1, 0, 1, 1, 1, 0, 0, 0, 1]);
+    }
+
+    #[test]
+    fn export() {
+        let record =
+            parse_csa("V2.2\nN+carol\nN-dave \"jr\"\n$OPENING:YAGURA\nPI\n+\n+2726FU,T5\n%TORYO\n")
+                .unwrap();
+        let stats = GameStats::from_records([&record]);
+
+        let mut csv = Vec::new();
+        stats.write_csv(&mut csv).unwrap();
+        let csv = String::from_utf8(csv).unwrap();
+        assert!(csv.starts_with(
+            "category,key,metric,value\nsummary,,games,1\nsummary,,total_moves,1\nsummary,,average_length,1.000\n"
+        ));
+        assert!(csv.contains("player,\"dave \"\"jr\"\"\",losses,1\n"));
+        assert!(csv.contains("player,carol,average_move_time,5.000\n"));
+        assert!(
+            csv.contains("ending,toryo,games,1\nopening,YAGURA,games,1\nmove_time,0s,moves,0\n")
+        );
+        assert!(csv.ends_with("move_time,5-9s,moves,1\nmove_time,10-29s,moves,0\nmove_time,30-59s,moves,0\nmove_time,60-119s,moves,0\nmove_time,120-299s,moves,0\nmove_time,300s+,moves,0\n"));
+
+        let json = stats.to_json();
+        assert!(json
+            .starts_with("{\"games\":1,\"total_moves\":1,\"average_length\":1.000,\"players\":{"));
+        assert!(json.contains(
+            "\"carol\":{\"games\":1,\"wins\":1,\"losses\":0,\"draws\":0,\"win_rate\":1.000,\"average_move_time\":5.000}"
+        ));
+        assert!(json.contains("\"average_move_time\":null}"));
+        assert!(json.contains(",\"endings\":{\"toryo\":1},\"openings\":{\"YAGURA\":1},"));
+        assert!(json.ends_with("{\"bucket\":\"300s+\",\"moves\":0}]}"));
+    }
+}
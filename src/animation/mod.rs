@@ -0,0 +1,568 @@
+mod font;
+mod raster;
+
+use std::borrow::Cow;
+use std::error::Error;
+use std::fmt;
+use std::time::Duration;
+
+use self::raster::{text_width, Canvas};
+use crate::board::Board;
+use crate::clock::{Clock, TimeControl};
+use crate::notation::{format_move, NotationStyle};
+use crate::parser::CsaError;
+use crate::value::{Action, Color, GameRecord, MoveFrom, Square};
+
+const PALETTE: [[u8; 3]; 8] = [
+    [0xff, 0xff, 0xff],
+    [0xf3, 0xd3, 0x8b],
+    [0x33, 0x33, 0x33],
+    [0xfb, 0xee, 0xd3],
+    [0x00, 0x00, 0x00],
+    [0xc0, 0x00, 0x00],
+    [0xf9, 0xa5, 0x5c],
+    [0x80, 0x80, 0x80],
+];
+const BACKGROUND: u8 = 0;
+const BOARD: u8 = 1;
+const LINE: u8 = 2;
+const PIECE: u8 = 3;
+const TEXT: u8 = 4;
+const PROMOTED: u8 = 5;
+const HIGHLIGHT: u8 = 6;
+const LABEL: u8 = 7;
+
+/// Largest width and height of frames, which is the limit of GIF.
+const MAX_FRAME_SIZE: u32 = u16::MAX as u32;
+
+/// Settings of animated images.
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct AnimationOptions {
+    pub frame_delay: Duration,
+    /// Delay of the last frame before the animation loops.
+    pub final_delay: Duration,
+    /// Size of a square in pixels.
+    ///
+    /// Sizes making frames larger than 65535 pixels on a side are rejected.
+    pub square_size: u32,
+    /// Draws the board from White's side.
+    pub flipped: bool,
+}
+
+impl Default for AnimationOptions {
+    fn default() -> Self {
+        AnimationOptions {
+            frame_delay: Duration::from_secs(1),
+            final_delay: Duration::from_secs(5),
+            square_size: 32,
+            flipped: false,
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum AnimationError {
+    Record(CsaError),
+    Gif(gif::EncodingError),
+    Png(png::EncodingError),
+    /// The square size makes frames too large.
+    SquareSize(u32),
+}
+
+impl fmt::Display for AnimationError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            AnimationError::Record(ref e) => write!(f, "failed to replay the record: {e}"),
+            AnimationError::Gif(ref e) => write!(f, "failed to encode GIF: {e}"),
+            AnimationError::Png(ref e) => write!(f, "failed to encode APNG: {e}"),
+            AnimationError::SquareSize(size) => {
+                write!(
+                    f,
+                    "square size {size} makes frames larger than {MAX_FRAME_SIZE} pixels"
+                )
+            }
+        }
+    }
+}
+
+impl Error for AnimationError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match *self {
+            AnimationError::Record(ref e) => Some(e),
+            AnimationError::Gif(ref e) => Some(e),
+            AnimationError::Png(ref e) => Some(e),
+            AnimationError::SquareSize(_) => None,
+        }
+    }
+}
+
+impl From<CsaError> for AnimationError {
+    fn from(e: CsaError) -> Self {
+        AnimationError::Record(e)
+    }
+}
+
+impl From<gif::EncodingError> for AnimationError {
+    fn from(e: gif::EncodingError) -> Self {
+        AnimationError::Gif(e)
+    }
+}
+
+impl From<png::EncodingError> for AnimationError {
+    fn from(e: png::EncodingError) -> Self {
+        AnimationError::Png(e)
+    }
+}
+
+////////////////////////////////////////////////////////////////////////////////
+
+impl GameRecord {
+    /// Renders the mainline as an animated GIF, one frame per move after the starting position.
+    pub fn to_gif(&self, options: &AnimationOptions) -> Result<Vec<u8>, AnimationError> {
+        let layout = Layout::new(options)?;
+        let frames = render_frames(self, options, &layout)?;
+        let palette = PALETTE.concat();
+        let mut buf = Vec::new();
+
+        {
+            let mut encoder = gif::Encoder::new(
+                &mut buf,
+                layout.width as u16,
+                layout.height as u16,
+                &palette,
+            )?;
+            encoder.set_repeat(gif::Repeat::Infinite)?;
+
+            for (canvas, delay) in &frames {
+                let frame = gif::Frame {
+                    width: canvas.width as u16,
+                    height: canvas.height as u16,
+                    delay: (delay.as_millis() / 10).min(u16::MAX as u128) as u16,
+                    buffer: Cow::Borrowed(&canvas.pixels),
+                    ..gif::Frame::default()
+                };
+                encoder.write_frame(&frame)?;
+            }
+        }
+
+        Ok(buf)
+    }
+
+    /// Renders the mainline as an animated PNG, one frame per move after the starting position.
+    pub fn to_apng(&self, options: &AnimationOptions) -> Result<Vec<u8>, AnimationError> {
+        let layout = Layout::new(options)?;
+        let frames = render_frames(self, options, &layout)?;
+        let mut buf = Vec::new();
+
+        {
+            let mut encoder = png::Encoder::new(&mut buf, layout.width, layout.height);
+            encoder.set_color(png::ColorType::Indexed);
+            encoder.set_depth(png::BitDepth::Eight);
+            encoder.set_palette(PALETTE.concat());
+            encoder.set_animated(frames.len() as u32, 0)?;
+
+            let mut writer = encoder.write_header()?;
+            for (canvas, delay) in &frames {
+                writer.set_frame_delay(delay.as_millis().min(u16::MAX as u128) as u16, 1000)?;
+                writer.write_image_data(&canvas.pixels)?;
+            }
+            writer.finish()?;
+        }
+
+        Ok(buf)
+    }
+}
+
+fn render_frames(
+    record: &GameRecord,
+    options: &AnimationOptions,
+    layout: &Layout,
+) -> Result<Vec<(Canvas, Duration)>, CsaError> {
+    let mut board = Board::from_position(&record.start_pos);
+    let mut clock = record
+        .time_limit
+        .as_ref()
+        .map(|time_limit| Clock::new(TimeControl::from(time_limit)));
+    let mut used = [Duration::ZERO; 2];
+    let mut side_to_move = record.start_pos.side_to_move;
+
+    let clock_text = |clock: &Option<Clock>, used: &[Duration; 2], color: Color| {
+        format_duration(match clock {
+            Some(clock) => clock.remaining(color),
+            None => used[color as usize],
+        })
+    };
+
+    let mut frames = vec![(
+        layout.render(
+            &board,
+            "Start",
+            None,
+            [
+                clock_text(&clock, &used, Color::Black),
+                clock_text(&clock, &used, Color::White),
+            ],
+        ),
+        options.frame_delay,
+    )];
+
+    for (i, mv) in record.moves.iter().enumerate() {
+        let notation = format_move(&board, &mv.action, None, NotationStyle::Western)?;
+        board.apply(&mv.action)?;
+
+        let color = match mv.action {
+            Action::Move(color, ..) => color,
+            _ => side_to_move,
+        };
+        side_to_move = color.flip();
+        if let Some(elapsed) = mv.time {
+            used[color as usize] += elapsed;
+            if let Some(ref mut clock) = clock {
+                clock.consume(color, elapsed);
+            }
+        }
+
+        let last_move = match mv.action {
+            Action::Move(_, from, to, _) => Some((from, to)),
+            _ => None,
+        };
+        frames.push((
+            layout.render(
+                &board,
+                &format!("{}. {notation}", i + 1),
+                last_move,
+                [
+                    clock_text(&clock, &used, Color::Black),
+                    clock_text(&clock, &used, Color::White),
+                ],
+            ),
+            options.frame_delay,
+        ));
+
+        if mv.action.is_terminal() {
+            break;
+        }
+    }
+
+    if let Some(last) = frames.last_mut() {
+        last.1 = options.final_delay;
+    }
+
+    Ok(frames)
+}
+
+fn format_duration(d: Duration) -> String {
+    let secs = d.as_secs();
+    format!("{}:{:02}:{:02}", secs / 3600, secs / 60 % 60, secs % 60)
+}
+
+////////////////////////////////////////////////////////////////////////////////
+
+struct Layout {
+    flipped: bool,
+    square: u32,
+    /// Scale of texts outside the board.
+    text_scale: u32,
+    pad: u32,
+    line_height: u32,
+    board_y: u32,
+    width: u32,
+    height: u32,
+}
+
+impl Layout {
+    fn new(options: &AnimationOptions) -> Result<Layout, AnimationError> {
+        let square = options.square_size.max(12);
+        if square > MAX_FRAME_SIZE / 9 {
+            return Err(AnimationError::SquareSize(options.square_size));
+        }
+        let text_scale = (square / 16).max(1);
+        let pad = square / 4;
+        let line_height = font::HEIGHT * text_scale + pad;
+        let board_y = pad + line_height * 3;
+
+        let layout = Layout {
+            flipped: options.flipped,
+            square,
+            text_scale,
+            pad,
+            line_height,
+            board_y,
+            width: pad + square * 9 + text_width("i", text_scale) + pad * 2,
+            height: board_y + square * 9 + pad + line_height,
+        };
+        if layout.width > MAX_FRAME_SIZE || layout.height > MAX_FRAME_SIZE {
+            return Err(AnimationError::SquareSize(options.square_size));
+        }
+
+        Ok(layout)
+    }
+
+    fn square_origin(&self, sq: Square) -> (u32, u32) {
+        let (col, row) = if self.flipped {
+            (sq.file() - 1, 9 - sq.rank())
+        } else {
+            (9 - sq.file(), sq.rank() - 1)
+        };
+
+        (
+            self.pad + self.square * col as u32,
+            self.board_y + self.square * row as u32,
+        )
+    }
+
+    fn render(
+        &self,
+        board: &Board,
+        title: &str,
+        last_move: Option<(MoveFrom, Square)>,
+        clocks: [String; 2],
+    ) -> Canvas {
+        let mut canvas = Canvas::new(self.width, self.height, BACKGROUND);
+        let s = self.square;
+
+        canvas.draw_text(self.pad, self.pad, title, self.text_scale, TEXT, false);
+
+        let (top, bottom) = if self.flipped {
+            (Color::Black, Color::White)
+        } else {
+            (Color::White, Color::Black)
+        };
+        self.render_hand(
+            &mut canvas,
+            board,
+            top,
+            &clocks[top as usize],
+            self.pad + self.line_height,
+        );
+        self.render_hand(
+            &mut canvas,
+            board,
+            bottom,
+            &clocks[bottom as usize],
+            self.board_y + s * 9 + self.pad / 2,
+        );
+
+        // Coordinates
+        for i in 0..9u8 {
+            let (file, rank) = if self.flipped {
+                (i + 1, 9 - i)
+            } else {
+                (9 - i, i + 1)
+            };
+            let file_label = file.to_string();
+            let rank_label = ((b'a' + rank - 1) as char).to_string();
+            let offset = s * i as u32 + (s - text_width(&file_label, self.text_scale)) / 2;
+            canvas.draw_text(
+                self.pad + offset,
+                self.pad + self.line_height * 2,
+                &file_label,
+                self.text_scale,
+                LABEL,
+                false,
+            );
+            canvas.draw_text(
+                self.pad + s * 9 + self.pad / 2,
+                self.board_y + s * i as u32 + (s - font::HEIGHT * self.text_scale) / 2,
+                &rank_label,
+                self.text_scale,
+                LABEL,
+                false,
+            );
+        }
+
+        // Board
+        canvas.fill_rect(self.pad, self.board_y, s * 9, s * 9, BOARD);
+        if let Some((from, to)) = last_move {
+            for sq in [from_square(from), Some(to)].into_iter().flatten() {
+                let (x, y) = self.square_origin(sq);
+                canvas.fill_rect(x, y, s, s, HIGHLIGHT);
+            }
+        }
+        for i in 0..=9 {
+            canvas.fill_rect(self.pad + s * i, self.board_y, 1, s * 9 + 1, LINE);
+            canvas.fill_rect(self.pad, self.board_y + s * i, s * 9 + 1, 1, LINE);
+        }
+
+        // Pieces
+        let inset = (s / 10).max(1);
+        let piece_scale = ((s - inset * 2 - 2) / text_width("+P", 1)).max(1);
+        for (sq, color, pt) in board.pieces() {
+            let (x, y) = self.square_origin(sq);
+            canvas.fill_rect(x + inset, y + inset, s - inset * 2, s - inset * 2, PIECE);
+            canvas.stroke_rect(x + inset, y + inset, s - inset * 2, s - inset * 2, LINE);
+
+            let letter = pt.usi_char().unwrap_or('?');
+            let label = if pt.is_promoted() {
+                format!("+{letter}")
+            } else {
+                letter.to_string()
+            };
+            let (w, h) = (text_width(&label, piece_scale), font::HEIGHT * piece_scale);
+            canvas.draw_text(
+                x + (s - w) / 2,
+                y + (s - h) / 2,
+                &label,
+                piece_scale,
+                if pt.is_promoted() { PROMOTED } else { TEXT },
+                (color == Color::White) != self.flipped,
+            );
+        }
+
+        canvas
+    }
+
+    fn render_hand(&self, canvas: &mut Canvas, board: &Board, color: Color, clock: &str, y: u32) {
+        let mark = match color {
+            Color::Black => "B:",
+            Color::White => "W:",
+        };
+        let mut text = mark.to_string();
+        for (pt, n) in board.hand_pieces(color) {
+            text.push(' ');
+            text.push(pt.usi_char().unwrap_or('?'));
+            if n > 1 {
+                text.push_str(&n.to_string());
+            }
+        }
+
+        let available = self.width - self.pad * 2;
+        let scale = if text_width(&format!("{text}  {clock}"), self.text_scale) <= available {
+            self.text_scale
+        } else {
+            1
+        };
+        canvas.draw_text(self.pad, y, &text, scale, TEXT, false);
+        canvas.draw_text(
+            self.width - self.pad - text_width(clock, scale),
+            y,
+            clock,
+            scale,
+            TEXT,
+            false,
+        );
+    }
+}
+
+fn from_square(from: MoveFrom) -> Option<Square> {
+    match from {
+        MoveFrom::Board(sq) => Some(sq),
+        MoveFrom::Hand => None,
+    }
+}
+
+////////////////////////////////////////////////////////////////////////////////
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn record() -> GameRecord {
+        let csa = "\
+$TIME_LIMIT:00:10+30
+PI
++
++7776FU
+T12
+-3334FU
+T5
++8822UM
+T3
+-3122GI
+T1
+%TORYO
+";
+        crate::parse_csa(csa).unwrap()
+    }
+
+    #[test]
+    fn frames() {
+        let options = AnimationOptions::default();
+        let layout = Layout::new(&options).unwrap();
+        let frames = render_frames(&record(), &options, &layout).unwrap();
+
+        assert_eq!(frames.len(), 6);
+        assert!(frames
+            .iter()
+            .all(|(canvas, _)| (canvas.width, canvas.height) == (layout.width, layout.height)));
+        assert_eq!(frames[0].1, Duration::from_secs(1));
+        assert_eq!(frames[5].1, Duration::from_secs(5));
+
+        // The destination of the last move is highlighted.
+        let (x, y) = layout.square_origin(Square::new(7, 6).unwrap());
+        assert_eq!(frames[1].0.pixel(x + 1, y + 1), HIGHLIGHT);
+        assert_eq!(frames[2].0.pixel(x + 1, y + 1), BOARD);
+        assert_ne!(frames[1].0.pixels, frames[2].0.pixels);
+    }
+
+    #[test]
+    fn clock_text() {
+        assert_eq!(format_duration(Duration::from_secs(588)), "0:09:48");
+        assert_eq!(format_duration(Duration::from_secs(3723)), "1:02:03");
+    }
+
+    #[test]
+    fn gif() {
+        let options = AnimationOptions {
+            square_size: 16,
+            ..AnimationOptions::default()
+        };
+        let data = record().to_gif(&options).unwrap();
+
+        let mut decoder = gif::DecodeOptions::new().read_info(&data[..]).unwrap();
+        let layout = Layout::new(&options).unwrap();
+        assert_eq!(decoder.width() as u32, layout.width);
+        assert_eq!(decoder.height() as u32, layout.height);
+
+        let mut delays = Vec::new();
+        while let Some(frame) = decoder.read_next_frame().unwrap() {
+            delays.push(frame.delay);
+        }
+        assert_eq!(delays, vec![100, 100, 100, 100, 100, 500]);
+    }
+
+    #[test]
+    fn apng() {
+        let data = record().to_apng(&AnimationOptions::default()).unwrap();
+
+        let decoder = png::Decoder::new(&data[..]);
+        let reader = decoder.read_info().unwrap();
+        let control = reader.info().animation_control.unwrap();
+        assert_eq!(control.num_frames, 6);
+        assert_eq!(control.num_plays, 0);
+    }
+
+    #[test]
+    fn invalid_record() {
+        let g = crate::parse_csa("PI\n+\n+5554FU\n").unwrap();
+        assert!(matches!(
+            g.to_gif(&AnimationOptions::default()),
+            Err(AnimationError::Record(CsaError::InvalidMove(_)))
+        ));
+    }
+
+    #[test]
+    fn square_size() {
+        let size = |square_size| {
+            let options = AnimationOptions {
+                square_size,
+                ..AnimationOptions::default()
+            };
+            Layout::new(&options).map(|layout| (layout.width, layout.height))
+        };
+
+        let (width, height) = size(4096).unwrap();
+        assert!(width <= MAX_FRAME_SIZE && height <= MAX_FRAME_SIZE);
+        assert!(matches!(size(8192), Err(AnimationError::SquareSize(8192))));
+        assert!(matches!(size(u32::MAX), Err(AnimationError::SquareSize(_))));
+
+        let options = AnimationOptions {
+            square_size: 10_000,
+            ..AnimationOptions::default()
+        };
+        assert!(matches!(
+            record().to_gif(&options),
+            Err(AnimationError::SquareSize(10_000))
+        ));
+    }
+}
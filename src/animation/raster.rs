@@ -0,0 +1,76 @@
+use super::font;
+
+/// An image whose pixels are indices into a palette.
+pub(super) struct Canvas {
+    pub(super) width: u32,
+    pub(super) height: u32,
+    pub(super) pixels: Vec<u8>,
+}
+
+impl Canvas {
+    pub(super) fn new(width: u32, height: u32, color: u8) -> Canvas {
+        Canvas {
+            width,
+            height,
+            pixels: vec![color; (width * height) as usize],
+        }
+    }
+
+    #[cfg(test)]
+    pub(super) fn pixel(&self, x: u32, y: u32) -> u8 {
+        self.pixels[(y * self.width + x) as usize]
+    }
+
+    pub(super) fn fill_rect(&mut self, x: u32, y: u32, width: u32, height: u32, color: u8) {
+        for py in y..(y + height).min(self.height) {
+            for px in x..(x + width).min(self.width) {
+                self.pixels[(py * self.width + px) as usize] = color;
+            }
+        }
+    }
+
+    pub(super) fn stroke_rect(&mut self, x: u32, y: u32, width: u32, height: u32, color: u8) {
+        self.fill_rect(x, y, width, 1, color);
+        self.fill_rect(x, y + height - 1, width, 1, color);
+        self.fill_rect(x, y, 1, height, color);
+        self.fill_rect(x + width - 1, y, 1, height, color);
+    }
+
+    /// Draws `text` with its top-left corner at `(x, y)`, rotated by 180 degrees around its center if `upside_down`.
+    pub(super) fn draw_text(
+        &mut self,
+        x: u32,
+        y: u32,
+        text: &str,
+        scale: u32,
+        color: u8,
+        upside_down: bool,
+    ) {
+        let width = text_width(text, scale);
+        let height = font::HEIGHT * scale;
+
+        for (i, c) in text.chars().enumerate() {
+            let left = i as u32 * (font::WIDTH + 1) * scale;
+            for (col, bits) in font::glyph(c).iter().enumerate() {
+                for row in 0..font::HEIGHT {
+                    if bits & (1 << row) == 0 {
+                        continue;
+                    }
+
+                    let (mut px, mut py) = (left + col as u32 * scale, row * scale);
+                    if upside_down {
+                        px = width - px - scale;
+                        py = height - py - scale;
+                    }
+                    self.fill_rect(x + px, y + py, scale, scale, color);
+                }
+            }
+        }
+    }
+}
+
+/// Returns the width of `text` drawn at `scale`, without the spacing after the last character.
+pub(super) fn text_width(text: &str, scale: u32) -> u32 {
+    let n = text.chars().count() as u32;
+    (n * (font::WIDTH + 1)).saturating_sub(1) * scale
+}
@@ -0,0 +1,655 @@
+use crate::parser::CsaError;
+use crate::value::{hirate_grid, Action, Color, MoveFrom, PieceType, Position, Square};
+
+/// Piece types which can be held in hand, in the order used by `Board::hand_pieces`.
+pub const HAND_PIECE_TYPES: [PieceType; 7] = [
+    PieceType::Rook,
+    PieceType::Bishop,
+    PieceType::Gold,
+    PieceType::Silver,
+    PieceType::Knight,
+    PieceType::Lance,
+    PieceType::Pawn,
+];
+
+const PIECE_COUNTS: [u8; 7] = [2, 2, 4, 4, 4, 4, 18];
+
+fn hand_index(pt: PieceType) -> Option<usize> {
+    HAND_PIECE_TYPES.iter().position(|&p| p == pt)
+}
+
+/// Derives the Zobrist key of a feature of a position with SplitMix64.
+fn zobrist_key(feature: u64) -> u64 {
+    let mut z = feature.wrapping_add(1).wrapping_mul(0x9e37_79b9_7f4a_7c15);
+    z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+    z ^ (z >> 31)
+}
+
+/// A position materialized as pieces on squares and in hand, used to replay moves.
+#[derive(Debug, PartialEq, Eq, Clone, Hash)]
+pub struct Board {
+    squares: [Option<(Color, PieceType)>; 81],
+    hands: [[u8; 7]; 2],
+    side_to_move: Color,
+}
+
+impl Default for Board {
+    fn default() -> Self {
+        Board {
+            squares: [None; 81],
+            hands: [[0; 7]; 2],
+            side_to_move: Color::Black,
+        }
+    }
+}
+
+impl Board {
+    /// Materializes a starting position.
+    ///
+    /// Pieces which cannot be held in hand are ignored when placed on `00`.
+    pub fn from_position(pos: &Position) -> Board {
+        let mut board = Board {
+            side_to_move: pos.side_to_move,
+            ..Board::default()
+        };
+
+        let grid = pos.bulk.unwrap_or_else(hirate_grid);
+        for (r, row) in grid.iter().enumerate() {
+            for (c, pc) in row.iter().enumerate() {
+                let sq = Square::new(9 - c as u8, r as u8 + 1).unwrap();
+                board.set(sq, *pc);
+            }
+        }
+
+        if pos.bulk.is_none() {
+            for &(sq, _) in &pos.drop_pieces {
+                board.set(sq, None);
+            }
+        }
+
+        for &(color, sq, pt) in &pos.add_pieces {
+            match (sq, pt) {
+                (None, PieceType::All) => {
+                    for (i, &pt) in HAND_PIECE_TYPES.iter().enumerate() {
+                        let remaining = PIECE_COUNTS[i].saturating_sub(board.count(pt));
+                        board.hands[color as usize][i] += remaining;
+                    }
+                }
+                (None, pt) => {
+                    if let Some(i) = hand_index(pt) {
+                        board.hands[color as usize][i] += 1;
+                    }
+                }
+                (Some(sq), pt) => board.set(sq, Some((color, pt))),
+            }
+        }
+
+        board
+    }
+
+    pub fn get(&self, sq: Square) -> Option<(Color, PieceType)> {
+        self.squares[sq.index()]
+    }
+
+    pub fn set(&mut self, sq: Square, pc: Option<(Color, PieceType)>) {
+        self.squares[sq.index()] = pc;
+    }
+
+    /// Returns an iterator over the pieces on the board in the order of square indices.
+    pub fn pieces(&self) -> impl Iterator<Item = (Square, Color, PieceType)> + '_ {
+        Square::iter().filter_map(move |sq| self.get(sq).map(|(color, pt)| (sq, color, pt)))
+    }
+
+    /// Returns the number of pieces in hand of the given type, which has to be unpromoted.
+    pub fn hand(&self, color: Color, pt: PieceType) -> u8 {
+        hand_index(pt).map_or(0, |i| self.hands[color as usize][i])
+    }
+
+    pub fn set_hand(&mut self, color: Color, pt: PieceType, count: u8) {
+        if let Some(i) = hand_index(pt) {
+            self.hands[color as usize][i] = count;
+        }
+    }
+
+    /// Returns the pieces in hand with their counts, from the most valuable.
+    pub fn hand_pieces(&self, color: Color) -> impl Iterator<Item = (PieceType, u8)> + '_ {
+        HAND_PIECE_TYPES
+            .iter()
+            .zip(self.hands[color as usize].iter())
+            .filter(|(_, &n)| n > 0)
+            .map(|(&pt, &n)| (pt, n))
+    }
+
+    pub fn side_to_move(&self) -> Color {
+        self.side_to_move
+    }
+
+    pub fn set_side_to_move(&mut self, color: Color) {
+        self.side_to_move = color;
+    }
+
+    /// Returns a Zobrist hash of the pieces on the board, the hands and the side to move.
+    ///
+    /// The keys are fixed, so hashes can be stored and compared across runs.
+    pub fn zobrist_hash(&self) -> u64 {
+        let mut hash = match self.side_to_move {
+            Color::Black => 0,
+            Color::White => zobrist_key(0),
+        };
+        for (sq, color, pt) in self.pieces() {
+            hash ^= zobrist_key(1 + ((sq.index() as u64) * 2 + color as u64) * 16 + pt as u64);
+        }
+        for color in [Color::Black, Color::White] {
+            for (i, &n) in self.hands[color as usize].iter().enumerate() {
+                if n > 0 {
+                    hash ^= zobrist_key(4096 + ((color as u64) * 7 + i as u64) * 32 + n as u64);
+                }
+            }
+        }
+        hash
+    }
+
+    /// Counts the pieces of the given unpromoted type, on the board including promoted ones and in both hands.
+    fn count(&self, pt: PieceType) -> u8 {
+        let on_board = self
+            .squares
+            .iter()
+            .flatten()
+            .filter(|(_, p)| p.hand_type() == Some(pt))
+            .count() as u8;
+
+        on_board + self.hand(Color::Black, pt) + self.hand(Color::White, pt)
+    }
+
+    /// Returns the squares the piece on `from` can move to, ignoring checks.
+    pub fn reachable_squares(&self, from: Square) -> Vec<Square> {
+        let Some((color, pt)) = self.get(from) else {
+            return Vec::new();
+        };
+        let sign = match color {
+            Color::Black => 1,
+            Color::White => -1,
+        };
+        let is_free = |sq: Square| self.get(sq).is_none_or(|(c, _)| c != color);
+
+        let mut squares = Vec::new();
+        for &(df, dr) in pt.steps() {
+            if let Some(sq) = from.shift(df * sign, dr * sign) {
+                if is_free(sq) {
+                    squares.push(sq);
+                }
+            }
+        }
+        for &(df, dr) in pt.slides() {
+            let mut cur = from;
+            while let Some(sq) = cur.shift(df * sign, dr * sign) {
+                if is_free(sq) {
+                    squares.push(sq);
+                }
+                if self.get(sq).is_some() {
+                    break;
+                }
+                cur = sq;
+            }
+        }
+
+        squares
+    }
+
+    /// Applies the action and returns the captured piece type, if any.
+    ///
+    /// Special moves leave the board unchanged. Moves are checked against the pieces present,
+    /// but not against the rules of movement.
+    pub fn apply(&mut self, action: &Action) -> Result<Option<PieceType>, CsaError> {
+        let Action::Move(color, from, to, pt) = *action else {
+            return Ok(None);
+        };
+        let invalid = || CsaError::InvalidMove(*action);
+
+        let captured = match self.get(to) {
+            Some((c, _)) if c == color => return Err(invalid()),
+            Some((_, captured)) => Some(captured),
+            None => None,
+        };
+
+        match from {
+            MoveFrom::Hand => {
+                let i = hand_index(pt).ok_or_else(invalid)?;
+                if captured.is_some() || self.hands[color as usize][i] == 0 {
+                    return Err(invalid());
+                }
+                self.hands[color as usize][i] -= 1;
+            }
+            MoveFrom::Board(from) => match self.get(from) {
+                Some((c, p)) if c == color && (p == pt || p.promote() == Some(pt)) => {
+                    self.set(from, None);
+                }
+                _ => return Err(invalid()),
+            },
+        }
+
+        if let Some(i) = captured.and_then(PieceType::hand_type).and_then(hand_index) {
+            self.hands[color as usize][i] += 1;
+        }
+        self.set(to, Some((color, pt)));
+        self.side_to_move = color.flip();
+
+        Ok(captured)
+    }
+
+    /// Returns the square of the king of the given side, if any.
+    pub fn king_square(&self, color: Color) -> Option<Square> {
+        self.pieces()
+            .find(|&(_, c, pt)| c == color && pt == PieceType::King)
+            .map(|(sq, _, _)| sq)
+    }
+
+    /// Returns `true` if any piece of `by` can move to `sq`.
+    pub fn is_attacked(&self, sq: Square, by: Color) -> bool {
+        self.pieces()
+            .filter(|&(_, c, _)| c == by)
+            .any(|(from, _, _)| self.reachable_squares(from).contains(&sq))
+    }
+
+    /// Returns `true` if the king of the given side is attacked.
+    pub fn is_in_check(&self, color: Color) -> bool {
+        self.king_square(color)
+            .is_some_and(|sq| self.is_attacked(sq, color.flip()))
+    }
+
+    /// Returns the legal moves of the side to move.
+    ///
+    /// Besides the rules of movement, moves must not leave the own king in check,
+    /// nor drop a pawn on a file with another unpromoted pawn (nifu) or to give mate (uchifuzume).
+    pub fn legal_moves(&self) -> Vec<Action> {
+        self.generate_moves(true)
+    }
+
+    pub fn is_legal(&self, action: &Action) -> bool {
+        matches!(*action, Action::Move(color, ..) if color == self.side_to_move)
+            && self.legal_moves().contains(action)
+    }
+
+    /// Returns `true` if the side to move is in check and has no legal moves.
+    pub fn is_checkmate(&self) -> bool {
+        self.is_in_check(self.side_to_move) && self.generate_moves(true).is_empty()
+    }
+
+    /// Returns `true` if the side to move can win by declaration (`%KACHI`) under the 27-point rule.
+    ///
+    /// The king has to be in the promotion zone and not in check, with at least 10 other pieces in the zone.
+    /// Those pieces and the pieces in hand have to be worth 28 points for Black and 27 points for White,
+    /// counting 5 for a rook or a bishop and 1 for the others.
+    pub fn can_declare_win(&self) -> bool {
+        let color = self.side_to_move;
+        match self.king_square(color) {
+            Some(sq) if sq.in_promotion_zone(color) => {}
+            _ => return false,
+        }
+        if self.is_in_check(color) {
+            return false;
+        }
+
+        let in_zone = self
+            .pieces()
+            .filter(|&(sq, c, pt)| {
+                c == color && pt != PieceType::King && sq.in_promotion_zone(color)
+            })
+            .map(|(_, _, pt)| pt)
+            .collect::<Vec<_>>();
+        let points = in_zone
+            .iter()
+            .map(|pt| pt.impasse_points() as u32)
+            .sum::<u32>()
+            + self
+                .hand_pieces(color)
+                .map(|(pt, n)| pt.impasse_points() as u32 * n as u32)
+                .sum::<u32>();
+        let required = match color {
+            Color::Black => 28,
+            Color::White => 27,
+        };
+
+        in_zone.len() >= 10 && points >= required
+    }
+
+    fn generate_moves(&self, check_uchifuzume: bool) -> Vec<Action> {
+        let color = self.side_to_move;
+        let mut moves = Vec::new();
+
+        for (from, _, pt) in self.pieces().filter(|&(_, c, _)| c == color) {
+            for to in self.reachable_squares(from) {
+                if pt.can_promote()
+                    && (from.in_promotion_zone(color) || to.in_promotion_zone(color))
+                {
+                    moves.push(Action::Move(
+                        color,
+                        MoveFrom::Board(from),
+                        to,
+                        pt.promote().unwrap(),
+                    ));
+                }
+                if can_stay(color, pt, to) {
+                    moves.push(Action::Move(color, MoveFrom::Board(from), to, pt));
+                }
+            }
+        }
+
+        for (pt, _) in self.hand_pieces(color) {
+            for to in Square::iter().filter(|&sq| self.get(sq).is_none()) {
+                if !can_stay(color, pt, to) {
+                    continue;
+                }
+                if pt == PieceType::Pawn
+                    && self.pieces().any(|(sq, c, p)| {
+                        c == color && p == PieceType::Pawn && sq.file() == to.file()
+                    })
+                {
+                    continue;
+                }
+                moves.push(Action::Move(color, MoveFrom::Hand, to, pt));
+            }
+        }
+
+        moves.retain(|action| {
+            let mut next = self.clone();
+            if next.apply(action).is_err() || next.is_in_check(color) {
+                return false;
+            }
+
+            !(check_uchifuzume
+                && matches!(*action, Action::Move(_, MoveFrom::Hand, _, PieceType::Pawn))
+                && next.is_in_check(color.flip())
+                && next.generate_moves(false).is_empty())
+        });
+
+        moves
+    }
+}
+
+/// Returns `false` if a piece on `sq` would have no squares to move to, e.g. a pawn on the last rank.
+fn can_stay(color: Color, pt: PieceType, sq: Square) -> bool {
+    match pt {
+        PieceType::Pawn | PieceType::Lance => sq.relative_rank(color) > 1,
+        PieceType::Knight => sq.relative_rank(color) > 2,
+        _ => true,
+    }
+}
+
+////////////////////////////////////////////////////////////////////////////////
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::value::Handicap;
+
+    fn sq(file: u8, rank: u8) -> Square {
+        Square::new(file, rank).unwrap()
+    }
+
+    fn mv(color: Color, from: (u8, u8), to: (u8, u8), pt: PieceType) -> Action {
+        let from = if from == (0, 0) {
+            MoveFrom::Hand
+        } else {
+            MoveFrom::Board(sq(from.0, from.1))
+        };
+        Action::Move(color, from, sq(to.0, to.1), pt)
+    }
+
+    #[test]
+    fn from_position() {
+        let board = Board::from_position(&Position::default());
+        assert_eq!(board.pieces().count(), 40);
+        assert_eq!(board.get(sq(5, 9)), Some((Color::Black, PieceType::King)));
+        assert_eq!(board.get(sq(8, 2)), Some((Color::White, PieceType::Rook)));
+        assert_eq!(board.get(sq(2, 8)), Some((Color::Black, PieceType::Rook)));
+        assert_eq!(board.get(sq(5, 5)), None);
+        assert_eq!(board.side_to_move(), Color::Black);
+
+        let board = Board::from_position(&Position::from_handicap(Handicap::TwoPieces));
+        assert_eq!(board.pieces().count(), 38);
+        assert_eq!(board.get(sq(8, 2)), None);
+        assert_eq!(board.get(sq(2, 2)), None);
+        assert_eq!(board.side_to_move(), Color::White);
+    }
+
+    #[test]
+    fn from_position_with_hands() {
+        let mut grid = [[None; 9]; 9];
+        grid[0][4] = Some((Color::White, PieceType::King));
+        grid[8][4] = Some((Color::Black, PieceType::King));
+        grid[4][4] = Some((Color::Black, PieceType::Dragon));
+        let pos = Position {
+            bulk: Some(grid),
+            add_pieces: vec![
+                (Color::Black, None, PieceType::Gold),
+                (Color::Black, None, PieceType::Pawn),
+                (Color::White, None, PieceType::All),
+            ],
+            ..Position::default()
+        };
+
+        let board = Board::from_position(&pos);
+        assert_eq!(board.hand(Color::Black, PieceType::Gold), 1);
+        assert_eq!(board.hand(Color::Black, PieceType::Pawn), 1);
+        assert_eq!(
+            board.hand_pieces(Color::White).collect::<Vec<_>>(),
+            vec![
+                (PieceType::Rook, 1),
+                (PieceType::Bishop, 2),
+                (PieceType::Gold, 3),
+                (PieceType::Silver, 4),
+                (PieceType::Knight, 4),
+                (PieceType::Lance, 4),
+                (PieceType::Pawn, 17)
+            ]
+        );
+    }
+
+    #[test]
+    fn apply() {
+        let mut board = Board::from_position(&Position::default());
+
+        assert_eq!(
+            board.apply(&mv(Color::Black, (7, 7), (7, 6), PieceType::Pawn)),
+            Ok(None)
+        );
+        assert_eq!(board.side_to_move(), Color::White);
+        board
+            .apply(&mv(Color::White, (3, 3), (3, 4), PieceType::Pawn))
+            .unwrap();
+        assert_eq!(
+            board.apply(&mv(Color::Black, (8, 8), (2, 2), PieceType::Horse)),
+            Ok(Some(PieceType::Bishop))
+        );
+        assert_eq!(board.hand(Color::Black, PieceType::Bishop), 1);
+        assert_eq!(board.get(sq(2, 2)), Some((Color::Black, PieceType::Horse)));
+
+        board
+            .apply(&mv(Color::White, (3, 1), (2, 2), PieceType::Silver))
+            .unwrap();
+        assert_eq!(board.hand(Color::White, PieceType::Bishop), 1);
+
+        board
+            .apply(&mv(Color::Black, (0, 0), (4, 5), PieceType::Bishop))
+            .unwrap();
+        assert_eq!(board.hand(Color::Black, PieceType::Bishop), 0);
+        assert_eq!(board.get(sq(4, 5)), Some((Color::Black, PieceType::Bishop)));
+
+        assert!(board.apply(&Action::Toryo).is_ok());
+    }
+
+    #[test]
+    fn apply_invalid() {
+        let mut board = Board::from_position(&Position::default());
+        let before = board.clone();
+
+        // Empty square.
+        assert!(board
+            .apply(&mv(Color::Black, (5, 5), (5, 4), PieceType::Pawn))
+            .is_err());
+        // Opponent's piece.
+        assert!(board
+            .apply(&mv(Color::Black, (3, 3), (3, 4), PieceType::Pawn))
+            .is_err());
+        // Mismatched piece type.
+        assert!(board
+            .apply(&mv(Color::Black, (7, 7), (7, 6), PieceType::Lance))
+            .is_err());
+        // Capturing own piece.
+        assert!(board
+            .apply(&mv(Color::Black, (9, 9), (9, 7), PieceType::Lance))
+            .is_err());
+        // Drop without pieces in hand.
+        assert!(board
+            .apply(&mv(Color::Black, (0, 0), (5, 5), PieceType::Pawn))
+            .is_err());
+
+        assert_eq!(board, before);
+    }
+
+    #[test]
+    fn reachable_squares() {
+        let board = Board::from_position(&Position::default());
+        assert_eq!(board.reachable_squares(sq(7, 7)), vec![sq(7, 6)]);
+        assert_eq!(board.reachable_squares(sq(3, 3)), vec![sq(3, 4)]);
+        assert_eq!(board.reachable_squares(sq(8, 8)), vec![]);
+        assert_eq!(board.reachable_squares(sq(5, 5)), vec![]);
+
+        let mut rook = board.reachable_squares(sq(2, 8));
+        rook.sort();
+        assert_eq!(
+            rook,
+            vec![sq(1, 8), sq(3, 8), sq(4, 8), sq(5, 8), sq(6, 8), sq(7, 8)]
+        );
+
+        let mut knight = board.reachable_squares(sq(2, 1));
+        knight.sort();
+        assert_eq!(knight, vec![]);
+
+        let mut board = Board::default();
+        board.set(sq(5, 5), Some((Color::White, PieceType::Knight)));
+        let mut knight = board.reachable_squares(sq(5, 5));
+        knight.sort();
+        assert_eq!(knight, vec![sq(4, 7), sq(6, 7)]);
+    }
+
+    fn perft(board: &Board, depth: u32) -> usize {
+        if depth == 0 {
+            return 1;
+        }
+        board
+            .legal_moves()
+            .iter()
+            .map(|action| {
+                let mut next = board.clone();
+                next.apply(action).unwrap();
+                perft(&next, depth - 1)
+            })
+            .sum()
+    }
+
+    #[test]
+    fn legal_moves() {
+        let board = Board::from_position(&Position::default());
+        assert_eq!(perft(&board, 1), 30);
+        assert_eq!(perft(&board, 2), 900);
+
+        // Nifu.
+        let mut board = Board::from_position(&Position::default());
+        board.set_hand(Color::Black, PieceType::Pawn, 1);
+        assert!(!board
+            .legal_moves()
+            .iter()
+            .any(|m| matches!(m, Action::Move(_, MoveFrom::Hand, _, _))));
+    }
+
+    fn corner_mate(hand: PieceType) -> Board {
+        let mut board = Board::default();
+        board.set(sq(1, 1), Some((Color::White, PieceType::King)));
+        board.set(sq(3, 2), Some((Color::Black, PieceType::Gold)));
+        board.set(sq(2, 4), Some((Color::Black, PieceType::Knight)));
+        board.set(sq(5, 9), Some((Color::Black, PieceType::King)));
+        board.set_hand(Color::Black, hand, 1);
+        board
+    }
+
+    #[test]
+    fn checkmate() {
+        let mut board = corner_mate(PieceType::Gold);
+        let drop = mv(Color::Black, (0, 0), (1, 2), PieceType::Gold);
+        assert!(board.is_legal(&drop));
+        assert!(!board.is_checkmate());
+
+        board.apply(&drop).unwrap();
+        assert!(board.is_in_check(Color::White));
+        assert!(board.is_checkmate());
+    }
+
+    #[test]
+    fn uchifuzume() {
+        let board = corner_mate(PieceType::Pawn);
+        assert!(!board.is_legal(&mv(Color::Black, (0, 0), (1, 2), PieceType::Pawn)));
+        assert!(board.is_legal(&mv(Color::Black, (0, 0), (1, 3), PieceType::Pawn)));
+
+        // Every square around the king is attacked.
+        let mut board = board;
+        board.set_side_to_move(Color::White);
+        assert!(!board.is_legal(&mv(Color::White, (1, 1), (2, 1), PieceType::King)));
+        assert_eq!(board.legal_moves(), vec![]);
+        assert!(!board.is_checkmate());
+    }
+
+    #[test]
+    fn declare_win() {
+        let mut board = Board::default();
+        board.set(sq(5, 1), Some((Color::Black, PieceType::King)));
+        board.set(sq(5, 9), Some((Color::White, PieceType::King)));
+        for (file, rank, pt) in [
+            (1, 1, PieceType::Rook),
+            (9, 1, PieceType::Rook),
+            (3, 1, PieceType::Bishop),
+            (7, 1, PieceType::Bishop),
+        ] {
+            board.set(sq(file, rank), Some((Color::Black, pt)));
+        }
+        for file in [2, 3, 4, 6, 7, 8] {
+            board.set(sq(file, 3), Some((Color::Black, PieceType::Pawn)));
+        }
+
+        board.set_hand(Color::Black, PieceType::Pawn, 1);
+        assert!(!board.can_declare_win());
+        board.set_hand(Color::Black, PieceType::Pawn, 2);
+        assert!(board.can_declare_win());
+
+        board.set(sq(8, 3), None);
+        board.set_hand(Color::Black, PieceType::Pawn, 3);
+        assert!(!board.can_declare_win());
+    }
+
+    #[test]
+    fn zobrist_hash() {
+        let start = Board::from_position(&Position::default());
+        let play = |moves: [((u8, u8), (u8, u8)); 3]| {
+            let mut board = start.clone();
+            for (from, to) in moves {
+                let color = board.side_to_move();
+                board.apply(&mv(color, from, to, PieceType::Pawn)).unwrap();
+            }
+            board
+        };
+
+        // The same position reached in another order has the same hash.
+        let mut board = play([((2, 7), (2, 6)), ((8, 3), (8, 4)), ((7, 7), (7, 6))]);
+        let mut other = play([((7, 7), (7, 6)), ((8, 3), (8, 4)), ((2, 7), (2, 6))]);
+        assert_ne!(board.zobrist_hash(), start.zobrist_hash());
+        assert_eq!(board.zobrist_hash(), other.zobrist_hash());
+
+        board.set_side_to_move(Color::Black);
+        assert_ne!(board.zobrist_hash(), other.zobrist_hash());
+        other.set_side_to_move(Color::Black);
+        other.set_hand(Color::White, PieceType::Pawn, 1);
+        assert_ne!(board.zobrist_hash(), other.zobrist_hash());
+    }
+}
@@ -0,0 +1,582 @@
+use std::cmp::Reverse;
+use std::collections::HashMap;
+use std::fs;
+use std::io::{self, ErrorKind, Read, Write};
+use std::path::Path;
+
+use crate::board::{Board, HAND_PIECE_TYPES};
+use crate::parser::{parse_csa, CsaError};
+use crate::value::{Action, Color, GameRecord, MoveFrom, PieceType, Square};
+
+/// The first bytes of a binary book written by [`OpeningBook::write_binary`].
+pub const BINARY_BOOK_MAGIC: &[u8; 8] = b"CSABOOK1";
+
+const BINARY_ENTRY_SIZE: usize = 30;
+
+/// Piece types in the order of their discriminants, used to encode moves.
+const PIECE_TYPES: [PieceType; 14] = [
+    PieceType::Pawn,
+    PieceType::Lance,
+    PieceType::Knight,
+    PieceType::Silver,
+    PieceType::Gold,
+    PieceType::Bishop,
+    PieceType::Rook,
+    PieceType::King,
+    PieceType::ProPawn,
+    PieceType::ProLance,
+    PieceType::ProKnight,
+    PieceType::ProSilver,
+    PieceType::Horse,
+    PieceType::Dragon,
+];
+
+/// Settings of [`BookBuilder`].
+#[derive(Debug, PartialEq, Clone)]
+pub struct BookConfig {
+    /// Number of plies of each game added to the book.
+    pub depth: usize,
+    /// Moves played fewer times are left out of the built book.
+    pub min_count: u32,
+    /// Games are added only if both players have a known rating of at least this value.
+    pub min_rating: Option<f64>,
+}
+
+impl Default for BookConfig {
+    fn default() -> Self {
+        BookConfig {
+            depth: 32,
+            min_count: 1,
+            min_rating: None,
+        }
+    }
+}
+
+/// A move of a book position with the results of the games it was played in.
+#[derive(Debug, PartialEq, Clone)]
+pub struct BookMove {
+    pub action: Action,
+    pub count: u32,
+    pub black_wins: u32,
+    pub white_wins: u32,
+    pub draws: u32,
+    /// Sum of the known ratings of the players who chose the move.
+    pub rating_sum: f64,
+    /// Number of ratings in `rating_sum`.
+    pub rated: u32,
+}
+
+impl BookMove {
+    pub fn average_rating(&self) -> Option<f64> {
+        (self.rated > 0).then(|| self.rating_sum / self.rated as f64)
+    }
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct BookPosition {
+    /// The position in SFEN, with the move number where it was first reached.
+    pub sfen: String,
+    /// Moves from the most played.
+    pub moves: Vec<BookMove>,
+}
+
+/// Positions keyed by [`Board::zobrist_hash`].
+#[derive(Debug, PartialEq, Clone, Default)]
+pub struct OpeningBook {
+    positions: HashMap<u64, BookPosition>,
+}
+
+impl OpeningBook {
+    pub fn get(&self, board: &Board) -> Option<&BookPosition> {
+        self.positions.get(&board.zobrist_hash())
+    }
+
+    pub fn len(&self) -> usize {
+        self.positions.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.positions.is_empty()
+    }
+
+    /// Returns the positions with their hashes in no particular order.
+    pub fn positions(&self) -> impl Iterator<Item = (u64, &BookPosition)> + '_ {
+        self.positions
+            .iter()
+            .map(|(&key, position)| (key, position))
+    }
+
+    /// Writes the book in the YaneuraOu `YANEURAOU-DB2016 1.00` format, sorted by SFEN.
+    ///
+    /// Each move is written with the most played reply as the ponder move, and without
+    /// score or depth.
+    pub fn write_yaneuraou<W: Write>(&self, mut writer: W) -> io::Result<()> {
+        let mut positions = self.positions.values().collect::<Vec<_>>();
+        positions.sort_by(|a, b| a.sfen.cmp(&b.sfen));
+
+        writeln!(writer, "#YANEURAOU-DB2016 1.00")?;
+        for position in positions {
+            let Ok(board) = Board::from_sfen(&position.sfen) else {
+                continue;
+            };
+            writeln!(writer, "sfen {}", position.sfen)?;
+            for mv in &position.moves {
+                let Some(usi) = board.usi_move(&mv.action) else {
+                    continue;
+                };
+                let mut next = board.clone();
+                let ponder = next
+                    .apply(&mv.action)
+                    .ok()
+                    .and_then(|_| self.get(&next))
+                    .and_then(|reply| reply.moves.first())
+                    .and_then(|reply| next.usi_move(&reply.action));
+                writeln!(
+                    writer,
+                    "{usi} {} 0 0 {}",
+                    ponder.as_deref().unwrap_or("none"),
+                    mv.count
+                )?;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Writes the book as fixed-size little-endian entries sorted by hash.
+    ///
+    /// After [`BINARY_BOOK_MAGIC`] and the number of entries as a `u32`, each move is an
+    /// entry of 30 bytes: the position hash (`u64`), the move (`u32`), the count, the wins
+    /// of Black and White and the draws (`u32` each), and the average rating rounded to a
+    /// `u16`, or 0 if unknown.
+    pub fn write_binary<W: Write>(&self, mut writer: W) -> io::Result<()> {
+        let mut entries = self
+            .positions
+            .iter()
+            .flat_map(|(&key, position)| {
+                position.moves.iter().filter_map(move |mv| {
+                    Some(BinaryBookEntry {
+                        key,
+                        mv: encode_move(&mv.action)?,
+                        count: mv.count,
+                        black_wins: mv.black_wins,
+                        white_wins: mv.white_wins,
+                        draws: mv.draws,
+                        rating: mv
+                            .average_rating()
+                            .map_or(0, |r| r.round().clamp(0.0, u16::MAX as f64) as u16),
+                    })
+                })
+            })
+            .collect::<Vec<_>>();
+        entries.sort_by(|a, b| a.key.cmp(&b.key).then(b.count.cmp(&a.count)));
+
+        writer.write_all(BINARY_BOOK_MAGIC)?;
+        writer.write_all(&(entries.len() as u32).to_le_bytes())?;
+        for entry in entries {
+            writer.write_all(&entry.key.to_le_bytes())?;
+            writer.write_all(&entry.mv.to_le_bytes())?;
+            for n in [entry.count, entry.black_wins, entry.white_wins, entry.draws] {
+                writer.write_all(&n.to_le_bytes())?;
+            }
+            writer.write_all(&entry.rating.to_le_bytes())?;
+        }
+
+        Ok(())
+    }
+}
+
+/// A move of a binary book, see [`OpeningBook::write_binary`].
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub struct BinaryBookEntry {
+    pub key: u64,
+    /// The encoded move, see [`BinaryBookEntry::action`].
+    pub mv: u32,
+    pub count: u32,
+    pub black_wins: u32,
+    pub white_wins: u32,
+    pub draws: u32,
+    /// The average rating of the players who chose the move, or 0 if unknown.
+    pub rating: u16,
+}
+
+impl BinaryBookEntry {
+    /// Decodes the move, which stores the destination in bits 0-6, the origin in bits 7-13
+    /// as a square index or 81 plus the index in [`HAND_PIECE_TYPES`], the piece type after
+    /// the move in bits 14-17 and the color in bit 18.
+    pub fn action(&self) -> Option<Action> {
+        let to = Square::from_index((self.mv & 0x7f) as usize)?;
+        let from = ((self.mv >> 7) & 0x7f) as usize;
+        let pt = *PIECE_TYPES.get(((self.mv >> 14) & 0xf) as usize)?;
+        let color = if self.mv & (1 << 18) == 0 {
+            Color::Black
+        } else {
+            Color::White
+        };
+
+        let from = match from {
+            0..=80 => MoveFrom::Board(Square::from_index(from)?),
+            _ if HAND_PIECE_TYPES.get(from - 81) == Some(&pt) => MoveFrom::Hand,
+            _ => return None,
+        };
+        Some(Action::Move(color, from, to, pt))
+    }
+}
+
+/// Reads the entries of a book written by [`OpeningBook::write_binary`].
+pub fn read_binary_book<R: Read>(mut reader: R) -> io::Result<Vec<BinaryBookEntry>> {
+    let mut header = [0; 12];
+    reader.read_exact(&mut header)?;
+    if &header[..8] != BINARY_BOOK_MAGIC {
+        return Err(io::Error::new(ErrorKind::InvalidData, "not a binary book"));
+    }
+    let len = u32::from_le_bytes(header[8..].try_into().unwrap());
+
+    let mut entries = Vec::new();
+    let mut buf = [0; BINARY_ENTRY_SIZE];
+    for _ in 0..len {
+        reader.read_exact(&mut buf)?;
+        let u32_at = |i: usize| u32::from_le_bytes(buf[i..i + 4].try_into().unwrap());
+        entries.push(BinaryBookEntry {
+            key: u64::from_le_bytes(buf[..8].try_into().unwrap()),
+            mv: u32_at(8),
+            count: u32_at(12),
+            black_wins: u32_at(16),
+            white_wins: u32_at(20),
+            draws: u32_at(24),
+            rating: u16::from_le_bytes(buf[28..].try_into().unwrap()),
+        });
+    }
+
+    Ok(entries)
+}
+
+fn encode_move(action: &Action) -> Option<u32> {
+    let Action::Move(color, from, to, pt) = *action else {
+        return None;
+    };
+    let from = match from {
+        MoveFrom::Board(sq) => sq.index(),
+        MoveFrom::Hand => 81 + HAND_PIECE_TYPES.iter().position(|&p| p == pt)?,
+    };
+    let pt = PIECE_TYPES.iter().position(|&p| p == pt)?;
+
+    Some(to.index() as u32 | (from as u32) << 7 | (pt as u32) << 14 | (color as u32) << 18)
+}
+
+////////////////////////////////////////////////////////////////////////////////
+
+/// Builds an [`OpeningBook`] from game records.
+#[derive(Debug, Clone, Default)]
+pub struct BookBuilder {
+    config: BookConfig,
+    book: OpeningBook,
+    games: usize,
+}
+
+impl BookBuilder {
+    pub fn new(config: BookConfig) -> BookBuilder {
+        BookBuilder {
+            config,
+            book: OpeningBook::default(),
+            games: 0,
+        }
+    }
+
+    /// Returns the number of games added so far.
+    pub fn games(&self) -> usize {
+        self.games
+    }
+
+    /// Adds the opening of a game whose players have the given ratings, Black first.
+    ///
+    /// Returns `false` if the game was left out by [`BookConfig::min_rating`].
+    pub fn add_record(&mut self, record: &GameRecord, ratings: [Option<f64>; 2]) -> bool {
+        if let Some(min) = self.config.min_rating {
+            if !ratings.iter().all(|r| r.is_some_and(|r| r >= min)) {
+                return false;
+            }
+        }
+
+        let winner = record.result().map(|result| result.winner);
+        let mut board = Board::from_position(&record.start_pos);
+        for (ply, mv) in record.moves.iter().take(self.config.depth).enumerate() {
+            // Stops at the end of the game or at a move the record should not contain.
+            if !board.is_legal(&mv.action) {
+                break;
+            }
+            let color = board.side_to_move();
+            let position = self
+                .book
+                .positions
+                .entry(board.zobrist_hash())
+                .or_insert_with(|| BookPosition {
+                    sfen: board.to_sfen(ply as u32 + 1),
+                    moves: Vec::new(),
+                });
+            let index = match position.moves.iter().position(|m| m.action == mv.action) {
+                Some(index) => index,
+                None => {
+                    position.moves.push(BookMove {
+                        action: mv.action,
+                        count: 0,
+                        black_wins: 0,
+                        white_wins: 0,
+                        draws: 0,
+                        rating_sum: 0.0,
+                        rated: 0,
+                    });
+                    position.moves.len() - 1
+                }
+            };
+
+            let book_move = &mut position.moves[index];
+            book_move.count += 1;
+            match winner {
+                Some(Some(Color::Black)) => book_move.black_wins += 1,
+                Some(Some(Color::White)) => book_move.white_wins += 1,
+                Some(None) => book_move.draws += 1,
+                None => {}
+            }
+            if let Some(rating) = ratings[color as usize] {
+                book_move.rating_sum += rating;
+                book_move.rated += 1;
+            }
+
+            board.apply(&mv.action).unwrap();
+        }
+
+        self.games += 1;
+        true
+    }
+
+    /// Parses a game in CSA and adds it with the ratings in its `'black_rate:` and
+    /// `'white_rate:` comments, as written by Floodgate.
+    pub fn add_csa(&mut self, csa: &str) -> Result<bool, CsaError> {
+        let record = parse_csa(csa)?;
+        Ok(self.add_record(&record, parse_ratings(csa)))
+    }
+
+    /// Adds the games in the `.csa` files under a directory and its subdirectories.
+    ///
+    /// Files which are not valid UTF-8 or CSA are skipped. Returns the number of games added.
+    pub fn add_dir<P: AsRef<Path>>(&mut self, dir: P) -> io::Result<usize> {
+        let mut paths = fs::read_dir(dir)?
+            .map(|entry| entry.map(|e| e.path()))
+            .collect::<io::Result<Vec<_>>>()?;
+        paths.sort();
+
+        let mut added = 0;
+        for path in paths {
+            if path.is_dir() {
+                added += self.add_dir(&path)?;
+                continue;
+            }
+            if path.extension().is_none_or(|ext| ext != "csa") {
+                continue;
+            }
+            let csa = match fs::read_to_string(&path) {
+                Ok(csa) => csa,
+                Err(e) if e.kind() == ErrorKind::InvalidData => continue,
+                Err(e) => return Err(e),
+            };
+            if let Ok(true) = self.add_csa(&csa) {
+                added += 1;
+            }
+        }
+
+        Ok(added)
+    }
+
+    /// Drops moves played fewer than [`BookConfig::min_count`] times and sorts the rest.
+    pub fn build(self) -> OpeningBook {
+        let min_count = self.config.min_count;
+        let mut positions = self.book.positions;
+        positions.retain(|_, position| {
+            position.moves.retain(|mv| mv.count >= min_count);
+            position.moves.sort_by_key(|mv| Reverse(mv.count));
+            !position.moves.is_empty()
+        });
+
+        OpeningBook { positions }
+    }
+}
+
+/// Reads the ratings of Black and White from Floodgate comments such as
+/// `'black_rate:player+0123456789abcdef:2500.0`.
+fn parse_ratings(csa: &str) -> [Option<f64>; 2] {
+    let mut ratings = [None, None];
+    for line in csa.lines() {
+        let (index, rest) = if let Some(rest) = line.strip_prefix("'black_rate:") {
+            (0, rest)
+        } else if let Some(rest) = line.strip_prefix("'white_rate:") {
+            (1, rest)
+        } else {
+            continue;
+        };
+        ratings[index] = rest
+            .rsplit(':')
+            .next()
+            .and_then(|rate| rate.trim().parse().ok());
+    }
+    ratings
+}
+
+////////////////////////////////////////////////////////////////////////////////
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::value::Position;
+
+    const GAME1: &str = "\
+V2.2
+N+alice
+N-bob
+'black_rate:alice+0123:2400.0
+'white_rate:bob+4567:2000.0
+PI
++
++7776FU
+-3334FU
++2726FU
+%TORYO
+";
+
+    const GAME2: &str = "\
+V2.2
+N+carol
+N-bob
+'black_rate:carol+89ab:2600.0
+'white_rate:bob+4567:2000.0
+PI
++
++7776FU
+-8384FU
+%TORYO
+";
+
+    #[test]
+    fn build() {
+        let mut builder = BookBuilder::new(BookConfig {
+            depth: 2,
+            ..BookConfig::default()
+        });
+        assert_eq!(builder.add_csa(GAME1), Ok(true));
+        assert_eq!(builder.add_csa(GAME1), Ok(true));
+        assert_eq!(builder.add_csa(GAME2), Ok(true));
+        assert_eq!(builder.games(), 3);
+        let book = builder.build();
+        assert_eq!(book.len(), 2);
+
+        let mut board = Board::from_position(&Position::default());
+        let start = book.get(&board).unwrap();
+        assert_eq!(
+            start.sfen,
+            "lnsgkgsnl/1r5b1/ppppppppp/9/9/9/PPPPPPPPP/1B5R1/LNSGKGSNL b - 1"
+        );
+        let [first] = &start.moves[..] else {
+            panic!("expected a single move");
+        };
+        assert_eq!(first.count, 3);
+        assert_eq!((first.black_wins, first.white_wins, first.draws), (2, 1, 0));
+        assert!((first.average_rating().unwrap() - 2466.67).abs() < 0.01);
+
+        board.apply(&first.action).unwrap();
+        let replies = &book.get(&board).unwrap().moves;
+        assert_eq!(replies.len(), 2);
+        assert_eq!(replies[0].count, 2);
+        assert_eq!(replies[1].average_rating(), Some(2000.0));
+
+        let mut yaneuraou = Vec::new();
+        book.write_yaneuraou(&mut yaneuraou).unwrap();
+        assert_eq!(
+            String::from_utf8(yaneuraou).unwrap(),
+            "\
+#YANEURAOU-DB2016 1.00
+sfen lnsgkgsnl/1r5b1/ppppppppp/9/9/2P6/PP1PPPPPP/1B5R1/LNSGKGSNL w - 2
+3c3d none 0 0 2
+8c8d none 0 0 1
+sfen lnsgkgsnl/1r5b1/ppppppppp/9/9/9/PPPPPPPPP/1B5R1/LNSGKGSNL b - 1
+7g7f 3c3d 0 0 3
+"
+        );
+    }
+
+    #[test]
+    fn filters() {
+        let mut builder = BookBuilder::new(BookConfig {
+            depth: 2,
+            min_count: 2,
+            min_rating: Some(2500.0),
+        });
+        assert_eq!(builder.add_csa(GAME1), Ok(false));
+        assert_eq!(builder.add_csa(GAME2), Ok(false));
+        assert!(builder.build().is_empty());
+
+        let mut builder = BookBuilder::new(BookConfig {
+            depth: 2,
+            min_count: 2,
+            min_rating: Some(2000.0),
+        });
+        for csa in [GAME1, GAME1, GAME2] {
+            builder.add_csa(csa).unwrap();
+        }
+        let book = builder.build();
+        assert_eq!(book.len(), 2);
+        assert!(book.positions().all(|(_, p)| p.moves.len() == 1));
+    }
+
+    #[test]
+    fn illegal_move() {
+        let mut builder = BookBuilder::new(BookConfig::default());
+        let record = parse_csa("V2.2\nPI\n+\n+7776FU\n-3334FU\n+2277UM\n-8384FU\n").unwrap();
+        assert!(builder.add_record(&record, [None, None]));
+        let book = builder.build();
+
+        // Only the moves before the illegal one are counted.
+        assert_eq!(book.len(), 2);
+        assert!(book
+            .positions()
+            .all(|(_, p)| p.moves.iter().all(|m| m.action != record.moves[2].action)));
+    }
+
+    #[test]
+    fn binary() {
+        let mut builder = BookBuilder::new(BookConfig::default());
+        builder.add_csa(GAME1).unwrap();
+        builder.add_csa(GAME2).unwrap();
+        let book = builder.build();
+
+        let mut bytes = Vec::new();
+        book.write_binary(&mut bytes).unwrap();
+        assert_eq!(&bytes[..8], BINARY_BOOK_MAGIC);
+        assert_eq!(bytes.len(), 12 + 4 * BINARY_ENTRY_SIZE);
+
+        let entries = read_binary_book(&bytes[..]).unwrap();
+        assert!(entries.windows(2).all(|w| w[0].key <= w[1].key));
+        for (key, position) in book.positions() {
+            for mv in &position.moves {
+                let entry = entries
+                    .iter()
+                    .find(|e| e.key == key && e.action() == Some(mv.action))
+                    .unwrap();
+                assert_eq!(entry.count, mv.count);
+                assert_eq!(entry.white_wins, mv.white_wins);
+            }
+        }
+
+        let drop = Action::Move(
+            Color::White,
+            MoveFrom::Hand,
+            Square::new(5, 5).unwrap(),
+            PieceType::Bishop,
+        );
+        let entry = BinaryBookEntry {
+            mv: encode_move(&drop).unwrap(),
+            ..entries[0]
+        };
+        assert_eq!(entry.action(), Some(drop));
+        assert!(read_binary_book(&b"NOTABOOK\0\0\0\0"[..]).is_err());
+    }
+}
@@ -0,0 +1,381 @@
+use std::time::Duration;
+
+use crate::value::{Action, Color, GameRecord, TimeLimit};
+
+/// Rules of the chess clock, following the conventions of shogi-server.
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct TimeControl {
+    pub main_time: Duration,
+    pub byoyomi: Duration,
+    pub increment: Duration,
+    /// Unit which the time spent on a move is rounded down to.
+    pub time_unit: Duration,
+    /// Minimum time charged for a move, applied after rounding.
+    pub least_time_per_move: Duration,
+}
+
+impl TimeControl {
+    pub fn sudden_death(main_time: Duration) -> TimeControl {
+        TimeControl {
+            main_time,
+            ..TimeControl::default()
+        }
+    }
+
+    pub fn is_sudden_death(&self) -> bool {
+        self.byoyomi.is_zero() && self.increment.is_zero()
+    }
+
+    /// Returns `true` if the clock never runs out.
+    pub fn is_unlimited(&self) -> bool {
+        self.main_time.is_zero() && self.byoyomi.is_zero() && self.increment.is_zero()
+    }
+
+    /// Returns the time charged for a move which took `elapsed`.
+    pub fn charge(&self, elapsed: Duration) -> Duration {
+        let rounded = if self.time_unit.is_zero() {
+            elapsed
+        } else {
+            // The remainder is below `time_unit`, which fits in `u64` nanoseconds for any real unit.
+            let rest = elapsed.as_nanos() % self.time_unit.as_nanos();
+            elapsed - Duration::from_nanos(rest as u64)
+        };
+
+        rounded.max(self.least_time_per_move)
+    }
+
+    /// Returns the number of `time_unit`s in `d`, as sent in `T` of moves and the `BEGIN Time` block.
+    pub fn to_units(&self, d: Duration) -> u128 {
+        d.as_nanos() / self.effective_unit().as_nanos()
+    }
+
+    /// Returns the duration of `n` `time_unit`s.
+    pub fn from_units(&self, n: u64) -> Duration {
+        self.effective_unit()
+            .saturating_mul(u32::try_from(n).unwrap_or(u32::MAX))
+    }
+
+    /// Returns `time_unit`, or a millisecond if it is zero.
+    pub(crate) fn effective_unit(&self) -> Duration {
+        if self.time_unit.is_zero() {
+            Duration::from_millis(1)
+        } else {
+            self.time_unit
+        }
+    }
+
+    /// Replays the times recorded in `record` and reports the remaining time after each move.
+    pub fn replay(&self, record: &GameRecord) -> ClockReplay {
+        let mut clock = Clock::new(self.clone());
+        let mut snapshots = Vec::new();
+        let mut side_to_move = record.start_pos.side_to_move;
+
+        for (index, mv) in record.moves.iter().enumerate() {
+            let color = match mv.action {
+                Action::Move(color, ..) => color,
+                _ => side_to_move,
+            };
+            side_to_move = color.flip();
+
+            let elapsed = match mv.time {
+                Some(t) => t,
+                None if mv.action.is_terminal() => break,
+                None => Duration::ZERO,
+            };
+
+            if !clock.consume(color, elapsed) {
+                return ClockReplay {
+                    snapshots,
+                    time_up: Some(TimeUp {
+                        move_index: index,
+                        color,
+                    }),
+                };
+            }
+
+            snapshots.push(ClockSnapshot {
+                move_index: index,
+                color,
+                consumed: self.charge(elapsed),
+                black: clock.remaining(Color::Black),
+                white: clock.remaining(Color::White),
+            });
+
+            if mv.action.is_terminal() {
+                break;
+            }
+        }
+
+        ClockReplay {
+            snapshots,
+            time_up: None,
+        }
+    }
+}
+
+impl Default for TimeControl {
+    fn default() -> Self {
+        TimeControl {
+            main_time: Duration::ZERO,
+            byoyomi: Duration::ZERO,
+            increment: Duration::ZERO,
+            time_unit: Duration::from_secs(1),
+            least_time_per_move: Duration::ZERO,
+        }
+    }
+}
+
+impl From<&TimeLimit> for TimeControl {
+    fn from(time_limit: &TimeLimit) -> Self {
+        TimeControl {
+            main_time: time_limit.main_time,
+            byoyomi: time_limit.byoyomi,
+            increment: time_limit.increment,
+            ..TimeControl::default()
+        }
+    }
+}
+
+////////////////////////////////////////////////////////////////////////////////
+
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct Clock {
+    control: TimeControl,
+    remaining: [Duration; 2],
+}
+
+impl Clock {
+    pub fn new(control: TimeControl) -> Clock {
+        let remaining = [control.main_time; 2];
+        Clock { control, remaining }
+    }
+
+    pub fn control(&self) -> &TimeControl {
+        &self.control
+    }
+
+    /// Returns the main time left for the given side.
+    pub fn remaining(&self, color: Color) -> Duration {
+        self.remaining[color as usize]
+    }
+
+    /// Returns how long the given side can think on the next move before running out of time.
+    ///
+    /// Returns `None` if the clock is unlimited.
+    pub fn time_available(&self, color: Color) -> Option<Duration> {
+        if self.control.is_unlimited() {
+            None
+        } else {
+            Some(self.remaining(color) + self.control.byoyomi)
+        }
+    }
+
+    /// Returns `true` if a move which took `elapsed` makes the given side lose on time.
+    pub fn is_time_up(&self, color: Color, elapsed: Duration) -> bool {
+        match self.time_available(color) {
+            Some(available) => self.control.charge(elapsed) >= available,
+            None => false,
+        }
+    }
+
+    /// Charges a move which took `elapsed` to the given side.
+    ///
+    /// Returns `false` without updating the clock if the side runs out of time.
+    pub fn consume(&mut self, color: Color, elapsed: Duration) -> bool {
+        if self.is_time_up(color, elapsed) {
+            return false;
+        }
+
+        let charged = self.control.charge(elapsed);
+        let remaining = &mut self.remaining[color as usize];
+        *remaining = remaining.saturating_sub(charged) + self.control.increment;
+
+        true
+    }
+}
+
+////////////////////////////////////////////////////////////////////////////////
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub struct ClockSnapshot {
+    /// Index of the move in `GameRecord::moves`.
+    pub move_index: usize,
+    pub color: Color,
+    pub consumed: Duration,
+    pub black: Duration,
+    pub white: Duration,
+}
+
+impl ClockSnapshot {
+    pub fn remaining(&self, color: Color) -> Duration {
+        match color {
+            Color::Black => self.black,
+            Color::White => self.white,
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub struct TimeUp {
+    /// Index of the move in `GameRecord::moves` which exceeded the time.
+    pub move_index: usize,
+    pub color: Color,
+}
+
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct ClockReplay {
+    pub snapshots: Vec<ClockSnapshot>,
+    pub time_up: Option<TimeUp>,
+}
+
+////////////////////////////////////////////////////////////////////////////////
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::value::{MoveFrom, MoveRecord, PieceType, Square};
+
+    fn secs(s: u64) -> Duration {
+        Duration::from_secs(s)
+    }
+
+    fn record(times: &[u64]) -> GameRecord {
+        let mut g = GameRecord::default();
+        for (i, t) in times.iter().enumerate() {
+            let color = if i % 2 == 0 {
+                Color::Black
+            } else {
+                Color::White
+            };
+            g.moves.push(MoveRecord {
+                action: Action::Move(
+                    color,
+                    MoveFrom::Board(Square::new(1, 1).unwrap()),
+                    Square::new(1, 2).unwrap(),
+                    PieceType::Pawn,
+                ),
+                time: Some(secs(*t)),
+                comments: vec![],
+            });
+        }
+        g
+    }
+
+    #[test]
+    fn charge() {
+        let control = TimeControl {
+            least_time_per_move: secs(1),
+            ..TimeControl::sudden_death(secs(60))
+        };
+        assert_eq!(control.charge(Duration::from_millis(300)), secs(1));
+        assert_eq!(control.charge(Duration::from_millis(2900)), secs(2));
+
+        let control = TimeControl::sudden_death(secs(60));
+        assert_eq!(control.charge(Duration::from_millis(999)), secs(0));
+
+        // More units than fit in `u32`.
+        let control = TimeControl {
+            time_unit: Duration::from_millis(1),
+            ..control
+        };
+        let long = Duration::from_millis(u32::MAX as u64 + 1500) + Duration::from_micros(700);
+        assert_eq!(
+            control.charge(long),
+            Duration::from_millis(u32::MAX as u64 + 1500)
+        );
+    }
+
+    #[test]
+    fn sudden_death() {
+        let control = TimeControl::sudden_death(secs(10));
+        assert!(control.is_sudden_death());
+
+        let replay = control.replay(&record(&[3, 4, 6, 5, 1, 1]));
+        assert_eq!(replay.snapshots.len(), 4);
+        assert_eq!(replay.snapshots[3].remaining(Color::White), secs(1));
+        assert_eq!(
+            replay.time_up,
+            Some(TimeUp {
+                move_index: 4,
+                color: Color::Black
+            })
+        );
+    }
+
+    #[test]
+    fn byoyomi() {
+        let control = TimeControl {
+            byoyomi: secs(10),
+            ..TimeControl::sudden_death(secs(5))
+        };
+
+        let replay = control.replay(&record(&[7, 9, 9, 9]));
+        assert_eq!(replay.time_up, None);
+        assert_eq!(replay.snapshots[0].black, secs(0));
+        assert_eq!(replay.snapshots[1].white, secs(0));
+
+        let replay = control.replay(&record(&[7, 9, 10]));
+        assert_eq!(
+            replay.time_up,
+            Some(TimeUp {
+                move_index: 2,
+                color: Color::Black
+            })
+        );
+    }
+
+    #[test]
+    fn increment() {
+        let control = TimeControl {
+            increment: secs(5),
+            ..TimeControl::sudden_death(secs(10))
+        };
+
+        let replay = control.replay(&record(&[8, 2, 6]));
+        assert_eq!(replay.time_up, None);
+        assert_eq!(replay.snapshots[0].black, secs(7));
+        assert_eq!(replay.snapshots[1].white, secs(13));
+        assert_eq!(replay.snapshots[2].black, secs(6));
+
+        let replay = control.replay(&record(&[8, 2, 7]));
+        assert_eq!(
+            replay.time_up,
+            Some(TimeUp {
+                move_index: 2,
+                color: Color::Black
+            })
+        );
+    }
+
+    #[test]
+    fn unlimited() {
+        let control = TimeControl::default();
+        let replay = control.replay(&record(&[1000, 1000]));
+        assert_eq!(replay.time_up, None);
+        assert_eq!(replay.snapshots.len(), 2);
+    }
+
+    #[test]
+    fn special_moves() {
+        let control = TimeControl::sudden_death(secs(10));
+        let mut g = record(&[3]);
+        g.moves.push(MoveRecord {
+            action: Action::Toryo,
+            time: Some(secs(12)),
+            comments: vec![],
+        });
+        let replay = control.replay(&g);
+        assert_eq!(
+            replay.time_up,
+            Some(TimeUp {
+                move_index: 1,
+                color: Color::White
+            })
+        );
+
+        g.moves[1].time = None;
+        let replay = control.replay(&g);
+        assert_eq!(replay.time_up, None);
+        assert_eq!(replay.snapshots.len(), 1);
+    }
+}
@@ -0,0 +1,268 @@
+use std::fmt::Write;
+
+use crate::board::Board;
+use crate::notation::{format_move, NotationStyle, FILES, RANKS};
+use crate::parser::CsaError;
+use crate::value::{Action, Color, GameRecord, Square};
+
+const ANSI_RED: &str = "\x1b[31m";
+const ANSI_RESET: &str = "\x1b[0m";
+
+impl Board {
+    /// Renders the board as a BOD diagram used in KIF files.
+    pub fn to_bod(&self) -> String {
+        let mut s = String::new();
+        let frame = format!("+{}+", "-".repeat(27));
+
+        writeln!(s, "後手の持駒：{}", self.bod_hand(Color::White)).unwrap();
+        writeln!(s, "{}", file_header()).unwrap();
+        writeln!(s, "{frame}").unwrap();
+        for (rank, rank_name) in RANKS.iter().enumerate() {
+            s.push('|');
+            for file in (1..=9).rev() {
+                let sq = Square::new(file, rank as u8 + 1).unwrap();
+                match self.get(sq) {
+                    Some((color, pt)) => {
+                        s.push(if color == Color::White { 'v' } else { ' ' });
+                        s.push(pt.kanji_char().unwrap_or('？'));
+                    }
+                    None => s.push_str(" ・"),
+                }
+            }
+            writeln!(s, "|{rank_name}").unwrap();
+        }
+        writeln!(s, "{frame}").unwrap();
+        writeln!(s, "先手の持駒：{}", self.bod_hand(Color::Black)).unwrap();
+        if self.side_to_move() == Color::White {
+            writeln!(s, "後手番").unwrap();
+        }
+
+        s
+    }
+
+    fn bod_hand(&self, color: Color) -> String {
+        let pieces = self
+            .hand_pieces(color)
+            .map(|(pt, n)| {
+                let count = if n > 1 {
+                    kanji_number(n)
+                } else {
+                    String::new()
+                };
+                format!("{}{count}　", pt.kanji().unwrap_or_default())
+            })
+            .collect::<String>();
+
+        if pieces.is_empty() {
+            "なし".to_string()
+        } else {
+            pieces
+        }
+    }
+
+    /// Renders the board with box-drawing characters for terminals.
+    ///
+    /// White's pieces are prefixed by `v`, or coloured red with ANSI escape codes if `ansi` is set.
+    pub fn to_unicode(&self, ansi: bool) -> String {
+        let mut s = String::new();
+        let line = "─".repeat(27);
+
+        writeln!(s, "☖ {}", self.unicode_hand(Color::White)).unwrap();
+        writeln!(s, "{}", file_header()).unwrap();
+        writeln!(s, "┌{line}┐").unwrap();
+        for (rank, rank_name) in RANKS.iter().enumerate() {
+            s.push('│');
+            for file in (1..=9).rev() {
+                let sq = Square::new(file, rank as u8 + 1).unwrap();
+                let name = match self.get(sq) {
+                    Some((_, pt)) => pt.kanji_char().unwrap_or('？'),
+                    None => '・',
+                };
+                match self.get(sq) {
+                    Some((Color::White, _)) if ansi => {
+                        write!(s, " {ANSI_RED}{name}{ANSI_RESET}").unwrap()
+                    }
+                    Some((Color::White, _)) => write!(s, "v{name}").unwrap(),
+                    _ => write!(s, " {name}").unwrap(),
+                }
+            }
+            writeln!(s, "│{rank_name}").unwrap();
+        }
+        writeln!(s, "└{line}┘").unwrap();
+        writeln!(s, "☗ {}", self.unicode_hand(Color::Black)).unwrap();
+        writeln!(
+            s,
+            "手番：{}",
+            match self.side_to_move() {
+                Color::Black => '☗',
+                Color::White => '☖',
+            }
+        )
+        .unwrap();
+
+        s
+    }
+
+    fn unicode_hand(&self, color: Color) -> String {
+        let pieces = self
+            .hand_pieces(color)
+            .map(|(pt, n)| {
+                let name = pt.kanji().unwrap_or_default();
+                if n > 1 {
+                    format!("{name}{n}")
+                } else {
+                    name.to_string()
+                }
+            })
+            .collect::<Vec<_>>();
+
+        if pieces.is_empty() {
+            "なし".to_string()
+        } else {
+            pieces.join(" ")
+        }
+    }
+}
+
+impl GameRecord {
+    /// Renders the position after the first `ply` records of the mainline as a BOD diagram,
+    /// followed by the move count and the last move.
+    pub fn to_bod(&self, ply: usize) -> Result<String, CsaError> {
+        let mut board = Board::from_position(&self.start_pos);
+        let mut last = None;
+        let mut last_to = None;
+
+        for record in self.moves.iter().take(ply) {
+            let notation = format_move(&board, &record.action, last_to, NotationStyle::Ki2)?;
+            board.apply(&record.action)?;
+            if let Action::Move(_, _, to, _) = record.action {
+                last_to = Some(to);
+            }
+            last = Some(notation);
+        }
+
+        let mut s = board.to_bod();
+        match last {
+            Some(last) => writeln!(s, "手数＝{}  {last}  まで", ply.min(self.moves.len())),
+            None => writeln!(s, "手数＝0  まで"),
+        }
+        .unwrap();
+
+        Ok(s)
+    }
+}
+
+fn file_header() -> String {
+    FILES
+        .iter()
+        .rev()
+        .fold(" ".to_string(), |s, file| s + " " + file)
+}
+
+/// Formats a number up to 99 in kanji numerals, as used for hand counts.
+fn kanji_number(n: u8) -> String {
+    let (tens, ones) = (n / 10, n % 10);
+    let mut s = String::new();
+
+    if tens > 1 {
+        s.push_str(RANKS[tens as usize - 1]);
+    }
+    if tens > 0 {
+        s.push('十');
+    }
+    if ones > 0 {
+        s.push_str(RANKS[ones as usize - 1]);
+    }
+
+    s
+}
+
+////////////////////////////////////////////////////////////////////////////////
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::value::PieceType;
+
+    #[test]
+    fn bod() {
+        let csa = "\
+PI
++
++7776FU
+-3334FU
++8822UM
+-3122GI
+";
+        let g = crate::parse_csa(csa).unwrap();
+
+        let bod = "\
+後手の持駒：なし
+  ９ ８ ７ ６ ５ ４ ３ ２ １
++---------------------------+
+|v香v桂v銀v金v玉v金v銀v桂v香|一
+| ・v飛 ・ ・ ・ ・ ・v角 ・|二
+|v歩v歩v歩v歩v歩v歩v歩v歩v歩|三
+| ・ ・ ・ ・ ・ ・ ・ ・ ・|四
+| ・ ・ ・ ・ ・ ・ ・ ・ ・|五
+| ・ ・ ・ ・ ・ ・ ・ ・ ・|六
+| 歩 歩 歩 歩 歩 歩 歩 歩 歩|七
+| ・ 角 ・ ・ ・ ・ ・ 飛 ・|八
+| 香 桂 銀 金 玉 金 銀 桂 香|九
++---------------------------+
+先手の持駒：なし
+手数＝0  まで
+";
+        assert_eq!(g.to_bod(0).unwrap(), bod);
+
+        let bod = g.to_bod(4).unwrap();
+        assert!(bod.starts_with("後手の持駒：角　\n"));
+        assert!(bod.contains("|v香v桂v銀v金v玉v金 ・v桂v香|一\n"));
+        assert!(bod.contains("| ・v飛 ・ ・ ・ ・ ・v銀 ・|二\n"));
+        assert!(bod.contains("|v歩v歩v歩v歩v歩v歩 ・v歩v歩|三\n"));
+        assert!(bod.ends_with("先手の持駒：角　\n手数＝4  △同　銀  まで\n"));
+
+        let bod = g.to_bod(3).unwrap();
+        assert!(bod.ends_with("先手の持駒：角　\n後手番\n手数＝3  ▲２二角成  まで\n"));
+    }
+
+    #[test]
+    fn hands() {
+        let mut board = Board::default();
+        board.set_hand(Color::Black, PieceType::Pawn, 18);
+        board.set_hand(Color::Black, PieceType::Gold, 2);
+        board.set_hand(Color::Black, PieceType::Rook, 1);
+
+        assert!(board.to_bod().contains("先手の持駒：飛　金二　歩十八　\n"));
+        assert!(board.to_unicode(false).contains("☗ 飛 金2 歩18\n"));
+        assert_eq!(kanji_number(10), "十");
+        assert_eq!(kanji_number(23), "二十三");
+    }
+
+    #[test]
+    fn unicode() {
+        let mut board = Board::default();
+        board.set(
+            Square::new(5, 1).unwrap(),
+            Some((Color::White, PieceType::King)),
+        );
+        board.set(
+            Square::new(5, 9).unwrap(),
+            Some((Color::Black, PieceType::King)),
+        );
+        board.set(
+            Square::new(2, 2).unwrap(),
+            Some((Color::White, PieceType::ProSilver)),
+        );
+
+        let s = board.to_unicode(false);
+        assert!(s.starts_with("☖ なし\n  ９ ８ ７ ６ ５ ４ ３ ２ １\n┌"));
+        assert!(s.contains("│ ・ ・ ・ ・v玉 ・ ・ ・ ・│一\n"));
+        assert!(s.contains("│ ・ ・ ・ ・ ・ ・ ・v全 ・│二\n"));
+        assert!(s.ends_with("☗ なし\n手番：☗\n"));
+
+        let s = board.to_unicode(true);
+        assert!(s.contains(" \x1b[31m玉\x1b[0m"));
+        assert!(!s.contains('v'));
+    }
+}
@@ -0,0 +1,358 @@
+use std::fmt::Write;
+
+use crate::board::Board;
+use crate::notation::{format_move, NotationStyle};
+use crate::parser::CsaError;
+use crate::value::{Action, Color, GameRecord, Square};
+
+const TEMPLATE: &str = r##"<!DOCTYPE html>
+<html lang="ja">
+<head>
+<meta charset="utf-8">
+<title>{{TITLE}}</title>
+<style>
+body { font-family: sans-serif; margin: 16px; color: #222; }
+h1 { font-size: 18px; }
+#viewer { display: flex; gap: 16px; flex-wrap: wrap; }
+#board { border-collapse: collapse; background: #f3d38b; }
+#board td { width: 36px; height: 40px; border: 1px solid #555; text-align: center; font-size: 24px; font-family: serif; }
+#board td.white { transform: rotate(180deg); }
+#board td.last { background: #f9a55c; }
+#board td.promoted { color: #c00000; }
+#board th { font-weight: normal; font-size: 12px; color: #666; }
+.hand { min-height: 1.5em; margin: 4px 0; }
+#moves { width: 200px; height: 420px; }
+#comments { width: 320px; height: 120px; white-space: pre-wrap; border: 1px solid #ccc; padding: 4px; overflow: auto; }
+#graph { border: 1px solid #ccc; cursor: pointer; }
+</style>
+</head>
+<body>
+<h1>{{TITLE}}</h1>
+<div id="viewer">
+<div>
+<div class="hand" id="white-hand"></div>
+<table id="board"></table>
+<div class="hand" id="black-hand"></div>
+<div>
+<button id="first">|&lt;</button>
+<button id="prev">&lt;</button>
+<button id="next">&gt;</button>
+<button id="last">&gt;|</button>
+</div>
+</div>
+<select id="moves" size="20"></select>
+<div>
+<div id="comments"></div>
+<svg id="graph" width="320" height="120" viewBox="0 0 320 120"></svg>
+</div>
+</div>
+<script type="application/json" id="game-data">{{DATA}}</script>
+<script>
+(function () {
+  var game = JSON.parse(document.getElementById("game-data").textContent);
+  var last = game.positions.length - 1;
+  var ply = 0;
+  var files = ["９", "８", "７", "６", "５", "４", "３", "２", "１"];
+  var ranks = ["一", "二", "三", "四", "五", "六", "七", "八", "九"];
+  var board = document.getElementById("board");
+  var moves = document.getElementById("moves");
+  var graph = document.getElementById("graph");
+  var svgNs = "http://www.w3.org/2000/svg";
+
+  var header = board.insertRow();
+  files.forEach(function (f) {
+    var th = document.createElement("th");
+    th.textContent = f;
+    header.appendChild(th);
+  });
+  var cells = [];
+  ranks.forEach(function (r) {
+    var row = board.insertRow();
+    for (var i = 0; i < 9; i++) {
+      cells.push(row.insertCell());
+    }
+    var th = document.createElement("th");
+    th.textContent = r;
+    row.appendChild(th);
+  });
+
+  game.moves.forEach(function (m, i) {
+    var option = document.createElement("option");
+    option.value = i;
+    option.textContent = i === 0 ? m : i + " " + m;
+    moves.appendChild(option);
+  });
+
+  function drawGraph() {
+    var w = 320, h = 120, limit = 2000;
+    var points = [];
+    game.evals.forEach(function (e, i) {
+      if (e === null) {
+        return;
+      }
+      var v = Math.max(-limit, Math.min(limit, e));
+      points.push((last === 0 ? 0 : i * w / last) + "," + (h / 2 - v * h / 2 / limit));
+    });
+    var axis = document.createElementNS(svgNs, "line");
+    axis.setAttribute("x1", 0);
+    axis.setAttribute("x2", w);
+    axis.setAttribute("y1", h / 2);
+    axis.setAttribute("y2", h / 2);
+    axis.setAttribute("stroke", "#aaa");
+    graph.appendChild(axis);
+    var line = document.createElementNS(svgNs, "polyline");
+    line.setAttribute("points", points.join(" "));
+    line.setAttribute("fill", "none");
+    line.setAttribute("stroke", "#1f5fbf");
+    graph.appendChild(line);
+    var cursor = document.createElementNS(svgNs, "line");
+    cursor.setAttribute("id", "cursor");
+    cursor.setAttribute("y1", 0);
+    cursor.setAttribute("y2", h);
+    cursor.setAttribute("stroke", "#c00000");
+    graph.appendChild(cursor);
+    graph.addEventListener("click", function (ev) {
+      var rect = graph.getBoundingClientRect();
+      go(Math.round((ev.clientX - rect.left) / rect.width * last));
+    });
+  }
+
+  function render() {
+    var pos = game.positions[ply];
+    pos.cells.forEach(function (c, i) {
+      var white = c.charAt(0) === "v";
+      cells[i].textContent = white ? c.substring(1) : c;
+      cells[i].className = (white ? "white " : "") +
+        (i === pos.last ? "last " : "") +
+        ("と杏圭全馬龍".indexOf(c.charAt(c.length - 1)) >= 0 && c !== "" ? "promoted" : "");
+    });
+    document.getElementById("black-hand").textContent = "☗" + game.black + " " + pos.black_hand;
+    document.getElementById("white-hand").textContent = "☖" + game.white + " " + pos.white_hand;
+    document.getElementById("comments").textContent = game.comments[ply].join("\n");
+    moves.value = ply;
+    var x = last === 0 ? 0 : ply * 320 / last;
+    var cursor = document.getElementById("cursor");
+    cursor.setAttribute("x1", x);
+    cursor.setAttribute("x2", x);
+  }
+
+  function go(n) {
+    ply = Math.max(0, Math.min(last, n));
+    render();
+  }
+
+  document.getElementById("first").onclick = function () { go(0); };
+  document.getElementById("prev").onclick = function () { go(ply - 1); };
+  document.getElementById("next").onclick = function () { go(ply + 1); };
+  document.getElementById("last").onclick = function () { go(last); };
+  moves.onchange = function () { go(parseInt(moves.value, 10)); };
+  document.addEventListener("keydown", function (ev) {
+    if (ev.key === "ArrowLeft") { go(ply - 1); ev.preventDefault(); }
+    if (ev.key === "ArrowRight") { go(ply + 1); ev.preventDefault(); }
+    if (ev.key === "Home") { go(0); ev.preventDefault(); }
+    if (ev.key === "End") { go(last); ev.preventDefault(); }
+  });
+
+  drawGraph();
+  render();
+})();
+</script>
+</body>
+</html>
+"##;
+
+impl GameRecord {
+    /// Exports the mainline as a self-contained HTML page with an interactive board.
+    ///
+    /// Comments starting with `*` followed by a number, e.g. `'** 30 -3334FU`, are plotted
+    /// as evaluation values from Black's point of view.
+    pub fn to_html(&self) -> Result<String, CsaError> {
+        let black = self.black_player.as_deref().unwrap_or("先手");
+        let white = self.white_player.as_deref().unwrap_or("後手");
+        let title = match self.event {
+            Some(ref event) => format!("{event}: {black} - {white}"),
+            None => format!("{black} - {white}"),
+        };
+
+        let mut board = Board::from_position(&self.start_pos);
+        let mut positions = vec![position_json(&board, None)];
+        let mut moves = vec![json_string("開始局面")];
+        let mut comments = vec!["[]".to_string()];
+        let mut evals = vec!["null".to_string()];
+        let mut last_to = None;
+
+        for record in &self.moves {
+            moves.push(json_string(&format_move(
+                &board,
+                &record.action,
+                last_to,
+                NotationStyle::Kif,
+            )?));
+            board.apply(&record.action)?;
+            if let Action::Move(_, _, to, _) = record.action {
+                last_to = Some(to);
+            }
+
+            positions.push(position_json(&board, last_to));
+            comments.push(json_array(record.comments.iter().map(|c| json_string(c))));
+            evals.push(
+                record
+                    .evaluation()
+                    .map_or("null".to_string(), |e| e.score.to_string()),
+            );
+        }
+
+        let data = format!(
+            r#"{{"black":{},"white":{},"positions":{},"moves":{},"comments":{},"evals":{}}}"#,
+            json_string(black),
+            json_string(white),
+            json_array(positions.into_iter()),
+            json_array(moves.into_iter()),
+            json_array(comments.into_iter()),
+            json_array(evals.into_iter()),
+        );
+
+        Ok(fill_template(&escape_html(&title), &data))
+    }
+}
+
+/// Substitutes the placeholders of the template in a single pass, so that placeholders
+/// appearing in names or comments are kept as they are.
+fn fill_template(title: &str, data: &str) -> String {
+    let mut page = String::with_capacity(TEMPLATE.len() + title.len() * 2 + data.len());
+    let mut rest = TEMPLATE;
+    while let Some(i) = rest.find("{{") {
+        page.push_str(&rest[..i]);
+        rest = &rest[i..];
+        if let Some(after) = rest.strip_prefix("{{TITLE}}") {
+            page.push_str(title);
+            rest = after;
+        } else if let Some(after) = rest.strip_prefix("{{DATA}}") {
+            page.push_str(data);
+            rest = after;
+        } else {
+            page.push_str("{{");
+            rest = &rest[2..];
+        }
+    }
+    page.push_str(rest);
+    page
+}
+
+fn position_json(board: &Board, last_to: Option<Square>) -> String {
+    let cells = (1..=9).flat_map(|rank| {
+        (1..=9).rev().map(move |file| {
+            let sq = Square::new(file, rank).unwrap();
+            match board.get(sq) {
+                Some((color, pt)) => {
+                    let mark = if color == Color::White { "v" } else { "" };
+                    json_string(&format!("{mark}{}", pt.kanji_char().unwrap_or('？')))
+                }
+                None => json_string(""),
+            }
+        })
+    });
+    let hand = |color| {
+        board
+            .hand_pieces(color)
+            .map(|(pt, n)| match n {
+                1 => pt.kanji().unwrap_or_default().to_string(),
+                _ => format!("{}{n}", pt.kanji().unwrap_or_default()),
+            })
+            .collect::<Vec<_>>()
+            .join(" ")
+    };
+    let last = last_to.map_or(-1, |sq| (sq.rank() as i32 - 1) * 9 + (9 - sq.file() as i32));
+
+    format!(
+        r#"{{"cells":{},"black_hand":{},"white_hand":{},"last":{last}}}"#,
+        json_array(cells),
+        json_string(&hand(Color::Black)),
+        json_string(&hand(Color::White)),
+    )
+}
+
+fn json_array(items: impl Iterator<Item = String>) -> String {
+    format!("[{}]", items.collect::<Vec<_>>().join(","))
+}
+
+/// Quotes a string for JSON embedded in a `<script>` element.
+pub(crate) fn json_string(s: &str) -> String {
+    let mut quoted = String::with_capacity(s.len() + 2);
+    quoted.push('"');
+    for c in s.chars() {
+        match c {
+            '"' => quoted.push_str("\\\""),
+            '\\' => quoted.push_str("\\\\"),
+            '\n' => quoted.push_str("\\n"),
+            '\r' => quoted.push_str("\\r"),
+            '\t' => quoted.push_str("\\t"),
+            '<' | '>' | '&' => write!(quoted, "\\u{:04x}", c as u32).unwrap(),
+            c if (c as u32) < 0x20 => write!(quoted, "\\u{:04x}", c as u32).unwrap(),
+            c => quoted.push(c),
+        }
+    }
+    quoted.push('"');
+    quoted
+}
+
+fn escape_html(s: &str) -> String {
+    s.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+////////////////////////////////////////////////////////////////////////////////
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn html() {
+        let csa = "\
+N+Black <b>
+N-White
+PI
++
++7776FU
+'** 30 -3334FU
+'</script>
+-3334FU
+'*-45
++8822UM
+%TORYO
+";
+        let g = crate::parse_csa(csa).unwrap();
+        let html = g.to_html().unwrap();
+
+        assert!(html.starts_with("<!DOCTYPE html>"));
+        assert!(html.contains("<title>Black &lt;b&gt; - White</title>"));
+        assert_eq!(html.matches("</script>").count(), 2);
+        assert!(html.contains(r#""evals":[null,30,-45,null,null]"#));
+        assert!(html.contains(
+            r#""moves":["開始局面","▲７六歩(77)","△３四歩(33)","▲２二角成(88)","投了"]"#
+        ));
+        assert!(html.contains(r#""black_hand":"角","white_hand":"","last":16}"#));
+        assert!(html.contains(r#"["** 30 -3334FU","\u003c/script\u003e"]"#));
+    }
+
+    #[test]
+    fn placeholders_in_values() {
+        let g = crate::parse_csa("N+{{DATA}}\nN-White\nPI\n+\n+7776FU\n'{{TITLE}}\n").unwrap();
+        let html = g.to_html().unwrap();
+
+        assert!(html.contains("<title>{{DATA}} - White</title>"));
+        assert!(html.contains("<h1>{{DATA}} - White</h1>"));
+        assert!(html.contains(r#""black":"{{DATA}}","white":"White""#));
+        assert!(html.contains(r#""comments":[[],["{{TITLE}}"]]"#));
+    }
+
+    #[test]
+    fn json() {
+        assert_eq!(json_string("a\"b\\c\nd"), r#""a\"b\\c\nd""#);
+        assert_eq!(json_string("<&>"), r#""\u003c\u0026\u003e""#);
+        assert_eq!(json_array(vec![].into_iter()), "[]");
+    }
+}
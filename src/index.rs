@@ -0,0 +1,311 @@
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, BufRead, BufReader, BufWriter, ErrorKind, Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+
+use crate::board::Board;
+use crate::parser::parse_csa;
+use crate::usi::UsiError;
+use crate::value::{Action, GameRecord};
+
+/// The first bytes of each segment file of a [`PositionIndex`].
+pub const INDEX_SEGMENT_MAGIC: &[u8; 8] = b"CSAIDX01";
+
+const GAMES_FILE: &str = "games.txt";
+const HEADER_SIZE: u64 = 12;
+const ENTRY_SIZE: u64 = 16;
+
+/// A game which reached a searched position.
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct PositionHit {
+    pub game_id: String,
+    /// Number of moves played from the starting position of the game.
+    pub ply: u32,
+}
+
+/// An on-disk index of the positions reached in a collection of games.
+///
+/// The index is a directory holding `games.txt`, with one game ID per line, and segment
+/// files written by each call to [`PositionIndex::add_games`]. A segment starts with
+/// [`INDEX_SEGMENT_MAGIC`] and the number of entries as a little-endian `u32`, followed by
+/// entries of 16 bytes sorted by the position hash (`u64`), each with the line number of
+/// the game (`u32`) and the ply (`u32`). Searches read only the matching entries of each
+/// segment and the list of game IDs.
+#[derive(Debug, Clone)]
+pub struct PositionIndex {
+    dir: PathBuf,
+    games: Vec<String>,
+    segments: Vec<PathBuf>,
+}
+
+impl PositionIndex {
+    /// Opens the index in a directory, creating an empty one if it does not exist.
+    pub fn open<P: AsRef<Path>>(dir: P) -> io::Result<PositionIndex> {
+        let dir = dir.as_ref().to_path_buf();
+        fs::create_dir_all(&dir)?;
+
+        let games = match File::open(dir.join(GAMES_FILE)) {
+            Ok(file) => BufReader::new(file).lines().collect::<io::Result<_>>()?,
+            Err(e) if e.kind() == ErrorKind::NotFound => Vec::new(),
+            Err(e) => return Err(e),
+        };
+        let mut segments = fs::read_dir(&dir)?
+            .map(|entry| entry.map(|e| e.path()))
+            .collect::<io::Result<Vec<_>>>()?;
+        segments.retain(|path| path.extension().is_some_and(|ext| ext == "idx"));
+        segments.sort();
+
+        Ok(PositionIndex {
+            dir,
+            games,
+            segments,
+        })
+    }
+
+    /// Returns the number of indexed games.
+    pub fn len(&self) -> usize {
+        self.games.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.games.is_empty()
+    }
+
+    /// Indexes every position of the mainline of each game, stored under the given ID,
+    /// as a new segment. Line breaks in IDs are replaced by spaces.
+    ///
+    /// Returns the number of games added.
+    pub fn add_games<I>(&mut self, games: I) -> io::Result<usize>
+    where
+        I: IntoIterator<Item = (String, GameRecord)>,
+    {
+        let first = self.games.len() as u32;
+        let mut ids = Vec::new();
+        let mut entries = Vec::new();
+        for (id, record) in games {
+            let game = first + ids.len() as u32;
+            let mut board = Board::from_position(&record.start_pos);
+            entries.push((board.zobrist_hash(), game, 0));
+            for (ply, mv) in (1u32..).zip(&record.moves) {
+                if !matches!(mv.action, Action::Move(..)) || board.apply(&mv.action).is_err() {
+                    break;
+                }
+                entries.push((board.zobrist_hash(), game, ply));
+            }
+            ids.push(id.replace(['\r', '\n'], " "));
+        }
+        if ids.is_empty() {
+            return Ok(0);
+        }
+        entries.sort_unstable();
+
+        // Game IDs are written first so that entries never refer to missing games.
+        let mut games_file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(self.dir.join(GAMES_FILE))?;
+        games_file.write_all(
+            ids.iter()
+                .map(|id| format!("{id}\n"))
+                .collect::<String>()
+                .as_bytes(),
+        )?;
+
+        let path = self
+            .dir
+            .join(format!("positions-{:06}.idx", self.segments.len()));
+        let mut writer = BufWriter::new(File::create(&path)?);
+        writer.write_all(INDEX_SEGMENT_MAGIC)?;
+        writer.write_all(&(entries.len() as u32).to_le_bytes())?;
+        for (hash, game, ply) in entries {
+            writer.write_all(&hash.to_le_bytes())?;
+            writer.write_all(&game.to_le_bytes())?;
+            writer.write_all(&ply.to_le_bytes())?;
+        }
+        writer.flush()?;
+
+        let added = ids.len();
+        self.games.extend(ids);
+        self.segments.push(path);
+        Ok(added)
+    }
+
+    /// Indexes the `.csa` files under a directory and its subdirectories, using their
+    /// paths as game IDs.
+    ///
+    /// Files which cannot be read as UTF-8 or parsed as CSA are skipped. Returns the number
+    /// of games added.
+    pub fn add_dir<P: AsRef<Path>>(&mut self, dir: P) -> io::Result<usize> {
+        let mut paths = Vec::new();
+        collect_csa_paths(dir.as_ref(), &mut paths)?;
+
+        self.add_games(paths.into_iter().filter_map(|path| {
+            let record = parse_csa(&fs::read_to_string(&path).ok()?).ok()?;
+            Some((path.display().to_string(), record))
+        }))
+    }
+
+    /// Returns the games which reached the position, ordered by game and ply.
+    pub fn search(&self, board: &Board) -> io::Result<Vec<PositionHit>> {
+        let hash = board.zobrist_hash();
+        let mut found = Vec::new();
+        for path in &self.segments {
+            search_segment(path, hash, &mut found)?;
+        }
+        found.sort_unstable();
+
+        found
+            .into_iter()
+            .map(|(game, ply)| {
+                let game_id = self.games.get(game as usize).cloned().ok_or_else(|| {
+                    io::Error::new(ErrorKind::InvalidData, "unknown game in index")
+                })?;
+                Ok(PositionHit { game_id, ply })
+            })
+            .collect()
+    }
+
+    /// Returns the games which reached the position given in SFEN.
+    pub fn search_sfen(&self, sfen: &str) -> Result<Vec<PositionHit>, UsiError> {
+        let board = Board::from_sfen(sfen)?;
+        Ok(self.search(&board)?)
+    }
+}
+
+fn collect_csa_paths(dir: &Path, paths: &mut Vec<PathBuf>) -> io::Result<()> {
+    let mut entries = fs::read_dir(dir)?
+        .map(|entry| entry.map(|e| e.path()))
+        .collect::<io::Result<Vec<_>>>()?;
+    entries.sort();
+
+    for path in entries {
+        if path.is_dir() {
+            collect_csa_paths(&path, paths)?;
+        } else if path.extension().is_some_and(|ext| ext == "csa") {
+            paths.push(path);
+        }
+    }
+
+    Ok(())
+}
+
+/// Appends the games and plies of the entries with the given hash.
+fn search_segment(path: &Path, hash: u64, found: &mut Vec<(u32, u32)>) -> io::Result<()> {
+    let mut reader = BufReader::new(File::open(path)?);
+    let mut header = [0; HEADER_SIZE as usize];
+    reader.read_exact(&mut header)?;
+    if &header[..8] != INDEX_SEGMENT_MAGIC {
+        return Err(io::Error::new(
+            ErrorKind::InvalidData,
+            "not an index segment",
+        ));
+    }
+    let len = u32::from_le_bytes(header[8..].try_into().unwrap()) as u64;
+
+    let mut read_entry = |i: u64| -> io::Result<(u64, u32, u32)> {
+        let mut buf = [0; ENTRY_SIZE as usize];
+        reader.seek(SeekFrom::Start(HEADER_SIZE + i * ENTRY_SIZE))?;
+        reader.read_exact(&mut buf)?;
+        Ok((
+            u64::from_le_bytes(buf[..8].try_into().unwrap()),
+            u32::from_le_bytes(buf[8..12].try_into().unwrap()),
+            u32::from_le_bytes(buf[12..].try_into().unwrap()),
+        ))
+    };
+
+    // Finds the first entry with the hash.
+    let (mut low, mut high) = (0, len);
+    while low < high {
+        let mid = low + (high - low) / 2;
+        if read_entry(mid)?.0 < hash {
+            low = mid + 1;
+        } else {
+            high = mid;
+        }
+    }
+
+    for i in low..len {
+        let (entry_hash, game, ply) = read_entry(i)?;
+        if entry_hash != hash {
+            break;
+        }
+        found.push((game, ply));
+    }
+
+    Ok(())
+}
+
+////////////////////////////////////////////////////////////////////////////////
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::usi::STARTPOS_SFEN;
+
+    fn game(moves: &str) -> GameRecord {
+        parse_csa(&format!("V2.2\nPI\n+\n{moves}")).unwrap()
+    }
+
+    #[test]
+    fn search() {
+        let dir = std::env::temp_dir().join(format!("csa-index-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+
+        let mut index = PositionIndex::open(&dir).unwrap();
+        assert!(index.is_empty());
+        let added = index
+            .add_games([
+                ("a".to_string(), game("+7776FU\n-3334FU\n+2726FU\n")),
+                ("b".to_string(), game("+2726FU\n-3334FU\n+7776FU\n%TORYO\n")),
+            ])
+            .unwrap();
+        assert_eq!(added, 2);
+
+        // Games added later go to a new segment.
+        let mut index = PositionIndex::open(&dir).unwrap();
+        index
+            .add_games([("c\nd".to_string(), game("+7776FU\n-8384FU\n"))])
+            .unwrap();
+
+        let index = PositionIndex::open(&dir).unwrap();
+        assert_eq!(index.len(), 3);
+        let hit = |game_id: &str, ply| PositionHit {
+            game_id: game_id.to_string(),
+            ply,
+        };
+
+        assert_eq!(
+            index.search_sfen(STARTPOS_SFEN).unwrap(),
+            [hit("a", 0), hit("b", 0), hit("c d", 0)]
+        );
+        assert_eq!(
+            index
+                .search_sfen("lnsgkgsnl/1r5b1/pppppp1pp/6p2/9/2P6/PP1PPPPPP/1B5R1/LNSGKGSNL b - 3")
+                .unwrap(),
+            [hit("a", 2)]
+        );
+        // Reached by transposition.
+        assert_eq!(
+            index
+                .search_sfen(
+                    "lnsgkgsnl/1r5b1/pppppp1pp/6p2/9/2P4P1/PP1PPPP1P/1B5R1/LNSGKGSNL w - 4"
+                )
+                .unwrap(),
+            [hit("a", 3), hit("b", 3)]
+        );
+        assert_eq!(index.search_sfen("9/9/9/9/9/9/9/9/9 b - 1").unwrap(), []);
+        assert!(index.search_sfen("9/9 b - 1").is_err());
+
+        let games = dir.join("games").join("2024");
+        fs::create_dir_all(&games).unwrap();
+        fs::write(games.join("e.csa"), "V2.2\nPI\n+\n+7776FU\n").unwrap();
+        fs::write(games.join("f.csa"), "not a game").unwrap();
+        fs::write(games.join("g.kif"), "V2.2\nPI\n+\n").unwrap();
+        let mut index = PositionIndex::open(dir.join("index")).unwrap();
+        assert_eq!(index.add_dir(dir.join("games")).unwrap(), 1);
+        let hits = index.search_sfen(STARTPOS_SFEN).unwrap();
+        assert_eq!(hits.len(), 1);
+        assert!(hits[0].game_id.ends_with("e.csa"));
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
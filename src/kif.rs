@@ -0,0 +1,635 @@
+use std::cmp::Reverse;
+use std::fmt::Write;
+use std::time::Duration;
+
+use crate::board::Board;
+use crate::notation::{format_move, format_special_move, NotationStyle, FILES, RANKS};
+use crate::parser::CsaError;
+use crate::value::{
+    Action, Color, GameRecord, Handicap, MoveFrom, MoveRecord, PieceType, Position, Square,
+};
+use crate::variation::Variation;
+
+const MOVES_HEADER: &str = "手数----指手---------消費時間--";
+
+/// Names of pieces in moves, including the variants used by other software.
+const PIECE_NAMES: [(&str, PieceType); 16] = [
+    ("歩", PieceType::Pawn),
+    ("香", PieceType::Lance),
+    ("桂", PieceType::Knight),
+    ("銀", PieceType::Silver),
+    ("金", PieceType::Gold),
+    ("角", PieceType::Bishop),
+    ("飛", PieceType::Rook),
+    ("玉", PieceType::King),
+    ("王", PieceType::King),
+    ("と", PieceType::ProPawn),
+    ("成香", PieceType::ProLance),
+    ("成桂", PieceType::ProKnight),
+    ("成銀", PieceType::ProSilver),
+    ("馬", PieceType::Horse),
+    ("龍", PieceType::Dragon),
+    ("竜", PieceType::Dragon),
+];
+
+/// Special moves in the order they are matched, as `反則負け` stands for both illegal moves
+/// and illegal actions.
+const SPECIAL_MOVES: [Action; 12] = [
+    Action::Toryo,
+    Action::Chudan,
+    Action::Sennichite,
+    Action::TimeUp,
+    Action::IllegalMove,
+    Action::Jishogi,
+    Action::Kachi,
+    Action::Hikiwake,
+    Action::Matta,
+    Action::Tsumi,
+    Action::Fuzumi,
+    Action::Error,
+];
+
+impl GameRecord {
+    /// Exports the record in KIF, writing the variations as `変化` sections.
+    ///
+    /// The header holds the event, the site, the opening, the players and the starting
+    /// position, which is written as a BOD diagram unless it is one of the [`Handicap`] presets.
+    pub fn to_kif(&self) -> Result<String, CsaError> {
+        let mut s = String::new();
+
+        for (key, value) in [
+            ("棋戦", &self.event),
+            ("場所", &self.site),
+            ("戦型", &self.opening),
+        ] {
+            if let Some(value) = value {
+                writeln!(s, "{key}：{value}").unwrap();
+            }
+        }
+        let board = Board::from_position(&self.start_pos);
+        match self.start_pos.handicap() {
+            Some(handicap) => writeln!(s, "手合割：{}", handicap.kanji()).unwrap(),
+            None => s.push_str(&board.to_bod()),
+        }
+        for (key, value) in [("先手", &self.black_player), ("後手", &self.white_player)] {
+            if let Some(value) = value {
+                writeln!(s, "{key}：{value}").unwrap();
+            }
+        }
+        writeln!(s, "{MOVES_HEADER}").unwrap();
+
+        write_line(
+            &mut s,
+            board,
+            1,
+            &self.moves,
+            &self.variations,
+            None,
+            [Duration::ZERO; 2],
+        )?;
+
+        Ok(s)
+    }
+}
+
+/// Writes the moves numbered from `first_ply` played on `board`, then the variations
+/// branching off from them, latest first as in other KIF software.
+fn write_line(
+    s: &mut String,
+    mut board: Board,
+    first_ply: usize,
+    moves: &[MoveRecord],
+    variations: &[Variation],
+    mut last_to: Option<Square>,
+    mut totals: [Duration; 2],
+) -> Result<(), CsaError> {
+    let mut states = Vec::with_capacity(moves.len() + 1);
+
+    for (i, record) in moves.iter().enumerate() {
+        states.push((board.clone(), last_to, totals));
+        let color = board.side_to_move();
+        let notation = format_move(&board, &record.action, last_to, NotationStyle::Kif)?;
+        board.apply(&record.action)?;
+        if let Action::Move(_, _, to, _) = record.action {
+            last_to = Some(to);
+        }
+
+        write!(
+            s,
+            "{:>4} {}",
+            first_ply + i,
+            notation.trim_start_matches(['▲', '△'])
+        )
+        .unwrap();
+        if let Some(time) = record.time {
+            totals[color as usize] += time;
+            let (secs, total) = (time.as_secs(), totals[color as usize].as_secs());
+            write!(
+                s,
+                "   ({:>2}:{:02}/{:02}:{:02}:{:02})",
+                secs / 60,
+                secs % 60,
+                total / 3600,
+                total / 60 % 60,
+                total % 60
+            )
+            .unwrap();
+        }
+        if variations.iter().any(|variation| variation.start == i) {
+            s.push('+');
+        }
+        s.push('\n');
+        for comment in &record.comments {
+            writeln!(s, "*{comment}").unwrap();
+        }
+    }
+    states.push((board, last_to, totals));
+
+    let mut sorted = variations.iter().collect::<Vec<_>>();
+    sorted.sort_by_key(|variation| Reverse(variation.start));
+    for variation in sorted {
+        // Variations out of the line are skipped, as in `GameRecord::line`.
+        let Some((board, last_to, totals)) = states.get(variation.start) else {
+            continue;
+        };
+        let ply = first_ply + variation.start;
+
+        write!(s, "\n変化：{ply}手\n").unwrap();
+        write_line(
+            s,
+            board.clone(),
+            ply,
+            &variation.moves,
+            &variation.variations,
+            *last_to,
+            *totals,
+        )?;
+    }
+
+    Ok(())
+}
+
+/// Parses a record in KIF, reading `変化` sections as variations.
+///
+/// Only the header items written by [`GameRecord::to_kif`] are read. Variations branching
+/// off from the same line are ordered by the move they replace.
+pub fn parse_kif(s: &str) -> Result<GameRecord, CsaError> {
+    let mut record = GameRecord::default();
+    let mut handicap = Handicap::Even;
+    let mut rows = Vec::new();
+    let mut hands = Vec::new();
+    let mut side_to_move = Color::Black;
+    let mut lines = vec![Line::new(1)];
+    let mut in_moves = false;
+
+    for line in s.lines() {
+        let line = line.trim_end();
+        if line.is_empty() || line.starts_with('#') || line.starts_with("まで") {
+            continue;
+        }
+
+        if let Some(comment) = line.strip_prefix('*') {
+            // Comments on the game itself are not kept.
+            if let Some(mv) = lines.last_mut().and_then(|line| line.moves.last_mut()) {
+                mv.comments.push(comment.to_string());
+            }
+        } else if line.starts_with("手数") {
+            in_moves = true;
+            record.start_pos = if rows.is_empty() {
+                Position::from_handicap(handicap)
+            } else {
+                Position {
+                    drop_pieces: Vec::new(),
+                    bulk: Some(
+                        std::mem::take(&mut rows)
+                            .try_into()
+                            .map_err(|_| CsaError::ParseError())?,
+                    ),
+                    add_pieces: std::mem::take(&mut hands),
+                    side_to_move,
+                }
+            };
+        } else if let Some(ply) = line
+            .strip_prefix("変化：")
+            .and_then(|ply| ply.strip_suffix('手'))
+        {
+            let ply = ply.trim().parse().map_err(|_| CsaError::ParseError())?;
+            start_variation(&mut lines, ply)?;
+        } else if in_moves {
+            let mv = parse_move_line(line, &lines, record.start_pos.side_to_move)?;
+            lines.last_mut().unwrap().moves.push(mv);
+        } else if let Some(row) = line.strip_prefix('|') {
+            rows.push(parse_bod_row(row)?);
+        } else if line == "後手番" || line == "上手番" {
+            side_to_move = Color::White;
+        } else if let Some((key, value)) = line.split_once('：') {
+            let value = value.trim();
+            match key {
+                "先手" | "下手" => record.black_player = Some(value.to_string()),
+                "後手" | "上手" => record.white_player = Some(value.to_string()),
+                "棋戦" => record.event = Some(value.to_string()),
+                "場所" => record.site = Some(value.to_string()),
+                "戦型" => record.opening = Some(value.to_string()),
+                "手合割" => {
+                    handicap = Handicap::ALL
+                        .into_iter()
+                        .find(|h| h.kanji() == value)
+                        .ok_or(CsaError::ParseError())?;
+                }
+                "先手の持駒" | "下手の持駒" => {
+                    parse_bod_hand(Color::Black, value, &mut hands)?
+                }
+                "後手の持駒" | "上手の持駒" => {
+                    parse_bod_hand(Color::White, value, &mut hands)?
+                }
+                _ => {}
+            }
+        }
+    }
+
+    if !in_moves {
+        return Err(CsaError::ParseError());
+    }
+    while lines.len() > 1 {
+        end_line(&mut lines);
+    }
+    let mainline = lines.pop().unwrap().into_variation(1);
+    record.moves = mainline.moves;
+    record.variations = mainline.variations;
+
+    Ok(record)
+}
+
+/// A line of moves being read, starting from the move numbered `first_ply`.
+struct Line {
+    first_ply: usize,
+    moves: Vec<MoveRecord>,
+    variations: Vec<Variation>,
+}
+
+impl Line {
+    fn new(first_ply: usize) -> Line {
+        Line {
+            first_ply,
+            moves: Vec::new(),
+            variations: Vec::new(),
+        }
+    }
+
+    fn into_variation(mut self, parent_first_ply: usize) -> Variation {
+        self.variations.sort_by_key(|variation| variation.start);
+        Variation {
+            start: self.first_ply - parent_first_ply,
+            moves: self.moves,
+            variations: self.variations,
+        }
+    }
+}
+
+/// Ends the lines branching off at or after the move `ply`, then starts a variation replacing it.
+fn start_variation(lines: &mut Vec<Line>, ply: usize) -> Result<(), CsaError> {
+    while lines.len() > 1 && lines.last().unwrap().first_ply >= ply {
+        end_line(lines);
+    }
+
+    let parent = lines.last().unwrap();
+    if ply < parent.first_ply || ply > parent.first_ply + parent.moves.len() {
+        return Err(CsaError::ParseError());
+    }
+    lines.push(Line::new(ply));
+
+    Ok(())
+}
+
+fn end_line(lines: &mut Vec<Line>) {
+    let line = lines.pop().unwrap();
+    let parent = lines.last_mut().unwrap();
+    parent
+        .variations
+        .push(line.into_variation(parent.first_ply));
+}
+
+/// Returns the destination of the move `ply` in the line being read.
+fn destination(lines: &[Line], ply: usize) -> Option<Square> {
+    let line = lines.iter().rev().find(|line| line.first_ply <= ply)?;
+    match line.moves.get(ply - line.first_ply)?.action {
+        Action::Move(_, _, to, _) => Some(to),
+        _ => None,
+    }
+}
+
+/// Parses a line such as `   3 ２二角成(88)   ( 0:05/00:00:06)+`.
+fn parse_move_line(line: &str, lines: &[Line], first: Color) -> Result<MoveRecord, CsaError> {
+    let line = line.trim_start_matches(' ').trim_end_matches('+');
+    let (ply, rest) = line.split_once(' ').ok_or(CsaError::ParseError())?;
+    let ply = ply.parse::<usize>().map_err(|_| CsaError::ParseError())?;
+    let current = lines.last().unwrap();
+    if ply != current.first_ply + current.moves.len() {
+        return Err(CsaError::ParseError());
+    }
+
+    let (text, time) = rest
+        .trim_start_matches(' ')
+        .split_once(' ')
+        .unwrap_or((rest.trim_start_matches(' '), ""));
+    let color = if ply % 2 == 1 { first } else { first.flip() };
+    let last_to = ply.checked_sub(1).and_then(|ply| destination(lines, ply));
+    let action = parse_move(text, color, last_to).ok_or(CsaError::ParseError())?;
+
+    let time = match time.trim() {
+        "" => None,
+        time => Some(parse_time(time).ok_or(CsaError::ParseError())?),
+    };
+
+    Ok(MoveRecord {
+        action,
+        time,
+        comments: Vec::new(),
+    })
+}
+
+fn parse_move(text: &str, color: Color, last_to: Option<Square>) -> Option<Action> {
+    if let Some(action) = SPECIAL_MOVES
+        .iter()
+        .find(|action| format_special_move(action, NotationStyle::Kif) == text)
+    {
+        return Some(*action);
+    }
+
+    let (to, rest) = match text.strip_prefix('同') {
+        Some(rest) => (last_to?, rest.trim_start_matches('　')),
+        None => {
+            let file = FILES.iter().position(|f| text.starts_with(f))?;
+            let rest = &text[FILES[file].len()..];
+            let rank = RANKS.iter().position(|r| rest.starts_with(r))?;
+            (
+                Square::new(file as u8 + 1, rank as u8 + 1)?,
+                &rest[RANKS[rank].len()..],
+            )
+        }
+    };
+    let (moved, rest) = PIECE_NAMES
+        .iter()
+        .find_map(|&(name, pt)| Some((pt, rest.strip_prefix(name)?)))?;
+    let (pt, rest) = match rest.strip_prefix('成') {
+        Some(rest) => (moved.promote()?, rest),
+        None => (moved, rest.strip_prefix("不成").unwrap_or(rest)),
+    };
+
+    let from = if rest == "打" {
+        MoveFrom::Hand
+    } else {
+        let origin = rest.strip_prefix('(')?.strip_suffix(')')?.as_bytes();
+        let [file @ b'1'..=b'9', rank @ b'1'..=b'9'] = *origin else {
+            return None;
+        };
+        MoveFrom::Board(Square::new(file - b'0', rank - b'0')?)
+    };
+
+    Some(Action::Move(color, from, to, pt))
+}
+
+/// Parses the time spent on a move from `( 0:05/00:00:06)`.
+fn parse_time(s: &str) -> Option<Duration> {
+    let s = s.strip_prefix('(')?.strip_suffix(')')?;
+    let (spent, _) = s.split_once('/')?;
+    let (minutes, seconds) = spent.trim().split_once(':')?;
+    let secs = minutes.parse::<u64>().ok()? * 60 + seconds.parse::<u64>().ok()?;
+
+    Some(Duration::from_secs(secs))
+}
+
+fn bod_piece(c: char) -> Option<PieceType> {
+    match c {
+        '杏' => Some(PieceType::ProLance),
+        '圭' => Some(PieceType::ProKnight),
+        '全' => Some(PieceType::ProSilver),
+        c => PIECE_NAMES
+            .iter()
+            .find(|(name, _)| name.chars().eq([c]))
+            .map(|&(_, pt)| pt),
+    }
+}
+
+/// Parses a row of a BOD diagram such as `v香v桂 ・ ・ ・ ・ ・v桂v香|一`.
+fn parse_bod_row(row: &str) -> Result<[Option<(Color, PieceType)>; 9], CsaError> {
+    let (cells, _) = row.split_once('|').ok_or(CsaError::ParseError())?;
+    let chars = cells.chars().collect::<Vec<_>>();
+    if chars.len() != 18 {
+        return Err(CsaError::ParseError());
+    }
+
+    let mut parsed = [None; 9];
+    for (cell, piece) in chars.chunks(2).zip(parsed.iter_mut()) {
+        *piece = match *cell {
+            [_, '・'] => None,
+            [mark, c] => {
+                let color = if mark == 'v' {
+                    Color::White
+                } else {
+                    Color::Black
+                };
+                Some((color, bod_piece(c).ok_or(CsaError::ParseError())?))
+            }
+            _ => unreachable!(),
+        };
+    }
+
+    Ok(parsed)
+}
+
+/// Parses the pieces in hand of a BOD diagram such as `角　歩十二　`.
+fn parse_bod_hand(
+    color: Color,
+    s: &str,
+    hands: &mut Vec<(Color, Option<Square>, PieceType)>,
+) -> Result<(), CsaError> {
+    if s == "なし" {
+        return Ok(());
+    }
+
+    for piece in s.split(['　', ' ']).filter(|piece| !piece.is_empty()) {
+        let mut chars = piece.chars();
+        let pt = chars
+            .next()
+            .and_then(bod_piece)
+            .ok_or(CsaError::ParseError())?;
+        let count = match chars.as_str() {
+            "" => 1,
+            count => kanji_number(count).ok_or(CsaError::ParseError())?,
+        };
+        hands.extend(std::iter::repeat_n((color, None, pt), count));
+    }
+
+    Ok(())
+}
+
+/// Parses a number up to 99 in kanji numerals.
+fn kanji_number(s: &str) -> Option<usize> {
+    let digit = |s: &str| RANKS.iter().position(|&r| r == s).map(|i| i + 1);
+
+    match s.split_once('十') {
+        Some((tens, ones)) => {
+            let tens = if tens.is_empty() { 1 } else { digit(tens)? };
+            let ones = if ones.is_empty() { 0 } else { digit(ones)? };
+            Some(tens * 10 + ones)
+        }
+        None => digit(s),
+    }
+}
+
+////////////////////////////////////////////////////////////////////////////////
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::parse_csa;
+
+    fn moves(csa: &str) -> Vec<MoveRecord> {
+        parse_csa(&format!("PI\n+\n{csa}")).unwrap().moves
+    }
+
+    #[test]
+    fn round_trip() {
+        let csa = "\
+N+Black
+N-White
+$EVENT:Test
+$OPENING:Kakugawari
+PI
++
++7776FU
+T1
+-3334FU
+T2
+'** 30 +8822UM
++8822UM
+T61
+-3122GI
+T3
++0045KA
+T4
+%TORYO
+";
+        let mut g = parse_csa(csa).unwrap();
+        g.add_variation(1, moves("-8384FU\n"));
+        let v = g.add_variation(2, moves("+2726FU\n-8384FU\n"));
+        v.add_variation(1, moves("-4132KI\n"));
+
+        let kif = g.to_kif().unwrap();
+        assert_eq!(
+            kif,
+            "\
+棋戦：Test
+戦型：Kakugawari
+手合割：平手
+先手：Black
+後手：White
+手数----指手---------消費時間--
+   1 ７六歩(77)   ( 0:01/00:00:01)
+   2 ３四歩(33)   ( 0:02/00:00:02)+
+*** 30 +8822UM
+   3 ２二角成(88)   ( 1:01/00:01:02)+
+   4 同　銀(31)   ( 0:03/00:00:05)
+   5 ４五角打   ( 0:04/00:01:06)
+   6 投了
+
+変化：3手
+   3 ２六歩(27)
+   4 ８四歩(83)+
+
+変化：4手
+   4 ３二金(41)
+
+変化：2手
+   2 ８四歩(83)
+"
+        );
+        assert_eq!(parse_kif(&kif).unwrap(), g);
+    }
+
+    #[test]
+    fn bod() {
+        let csa = "\
+P1 *  *  *  * -OU *  *  *  * 
+P2 *  *  *  *  *  *  *  *  * 
+P3 *  *  *  * +FU *  *  *  * 
+P4 *  *  *  *  *  *  *  *  * 
+P5 *  *  *  *  *  *  *  *  * 
+P6 *  *  *  *  *  *  *  *  * 
+P7 *  *  *  *  *  *  *  *  * 
+P8 *  *  *  *  *  *  *  *  * 
+P9 *  *  *  *  *  *  *  *  * 
+P+00KI
+P-00AL
+-
+-5142OU
++0052KI
+";
+        let g = parse_csa(csa).unwrap();
+        let parsed = parse_kif(&g.to_kif().unwrap()).unwrap();
+
+        assert_eq!(
+            Board::from_position(&parsed.start_pos),
+            Board::from_position(&g.start_pos)
+        );
+        assert_eq!(parsed.moves, g.moves);
+    }
+
+    #[test]
+    fn other_software() {
+        let kif = "\
+# ---- Kifu for Windows ----
+開始日時：2024/01/01
+手合割：香落ち
+上手：White
+下手：Black
+手数----指手---------消費時間--
+*game comment
+   1 ３四歩(33)   ( 0:10/00:00:10)
+   2 ７六歩(77)   ( 0:05/00:00:05)+
+   3 ８八角成(22)   ( 0:10/00:00:20)
+   4 同　銀(79)   ( 0:05/00:00:10)
+   5 ３三角打   ( 0:10/00:00:30)
+*comment
+   6 中断
+まで5手で中断
+
+変化：2手
+   2 ２六歩(27)   ( 0:05/00:00:05)
+";
+        let g = parse_kif(kif).unwrap();
+
+        assert_eq!(g.start_pos, Position::from_handicap(Handicap::Lance));
+        assert_eq!(g.black_player.as_deref(), Some("Black"));
+        assert_eq!(g.white_player.as_deref(), Some("White"));
+        assert_eq!(g.moves.len(), 6);
+        assert_eq!(
+            g.moves[3].action,
+            Action::Move(
+                Color::Black,
+                MoveFrom::Board(Square::new(7, 9).unwrap()),
+                Square::new(8, 8).unwrap(),
+                PieceType::Silver
+            )
+        );
+        assert_eq!(g.moves[3].time, Some(Duration::from_secs(5)));
+        assert_eq!(g.moves[4].comments, ["comment"]);
+        assert_eq!(g.moves[5].action, Action::Chudan);
+        assert_eq!(
+            parse_move("１二竜(13)", Color::Black, None),
+            Some(Action::Move(
+                Color::Black,
+                MoveFrom::Board(Square::new(1, 3).unwrap()),
+                Square::new(1, 2).unwrap(),
+                PieceType::Dragon
+            ))
+        );
+        assert_eq!(g.variations[0].start, 1);
+        assert_eq!(g.line(&[0]).unwrap().len(), 2);
+
+        assert!(parse_kif("手合割：平手\n").is_err());
+        assert!(parse_kif("手数\n   2 ７六歩(77)\n").is_err());
+        assert!(parse_kif("手数\n   1 ７六歩(77)\n変化：3手\n").is_err());
+    }
+}
@@ -9,7 +9,7 @@
 //!
 //! ```
 //! use std::time::Duration;
-//! use csa::{parse_csa, Action, Color, GameRecord, MoveRecord, PieceType, Square};
+//! use csa::{parse_csa, Action, Color, GameRecord, MoveFrom, MoveRecord, PieceType, Square};
 //!
 //! let csa_str = "\
 //! V2.2
@@ -27,8 +27,9 @@
 //! assert_eq!(game.white_player, Some("YONENAGA".to_string()));
 //! assert_eq!(game.event, Some("13th World Computer Shogi Championship".to_string()));
 //! assert_eq!(game.moves[0],  MoveRecord{
-//!     action: Action::Move(Color::Black, Square::new(2, 7), Square::new(2, 6), PieceType::Pawn),
-//!     time: Some(Duration::from_secs(12))
+//!     action: Action::Move(Color::Black, MoveFrom::Board(Square::new(2, 7).unwrap()), Square::new(2, 6).unwrap(), PieceType::Pawn),
+//!     time: Some(Duration::from_secs(12)),
+//!     comments: vec![],
 //! });
 //! ```
 //!
@@ -36,7 +37,7 @@
 //!
 //! ```
 //! use std::time::Duration;
-//! use csa::{ Action, Color, GameRecord, MoveRecord, PieceType, Square};
+//! use csa::{Action, Color, GameRecord, MoveFrom, MoveRecord, PieceType, Square};
 //!
 //! let mut g = GameRecord::default();
 //! g.black_player = Some("NAKAHARA".to_string());
@@ -45,15 +46,17 @@
 //! g.moves.push(MoveRecord {
 //!     action: Action::Move(
 //!         Color::Black,
-//!         Square::new(2, 7),
-//!         Square::new(2, 6),
+//!         MoveFrom::Board(Square::new(2, 7).unwrap()),
+//!         Square::new(2, 6).unwrap(),
 //!         PieceType::Pawn,
 //!     ),
 //!     time: Some(Duration::from_secs(5)),
+//!     comments: vec![],
 //! });
 //! g.moves.push(MoveRecord {
 //!     action: Action::Toryo,
 //!     time: None,
+//!     comments: vec![],
 //! });
 //!
 //! let csa_str = "\
@@ -73,8 +76,43 @@
 //!
 //! [CSA]: http://www2.computer-shogi.org/protocol/record_v22.html
 
+pub mod analytics;
+#[cfg(feature = "animation")]
+pub mod animation;
+pub mod board;
+pub mod book;
+pub mod clock;
+// Only inherent methods of `Board` and `GameRecord`, leaving nothing to re-export.
+mod diagram;
+mod html;
+pub mod index;
+pub mod kif;
+pub mod notation;
 pub mod parser;
+pub mod protocol;
+pub mod server;
+#[cfg(feature = "svg")]
+pub mod svg;
+pub mod usi;
+pub mod validation;
 pub mod value;
+pub mod variation;
 
+pub use analytics::*;
+#[cfg(feature = "animation")]
+pub use animation::*;
+pub use board::*;
+pub use book::*;
+pub use clock::*;
+pub use index::*;
+pub use kif::*;
+pub use notation::*;
 pub use parser::*;
+pub use protocol::*;
+pub use server::*;
+#[cfg(feature = "svg")]
+pub use svg::*;
+pub use usi::*;
+pub use validation::*;
 pub use value::*;
+pub use variation::*;
@@ -0,0 +1,414 @@
+use crate::board::Board;
+use crate::parser::CsaError;
+use crate::value::{Action, Color, GameRecord, MoveFrom, PieceType, Square};
+
+pub(crate) const FILES: [&str; 9] = ["１", "２", "３", "４", "５", "６", "７", "８", "９"];
+pub(crate) const RANKS: [&str; 9] = ["一", "二", "三", "四", "五", "六", "七", "八", "九"];
+
+/// Styles of human-readable move notation.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum NotationStyle {
+    /// Japanese notation with the origin square, e.g. `▲７六歩(77)` as in KIF.
+    Kif,
+    /// Japanese notation disambiguated by relative movement, e.g. `▲５八金左` as in KI2.
+    Ki2,
+    /// Western (Hodges) notation, e.g. `P-7f` or `Sx6c+`.
+    Western,
+}
+
+impl GameRecord {
+    /// Formats every move record in the given style, replaying the moves from the starting position.
+    pub fn notation(&self, style: NotationStyle) -> Result<Vec<String>, CsaError> {
+        let mut board = Board::from_position(&self.start_pos);
+        let mut last_to = None;
+
+        self.moves
+            .iter()
+            .map(|record| {
+                let notation = format_move(&board, &record.action, last_to, style)?;
+                board.apply(&record.action)?;
+                if let Action::Move(_, _, to, _) = record.action {
+                    last_to = Some(to);
+                }
+
+                Ok(notation)
+            })
+            .collect()
+    }
+}
+
+/// Formats an action played on `board`.
+///
+/// `last_to` is the destination of the previous move, used for `同` in Japanese notations.
+pub fn format_move(
+    board: &Board,
+    action: &Action,
+    last_to: Option<Square>,
+    style: NotationStyle,
+) -> Result<String, CsaError> {
+    let Action::Move(color, from, to, pt) = *action else {
+        return Ok(format_special_move(action, style).to_string());
+    };
+
+    // The movement of the piece is checked, but not whether the move leaves the king in check.
+    let moved = match from {
+        MoveFrom::Hand if board.hand(color, pt) > 0 && board.get(to).is_none() => pt,
+        MoveFrom::Board(sq) => match board.get(sq) {
+            Some((c, p))
+                if c == color
+                    && (p == pt || p.promote() == Some(pt))
+                    && board.reachable_squares(sq).contains(&to) =>
+            {
+                p
+            }
+            _ => return Err(CsaError::InvalidMove(*action)),
+        },
+        MoveFrom::Hand => return Err(CsaError::InvalidMove(*action)),
+    };
+    let mv = MoveInfo {
+        color,
+        from,
+        to,
+        moved,
+        promoted: moved != pt,
+        can_promote: match from {
+            MoveFrom::Board(sq) => {
+                moved.can_promote() && (sq.in_promotion_zone(color) || to.in_promotion_zone(color))
+            }
+            MoveFrom::Hand => false,
+        },
+        capture: board.get(to).is_some(),
+        rivals: rivals(board, color, moved, from, to),
+    };
+
+    Ok(match style {
+        NotationStyle::Kif => format_kif(&mv, last_to),
+        NotationStyle::Ki2 => format_ki2(&mv, last_to),
+        NotationStyle::Western => format_western(&mv),
+    })
+}
+
+struct MoveInfo {
+    color: Color,
+    from: MoveFrom,
+    to: Square,
+    /// Piece type before the move.
+    moved: PieceType,
+    promoted: bool,
+    can_promote: bool,
+    capture: bool,
+    /// Other pieces of the same type which can move to the destination.
+    rivals: Vec<Square>,
+}
+
+fn rivals(board: &Board, color: Color, pt: PieceType, from: MoveFrom, to: Square) -> Vec<Square> {
+    board
+        .pieces()
+        .filter(|&(sq, c, p)| c == color && p == pt && from != MoveFrom::Board(sq))
+        .filter(|&(sq, _, _)| board.reachable_squares(sq).contains(&to))
+        .map(|(sq, _, _)| sq)
+        .collect()
+}
+
+fn japanese_prefix(mv: &MoveInfo, last_to: Option<Square>) -> String {
+    let mark = match mv.color {
+        Color::Black => "▲",
+        Color::White => "△",
+    };
+    let name = mv.moved.kanji().unwrap_or_default();
+
+    if last_to == Some(mv.to) {
+        let space = if name.chars().count() == 1 { "　" } else { "" };
+        format!("{mark}同{space}{name}")
+    } else {
+        format!(
+            "{mark}{}{}{name}",
+            FILES[mv.to.file() as usize - 1],
+            RANKS[mv.to.rank() as usize - 1]
+        )
+    }
+}
+
+fn japanese_promotion(mv: &MoveInfo) -> &'static str {
+    if mv.promoted {
+        "成"
+    } else if mv.can_promote {
+        "不成"
+    } else {
+        ""
+    }
+}
+
+fn format_kif(mv: &MoveInfo, last_to: Option<Square>) -> String {
+    let origin = match mv.from {
+        MoveFrom::Board(sq) => format!("({sq})"),
+        MoveFrom::Hand => "打".to_string(),
+    };
+
+    format!(
+        "{}{}{origin}",
+        japanese_prefix(mv, last_to),
+        japanese_promotion(mv)
+    )
+}
+
+fn format_ki2(mv: &MoveInfo, last_to: Option<Square>) -> String {
+    let relative = match mv.from {
+        MoveFrom::Hand if mv.rivals.is_empty() => "",
+        MoveFrom::Hand => "打",
+        MoveFrom::Board(from) => relative_movement(mv, from),
+    };
+
+    format!(
+        "{}{relative}{}",
+        japanese_prefix(mv, last_to),
+        japanese_promotion(mv)
+    )
+}
+
+/// Returns `上`, `引` or `寄` for the movement from `from` to `to`.
+fn direction(color: Color, from: Square, to: Square) -> &'static str {
+    let from_rank = from.relative_rank(color);
+    let to_rank = to.relative_rank(color);
+
+    match from_rank.cmp(&to_rank) {
+        std::cmp::Ordering::Greater => "上",
+        std::cmp::Ordering::Less => "引",
+        std::cmp::Ordering::Equal => "寄",
+    }
+}
+
+/// Returns the file counted from the right side of the given color.
+fn relative_file(color: Color, sq: Square) -> u8 {
+    match color {
+        Color::Black => sq.file(),
+        Color::White => 10 - sq.file(),
+    }
+}
+
+/// Returns `右` or `左` if `from` is the rightmost or the leftmost among `others`.
+fn side(color: Color, from: Square, others: &[Square]) -> Option<&'static str> {
+    let file = relative_file(color, from);
+    if others.iter().all(|&sq| relative_file(color, sq) > file) {
+        Some("右")
+    } else if others.iter().all(|&sq| relative_file(color, sq) < file) {
+        Some("左")
+    } else {
+        None
+    }
+}
+
+fn relative_movement(mv: &MoveInfo, from: Square) -> &'static str {
+    if mv.rivals.is_empty() {
+        return "";
+    }
+
+    let dir = direction(mv.color, from, mv.to);
+    let same_dir = mv
+        .rivals
+        .iter()
+        .copied()
+        .filter(|&sq| direction(mv.color, sq, mv.to) == dir)
+        .collect::<Vec<_>>();
+    if same_dir.is_empty() {
+        return dir;
+    }
+
+    let is_ranging = matches!(mv.moved, PieceType::Horse | PieceType::Dragon);
+    if !is_ranging && dir == "上" && from.file() == mv.to.file() {
+        return "直";
+    }
+    if let Some(side) = side(mv.color, from, &same_dir) {
+        return side;
+    }
+
+    match (side(mv.color, from, &mv.rivals), dir) {
+        (Some("右"), "上") => "右上",
+        (Some("右"), "引") => "右引",
+        (Some("右"), _) => "右寄",
+        (Some(_), "上") => "左上",
+        (Some(_), "引") => "左引",
+        (Some(_), _) => "左寄",
+        (None, dir) => dir,
+    }
+}
+
+fn western_square(sq: Square) -> String {
+    format!("{}{}", sq.file(), (b'a' + sq.rank() - 1) as char)
+}
+
+fn format_western(mv: &MoveInfo) -> String {
+    let prefix = if mv.moved.is_promoted() { "+" } else { "" };
+    let letter = mv.moved.usi_char().unwrap_or('?');
+    let to = western_square(mv.to);
+
+    match mv.from {
+        MoveFrom::Hand => format!("{letter}*{to}"),
+        MoveFrom::Board(from) => {
+            let origin = if mv.rivals.is_empty() {
+                String::new()
+            } else {
+                western_square(from)
+            };
+            let separator = if mv.capture { "x" } else { "-" };
+            let promotion = if mv.promoted {
+                "+"
+            } else if mv.can_promote {
+                "="
+            } else {
+                ""
+            };
+
+            format!("{prefix}{letter}{origin}{separator}{to}{promotion}")
+        }
+    }
+}
+
+pub(crate) fn format_special_move(action: &Action, style: NotationStyle) -> &'static str {
+    let (japanese, western) = match *action {
+        Action::Move(..) => ("", ""),
+        Action::Toryo => ("投了", "Resigns"),
+        Action::Chudan => ("中断", "Interrupted"),
+        Action::Sennichite => ("千日手", "Repetition"),
+        Action::TimeUp => ("切れ負け", "Time up"),
+        Action::IllegalMove => ("反則負け", "Illegal move"),
+        Action::IllegalAction(_) => ("反則負け", "Illegal action"),
+        Action::Jishogi => ("持将棋", "Impasse"),
+        Action::Kachi => ("入玉勝ち", "Win declaration"),
+        Action::Hikiwake => ("引き分け", "Draw"),
+        Action::Matta => ("待った", "Matta"),
+        Action::Tsumi => ("詰み", "Checkmate"),
+        Action::Fuzumi => ("不詰", "No checkmate"),
+        Action::Error => ("エラー", "Error"),
+    };
+
+    match style {
+        NotationStyle::Kif | NotationStyle::Ki2 => japanese,
+        NotationStyle::Western => western,
+    }
+}
+
+////////////////////////////////////////////////////////////////////////////////
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::value::Position;
+
+    fn sq(file: u8, rank: u8) -> Square {
+        Square::new(file, rank).unwrap()
+    }
+
+    fn format(board: &Board, csa: &str, style: NotationStyle) -> String {
+        let record = crate::parse_csa(&format!("PI\n+\n{csa}\n")).unwrap();
+        format_move(board, &record.moves[0].action, None, style).unwrap()
+    }
+
+    #[test]
+    fn game_notation() {
+        let csa = "\
+PI
++
++7776FU
+-3334FU
++8822UM
+-3122GI
++0045KA
+-2233GI
++4563KA
+%TORYO
+";
+        let g = crate::parse_csa(csa).unwrap();
+
+        assert_eq!(
+            g.notation(NotationStyle::Kif).unwrap(),
+            vec![
+                "▲７六歩(77)",
+                "△３四歩(33)",
+                "▲２二角成(88)",
+                "△同　銀(31)",
+                "▲４五角打",
+                "△３三銀(22)",
+                "▲６三角不成(45)",
+                "投了"
+            ]
+        );
+        assert_eq!(
+            g.notation(NotationStyle::Ki2).unwrap(),
+            vec![
+                "▲７六歩",
+                "△３四歩",
+                "▲２二角成",
+                "△同　銀",
+                "▲４五角",
+                "△３三銀",
+                "▲６三角不成",
+                "投了"
+            ]
+        );
+        assert_eq!(
+            g.notation(NotationStyle::Western).unwrap(),
+            vec!["P-7f", "P-3d", "Bx2b+", "Sx2b", "B*4e", "S-3c", "Bx6c=", "Resigns"]
+        );
+    }
+
+    #[test]
+    fn invalid_move() {
+        for csa in [
+            // A pawn moving two squares.
+            "PI\n+\n+7775FU\n",
+            // No piece on the origin.
+            "PI\n+\n+5554FU\n",
+            // A drop without the piece in hand.
+            "PI\n+\n+0055KA\n",
+        ] {
+            let g = crate::parse_csa(csa).unwrap();
+            assert!(g.notation(NotationStyle::Kif).is_err(), "{csa}");
+        }
+    }
+
+    #[test]
+    fn promoted_pieces() {
+        let mut board = Board::default();
+        board.set(sq(5, 5), Some((Color::Black, PieceType::ProSilver)));
+        board.set(sq(2, 2), Some((Color::White, PieceType::Dragon)));
+
+        assert_eq!(
+            format(&board, "+5544NG", NotationStyle::Kif),
+            "▲４四成銀(55)"
+        );
+        assert_eq!(format(&board, "+5544NG", NotationStyle::Western), "+S-4d");
+        assert_eq!(format(&board, "-2228RY", NotationStyle::Western), "+R-2h");
+    }
+
+    #[test]
+    fn disambiguation() {
+        let board = Board::from_position(&Position::default());
+
+        assert_eq!(format(&board, "+6958KI", NotationStyle::Ki2), "▲５八金左");
+        assert_eq!(format(&board, "+4958KI", NotationStyle::Ki2), "▲５八金右");
+        assert_eq!(format(&board, "+6958KI", NotationStyle::Western), "G6i-5h");
+        assert_eq!(format(&board, "+6978KI", NotationStyle::Ki2), "▲７八金");
+        assert_eq!(format(&board, "-4152KI", NotationStyle::Ki2), "△５二金左");
+
+        let mut board = Board::default();
+        board.set(sq(4, 9), Some((Color::Black, PieceType::Gold)));
+        board.set(sq(5, 8), Some((Color::Black, PieceType::Gold)));
+        assert_eq!(format(&board, "+4948KI", NotationStyle::Ki2), "▲４八金上");
+        assert_eq!(format(&board, "+5848KI", NotationStyle::Ki2), "▲４八金寄");
+
+        board.set(sq(3, 9), Some((Color::Black, PieceType::Gold)));
+        board.set(sq(5, 9), Some((Color::Black, PieceType::Gold)));
+        board.set(sq(5, 8), None);
+        assert_eq!(format(&board, "+4948KI", NotationStyle::Ki2), "▲４八金直");
+        assert_eq!(format(&board, "+3948KI", NotationStyle::Ki2), "▲４八金右");
+        assert_eq!(format(&board, "+5948KI", NotationStyle::Ki2), "▲４八金左");
+
+        let mut board = Board::default();
+        board.set(sq(5, 5), Some((Color::Black, PieceType::Silver)));
+        board.set_hand(Color::Black, PieceType::Silver, 1);
+        assert_eq!(format(&board, "+0044GI", NotationStyle::Ki2), "▲４四銀打");
+        assert_eq!(format(&board, "+0043GI", NotationStyle::Ki2), "▲４三銀");
+        assert_eq!(format(&board, "+0044GI", NotationStyle::Western), "S*4d");
+    }
+}
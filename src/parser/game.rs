@@ -1,17 +1,18 @@
 use nom::branch::alt;
-use nom::bytes::complete::{is_a, is_not, tag, take};
-use nom::character::complete::{anychar, digit1, one_of};
-use nom::combinator::{map, map_res, opt, value};
-use nom::multi::{count, many0, separated_list0};
-use nom::sequence::{delimited, pair, preceded, separated_pair, terminated, tuple};
+use nom::bytes::complete::{is_a, is_not, tag, take, take_till};
+use nom::character::complete::{anychar, digit1, one_of, space0, space1};
+use nom::combinator::{map, map_res, opt, recognize, value};
+use nom::error::{Error, ErrorKind};
+use nom::multi::{count, many0, many1, separated_list0};
+use nom::sequence::{delimited, pair, preceded, terminated, tuple};
 use nom::*;
 use std::str;
 use std::time::Duration;
 
-use super::time::{datetime, timelimit};
+use super::time::{datetime, timelimit, timelimit_v3};
 use crate::value::*;
 
-fn line_sep(input: &[u8]) -> IResult<&[u8], &[u8]> {
+pub(crate) fn line_sep(input: &[u8]) -> IResult<&[u8], &[u8]> {
     is_a("\r\n,")(input)
 }
 
@@ -20,14 +21,14 @@ fn not_line_sep(input: &[u8]) -> IResult<&[u8], &[u8]> {
 }
 
 fn comment(input: &[u8]) -> IResult<&[u8], &[u8]> {
-    preceded(tag("'"), not_line_sep)(input)
+    preceded(tag("'"), take_till(|c| c == b'\r' || c == b'\n'))(input)
 }
 
 fn comment_line(input: &[u8]) -> IResult<&[u8], &[u8]> {
     terminated(comment, line_sep)(input)
 }
 
-fn color(input: &[u8]) -> IResult<&[u8], Color> {
+pub(crate) fn color(input: &[u8]) -> IResult<&[u8], Color> {
     map(one_of("+-"), |s| match s {
         '+' => Color::Black,
         _ => Color::White,
@@ -112,14 +113,31 @@ fn one_digit(input: &[u8]) -> IResult<&[u8], u8> {
     })(input)
 }
 
+/// Parses a square on the board.
+///
+/// Digits outside 1..=9 fail with `ErrorKind::Verify` at the square without backtracking,
+/// so that [`parse_csa`](super::parse_csa) can report it.
 fn square(input: &[u8]) -> IResult<&[u8], Square> {
-    map(tuple((one_digit, one_digit)), |(file, rank)| {
-        Square::new(file, rank)
-    })(input)
+    let (rest, (file, rank)) = tuple((one_digit, one_digit))(input)?;
+    match Square::new(file, rank) {
+        Some(sq) => Ok((rest, sq)),
+        None => Err(Err::Failure(Error::new(input, ErrorKind::Verify))),
+    }
+}
+
+fn hand(input: &[u8]) -> IResult<&[u8], &[u8]> {
+    tag("00")(input)
+}
+
+fn move_from(input: &[u8]) -> IResult<&[u8], MoveFrom> {
+    alt((value(MoveFrom::Hand, hand), map(square, MoveFrom::Board)))(input)
 }
 
 fn version(input: &[u8]) -> IResult<&[u8], &[u8]> {
-    preceded(tag("V"), alt((tag("2.1"), tag("2.2"), tag("2"))))(input)
+    preceded(
+        tag("V"),
+        alt((tag("2.1"), tag("2.2"), tag("2"), tag("3.0"))),
+    )(input)
 }
 
 fn black_player(input: &[u8]) -> IResult<&[u8], &[u8]> {
@@ -144,15 +162,24 @@ fn game_timelimit_attr(input: &[u8]) -> IResult<&[u8], GameAttribute> {
     map(timelimit, GameAttribute::TimeLimit)(input)
 }
 
+fn game_timelimit_v3_attr(input: &[u8]) -> IResult<&[u8], GameAttribute> {
+    map(timelimit_v3, GameAttribute::TimeLimit)(input)
+}
+
 fn game_attr(input: &[u8]) -> IResult<&[u8], (String, GameAttribute)> {
-    preceded(
-        tag("$"),
-        separated_pair(
+    let (input, key) = terminated(
+        preceded(
+            tag("$"),
             map_res(is_not(":"), |s: &[u8]| String::from_utf8(s.to_vec())),
-            tag(":"),
-            alt((game_time_attr, game_timelimit_attr, game_text_attr)),
         ),
-    )(input)
+        tag(":"),
+    )(input)?;
+    let (input, value) = match key.as_str() {
+        "TIME" => alt((game_timelimit_v3_attr, game_text_attr))(input)?,
+        _ => alt((game_time_attr, game_timelimit_attr, game_text_attr))(input)?,
+    };
+
+    Ok((input, (key, value)))
 }
 
 fn handicap(input: &[u8]) -> IResult<&[u8], Vec<(Square, PieceType)>> {
@@ -196,10 +223,15 @@ fn grid(input: &[u8]) -> IResult<&[u8], Grid> {
     Ok((input, [r1, r2, r3, r4, r5, r6, r7, r8, r9]))
 }
 
-fn piece_placement(input: &[u8]) -> IResult<&[u8], Vec<(Color, Square, PieceType)>> {
+type Placement = (Color, Option<Square>, PieceType);
+
+fn piece_placement(input: &[u8]) -> IResult<&[u8], Vec<Placement>> {
     let (input, _) = tag("P")(input)?;
     let (input, c) = color(input)?;
-    let (input, pcs) = many0(tuple((square, piece_type)))(input)?;
+    let (input, pcs) = many0(tuple((
+        alt((value(None, hand), map(square, Some))),
+        piece_type,
+    )))(input)?;
 
     Ok((
         input,
@@ -207,9 +239,29 @@ fn piece_placement(input: &[u8]) -> IResult<&[u8], Vec<(Color, Square, PieceType
     ))
 }
 
+pub(crate) fn position(input: &[u8]) -> IResult<&[u8], Position> {
+    let (input, drop_pieces) = opt(terminated(handicap, line_sep))(input)?;
+    let (input, _) = many0(comment_line)(input)?;
+    let (input, bulk) = opt(terminated(grid, line_sep))(input)?;
+    let (input, _) = many0(comment_line)(input)?;
+    let (input, add_pieces) = many0(terminated(piece_placement, line_sep))(input)?;
+    let (input, _) = many0(comment_line)(input)?;
+    let (input, side_to_move) = terminated(color, line_sep)(input)?;
+
+    Ok((
+        input,
+        Position {
+            drop_pieces: drop_pieces.unwrap_or_default(),
+            bulk,
+            add_pieces: add_pieces.into_iter().flatten().collect(),
+            side_to_move,
+        },
+    ))
+}
+
 fn normal_move(input: &[u8]) -> IResult<&[u8], Action> {
     let (input, c) = color(input)?;
-    let (input, from) = square(input)?;
+    let (input, from) = move_from(input)?;
     let (input, to) = square(input)?;
     let (input, pt) = piece_type(input)?;
 
@@ -230,21 +282,69 @@ fn special_move(input: &[u8]) -> IResult<&[u8], Action> {
             value(Action::Jishogi, tag("JISHOGI")),
             value(Action::Hikiwake, tag("HIKIWAKE")),
             value(Action::Sennichite, tag("SENNICHITE")),
+            value(Action::TimeUp, tag("TIME_UP")),
+            value(Action::IllegalMove, tag("ILLEGAL_MOVE")),
+            map(
+                terminated(color, tag("ILLEGAL_ACTION")),
+                Action::IllegalAction,
+            ),
         )),
     )(input)
 }
 
-fn move_record(input: &[u8]) -> IResult<&[u8], MoveRecord> {
+pub(crate) fn move_record(input: &[u8]) -> IResult<&[u8], MoveRecord> {
     let (input, action) = alt((normal_move, special_move))(input)?;
     let (input, time) = opt(preceded(line_sep, preceded(tag("T"), decimal)))(input)?;
 
-    Ok((input, MoveRecord { action, time }))
+    Ok((
+        input,
+        MoveRecord {
+            action,
+            time,
+            comments: vec![],
+        },
+    ))
+}
+
+/// A move line of the server protocol, which may carry a comment as in `+7776FU,T12,'* 30`.
+pub(crate) fn move_line(input: &[u8]) -> IResult<&[u8], MoveRecord> {
+    let (input, record) = move_record(input)?;
+    let (input, comment) = opt(preceded(tag(","), map_res(comment, str::from_utf8)))(input)?;
+
+    Ok((
+        input,
+        MoveRecord {
+            comments: comment.into_iter().map(|c| c.to_string()).collect(),
+            ..record
+        },
+    ))
+}
+
+fn score(input: &[u8]) -> IResult<&[u8], i32> {
+    map_res(recognize(pair(opt(one_of("+-")), digit1)), |s| {
+        str::from_utf8(s).unwrap().parse::<i32>()
+    })(input)
+}
+
+/// An engine evaluation in a comment, e.g. `* 30 -3334FU +2726FU` or `** 30` in records.
+pub(crate) fn evaluation(input: &[u8]) -> IResult<&[u8], Evaluation> {
+    let (input, _) = many1(tag("*"))(input)?;
+    let (input, score) = preceded(space0, score)(input)?;
+    let (input, pv) = many0(preceded(space1, normal_move))(input)?;
+
+    Ok((input, Evaluation { score, pv }))
 }
 
-fn move_records(input: &[u8]) -> IResult<&[u8], Vec<MoveRecord>> {
+pub(crate) fn move_records(input: &[u8]) -> IResult<&[u8], Vec<MoveRecord>> {
     let (input, moves) = many0(map(
-        pair(terminated(move_record, line_sep), many0(comment_line)),
-        |(m, _)| m,
+        pair(
+            terminated(move_record, line_sep),
+            many0(map_res(comment_line, str::from_utf8)),
+        ),
+        |(m, comments)| MoveRecord {
+            comments: comments.into_iter().map(|c| c.to_string()).collect(),
+            ..m
+        },
     ))(input)?;
 
     Ok((input, moves))
@@ -270,13 +370,7 @@ pub fn game_record(input: &[u8]) -> IResult<&[u8], GameRecord> {
         |v: Option<Vec<(String, GameAttribute)>>| v.unwrap_or_default(),
     )(input)?;
     let (input, _) = many0(comment_line)(input)?;
-    let (input, drop_pieces) = opt(terminated(handicap, line_sep))(input)?;
-    let (input, _) = many0(comment_line)(input)?;
-    let (input, bulk) = opt(terminated(grid, line_sep))(input)?;
-    let (input, _) = many0(comment_line)(input)?;
-    let (input, add_pieces) = many0(terminated(piece_placement, line_sep))(input)?;
-    let (input, _) = many0(comment_line)(input)?;
-    let (input, side_to_move) = terminated(color, line_sep)(input)?;
+    let (input, start_pos) = position(input)?;
     let (input, _) = many0(comment_line)(input)?;
     let (input, moves) = move_records(input)?;
 
@@ -306,23 +400,20 @@ pub fn game_record(input: &[u8]) -> IResult<&[u8], GameRecord> {
                     GameAttribute::Time(ref t) => Some(t.clone()),
                     _ => None,
                 }),
-            time_limit: attrs.iter().find(|pair| pair.0 == "TIME_LIMIT").and_then(
-                |pair| match pair.1 {
+            time_limit: attrs
+                .iter()
+                .filter(|pair| pair.0 == "TIME_LIMIT" || pair.0 == "TIME")
+                .find_map(|pair| match pair.1 {
                     GameAttribute::TimeLimit(ref t) => Some(t.clone()),
                     _ => None,
-                },
-            ),
+                }),
             opening: attrs
                 .iter()
                 .find(|pair| pair.0 == "OPENING")
                 .map(|pair| pair.1.to_string()),
-            start_pos: Position {
-                drop_pieces: drop_pieces.unwrap_or_default(),
-                bulk,
-                add_pieces: add_pieces.into_iter().flatten().collect(),
-                side_to_move,
-            },
+            start_pos,
             moves,
+            variations: vec![],
         },
     ))
 }
@@ -341,6 +432,8 @@ mod tests {
             comment(b"'this is a comment"),
             Result::Ok((&b""[..], &b"this is a comment"[..]))
         );
+        assert_eq!(comment(b"'a, b\n"), Result::Ok((&b"\n"[..], &b"a, b"[..])));
+        assert_eq!(comment(b"'\n"), Result::Ok((&b"\n"[..], &b""[..])));
     }
 
     #[test]
@@ -390,8 +483,27 @@ mod tests {
 
     #[test]
     fn parse_square() {
-        assert_eq!(square(b"00"), Result::Ok((&b""[..], Square::new(0, 0))));
-        assert_eq!(square(b"99"), Result::Ok((&b""[..], Square::new(9, 9))));
+        assert!(square(b"00").is_err());
+        assert!(square(b"10").is_err());
+        assert!(square(b"01").is_err());
+        assert_eq!(
+            square(b"11"),
+            Result::Ok((&b""[..], Square::new(1, 1).unwrap()))
+        );
+        assert_eq!(
+            square(b"99"),
+            Result::Ok((&b""[..], Square::new(9, 9).unwrap()))
+        );
+    }
+
+    #[test]
+    fn parse_move_from() {
+        assert_eq!(move_from(b"00"), Result::Ok((&b""[..], MoveFrom::Hand)));
+        assert_eq!(
+            move_from(b"77"),
+            Result::Ok((&b""[..], MoveFrom::Board(Square::new(7, 7).unwrap())))
+        );
+        assert!(move_from(b"07").is_err());
     }
 
     #[test]
@@ -399,6 +511,7 @@ mod tests {
         assert_eq!(version(b"V2"), Result::Ok((&b""[..], &b"2"[..])));
         assert_eq!(version(b"V2.1"), Result::Ok((&b""[..], &b"2.1"[..])));
         assert_eq!(version(b"V2.2"), Result::Ok((&b""[..], &b"2.2"[..])));
+        assert_eq!(version(b"V3.0"), Result::Ok((&b""[..], &b"3.0"[..])));
     }
 
     #[test]
@@ -431,11 +544,50 @@ mod tests {
                     GameAttribute::Time(Time {
                         date: NativeDate::from_calendar_date(2002, time::Month::January, 1)
                             .unwrap(),
-                        time: Some(NativeTime::from_hms(19, 0, 0).unwrap())
+                        time: Some(NativeTime::from_hms(19, 0, 0).unwrap()),
+                        offset: None,
+                    })
+                )
+            ))
+        );
+        assert_eq!(
+            game_attr(b"$TIME_LIMIT:00:10+10"),
+            Result::Ok((
+                &b""[..],
+                (
+                    "TIME_LIMIT".to_string(),
+                    GameAttribute::TimeLimit(TimeLimit {
+                        main_time: Duration::from_secs(600),
+                        byoyomi: Duration::from_secs(10),
+                        increment: Duration::ZERO,
+                    })
+                )
+            ))
+        );
+        assert_eq!(
+            game_attr(b"$TIME:600+0+10"),
+            Result::Ok((
+                &b""[..],
+                (
+                    "TIME".to_string(),
+                    GameAttribute::TimeLimit(TimeLimit {
+                        main_time: Duration::from_secs(600),
+                        byoyomi: Duration::ZERO,
+                        increment: Duration::from_secs(10),
                     })
                 )
             ))
         );
+        assert_eq!(
+            game_attr(b"$EVENT:600+0+10"),
+            Result::Ok((
+                &b""[..],
+                (
+                    "EVENT".to_string(),
+                    GameAttribute::Str("600+0+10".to_string())
+                )
+            ))
+        );
     }
 
     #[test]
@@ -446,8 +598,8 @@ mod tests {
             Result::Ok((
                 &b""[..],
                 vec![
-                    (Square::new(8, 2), PieceType::Rook),
-                    (Square::new(2, 2), PieceType::Bishop)
+                    (Square::new(8, 2).unwrap(), PieceType::Rook),
+                    (Square::new(2, 2).unwrap(), PieceType::Bishop)
                 ]
             ))
         );
@@ -555,11 +707,15 @@ P9+KY+KE+GI+KI+OU+KI+GI+KE+KY";
         );
         assert_eq!(
             piece_placement(b"P-00AL"),
-            Result::Ok((
-                &b""[..],
-                vec![(Color::White, Square::new(0, 0), PieceType::All),]
-            ))
+            Result::Ok((&b""[..], vec![(Color::White, None, PieceType::All),]))
         );
+        assert!(matches!(
+            piece_placement(b"P+09KI"),
+            Err(Err::Failure(Error {
+                input: b"09KI",
+                code: ErrorKind::Verify
+            }))
+        ));
     }
 
     #[test]
@@ -570,8 +726,8 @@ P9+KY+KE+GI+KI+OU+KI+GI+KE+KY";
                 &b""[..],
                 Action::Move(
                     Color::Black,
-                    Square::new(2, 7),
-                    Square::new(2, 6),
+                    MoveFrom::Board(Square::new(2, 7).unwrap()),
+                    Square::new(2, 6).unwrap(),
                     PieceType::Pawn
                 )
             ))
@@ -582,14 +738,31 @@ P9+KY+KE+GI+KI+OU+KI+GI+KE+KY";
                 &b""[..],
                 Action::Move(
                     Color::White,
-                    Square::new(3, 3),
-                    Square::new(3, 4),
+                    MoveFrom::Board(Square::new(3, 3).unwrap()),
+                    Square::new(3, 4).unwrap(),
                     PieceType::Pawn
                 )
             ))
         );
     }
 
+    #[test]
+    fn parse_drop_move() {
+        assert_eq!(
+            normal_move(b"+0055KA"),
+            Result::Ok((
+                &b""[..],
+                Action::Move(
+                    Color::Black,
+                    MoveFrom::Hand,
+                    Square::new(5, 5).unwrap(),
+                    PieceType::Bishop
+                )
+            ))
+        );
+        assert!(normal_move(b"+5500KA").is_err());
+    }
+
     #[test]
     fn parse_special_move() {
         assert_eq!(
@@ -632,6 +805,102 @@ P9+KY+KE+GI+KI+OU+KI+GI+KE+KY";
             special_move(b"%SENNICHITE"),
             Result::Ok((&b""[..], Action::Sennichite))
         );
+        assert_eq!(
+            special_move(b"%TIME_UP"),
+            Result::Ok((&b""[..], Action::TimeUp))
+        );
+        assert_eq!(
+            special_move(b"%ILLEGAL_MOVE"),
+            Result::Ok((&b""[..], Action::IllegalMove))
+        );
+        assert_eq!(
+            special_move(b"%+ILLEGAL_ACTION"),
+            Result::Ok((&b""[..], Action::IllegalAction(Color::Black)))
+        );
+        assert_eq!(
+            special_move(b"%-ILLEGAL_ACTION"),
+            Result::Ok((&b""[..], Action::IllegalAction(Color::White)))
+        );
+    }
+
+    #[test]
+    fn parse_move_line() {
+        assert_eq!(
+            move_line(b"+7776FU,T12,'* 30 -3334FU"),
+            Result::Ok((
+                &b""[..],
+                MoveRecord {
+                    action: Action::Move(
+                        Color::Black,
+                        MoveFrom::Board(Square::new(7, 7).unwrap()),
+                        Square::new(7, 6).unwrap(),
+                        PieceType::Pawn
+                    ),
+                    time: Some(Duration::from_secs(12)),
+                    comments: vec!["* 30 -3334FU".to_string()],
+                }
+            ))
+        );
+        assert_eq!(
+            move_line(b"%TORYO,'* -9999"),
+            Result::Ok((
+                &b""[..],
+                MoveRecord {
+                    action: Action::Toryo,
+                    time: None,
+                    comments: vec!["* -9999".to_string()],
+                }
+            ))
+        );
+    }
+
+    #[test]
+    fn parse_evaluation() {
+        assert_eq!(
+            evaluation(b"** 30 -3334FU +2726FU"),
+            Result::Ok((
+                &b""[..],
+                Evaluation {
+                    score: 30,
+                    pv: vec![
+                        Action::Move(
+                            Color::White,
+                            MoveFrom::Board(Square::new(3, 3).unwrap()),
+                            Square::new(3, 4).unwrap(),
+                            PieceType::Pawn
+                        ),
+                        Action::Move(
+                            Color::Black,
+                            MoveFrom::Board(Square::new(2, 7).unwrap()),
+                            Square::new(2, 6).unwrap(),
+                            PieceType::Pawn
+                        ),
+                    ],
+                }
+            ))
+        );
+        assert_eq!(
+            evaluation(b"*-120"),
+            Result::Ok((
+                &b""[..],
+                Evaluation {
+                    score: -120,
+                    pv: vec![],
+                }
+            ))
+        );
+        assert_eq!(
+            evaluation(b"* 0 (depth 5)"),
+            Result::Ok((
+                &b" (depth 5)"[..],
+                Evaluation {
+                    score: 0,
+                    pv: vec![]
+                }
+            ))
+        );
+        assert!(evaluation(b"*jouseki").is_err());
+        assert!(evaluation(b"30").is_err());
     }
 
     #[test]
@@ -643,11 +912,12 @@ P9+KY+KE+GI+KI+OU+KI+GI+KE+KY";
                 MoveRecord {
                     action: Action::Move(
                         Color::Black,
-                        Square::new(2, 7),
-                        Square::new(2, 6),
+                        MoveFrom::Board(Square::new(2, 7).unwrap()),
+                        Square::new(2, 6).unwrap(),
                         PieceType::Pawn
                     ),
-                    time: Some(Duration::from_secs(5))
+                    time: Some(Duration::from_secs(5)),
+                    comments: vec![],
                 }
             ))
         );
@@ -658,11 +928,12 @@ P9+KY+KE+GI+KI+OU+KI+GI+KE+KY";
                 MoveRecord {
                     action: Action::Move(
                         Color::Black,
-                        Square::new(2, 7),
-                        Square::new(2, 6),
+                        MoveFrom::Board(Square::new(2, 7).unwrap()),
+                        Square::new(2, 6).unwrap(),
                         PieceType::Pawn
                     ),
-                    time: None
+                    time: None,
+                    comments: vec![],
                 }
             ))
         );
@@ -673,7 +944,8 @@ P9+KY+KE+GI+KI+OU+KI+GI+KE+KY";
                 &b""[..],
                 MoveRecord {
                     action: Action::Toryo,
-                    time: Some(Duration::from_secs(5))
+                    time: Some(Duration::from_secs(5)),
+                    comments: vec![],
                 }
             ))
         );
@@ -683,7 +955,8 @@ P9+KY+KE+GI+KI+OU+KI+GI+KE+KY";
                 &b""[..],
                 MoveRecord {
                     action: Action::Toryo,
-                    time: None
+                    time: None,
+                    comments: vec![],
                 }
             ))
         );
@@ -707,29 +980,32 @@ T5
                     MoveRecord {
                         action: Action::Move(
                             Color::Black,
-                            Square::new(7, 7),
-                            Square::new(7, 6),
+                            MoveFrom::Board(Square::new(7, 7).unwrap()),
+                            Square::new(7, 6).unwrap(),
                             PieceType::Pawn
                         ),
                         time: None,
+                        comments: vec!["** 30 -3334FU +2726FU".to_string()],
                     },
                     MoveRecord {
                         action: Action::Move(
                             Color::White,
-                            Square::new(3, 3),
-                            Square::new(3, 4),
+                            MoveFrom::Board(Square::new(3, 3).unwrap()),
+                            Square::new(3, 4).unwrap(),
                             PieceType::Pawn
                         ),
                         time: Some(Duration::from_secs(5)),
+                        comments: vec!["*jouseki".to_string()],
                     },
                     MoveRecord {
                         action: Action::Move(
                             Color::Black,
-                            Square::new(2, 7),
-                            Square::new(2, 6),
+                            MoveFrom::Board(Square::new(2, 7).unwrap()),
+                            Square::new(2, 6).unwrap(),
                             PieceType::Pawn
                         ),
                         time: None,
+                        comments: vec![],
                     },
                 ]
             ))
@@ -862,15 +1138,18 @@ T6
                     site: Some("KAZUSA ARC".to_string()),
                     start_time: Some(Time {
                         date: NativeDate::from_calendar_date(2003, time::Month::May, 3).unwrap(),
-                        time: Some(NativeTime::from_hms(10, 30, 0).unwrap())
+                        time: Some(NativeTime::from_hms(10, 30, 0).unwrap()),
+                        offset: None,
                     }),
                     end_time: Some(Time {
                         date: NativeDate::from_calendar_date(2003, time::Month::May, 3).unwrap(),
-                        time: Some(NativeTime::from_hms(11, 11, 5).unwrap())
+                        time: Some(NativeTime::from_hms(11, 11, 5).unwrap()),
+                        offset: None,
                     }),
                     time_limit: Some(TimeLimit {
                         main_time: Duration::from_secs(1500),
-                        byoyomi: Duration::from_secs(0)
+                        byoyomi: Duration::from_secs(0),
+                        increment: Duration::from_secs(0),
                     }),
                     opening: Some("YAGURA".to_string()),
                     start_pos: Position {
@@ -883,26 +1162,33 @@ T6
                         MoveRecord {
                             action: Action::Move(
                                 Color::Black,
-                                Square::new(2, 7),
-                                Square::new(2, 6),
+                                MoveFrom::Board(Square::new(2, 7).unwrap()),
+                                Square::new(2, 6).unwrap(),
                                 PieceType::Pawn
                             ),
-                            time: Some(Duration::from_secs(12))
+                            time: Some(Duration::from_secs(12)),
+                            comments: vec![],
                         },
                         MoveRecord {
                             action: Action::Move(
                                 Color::White,
-                                Square::new(3, 3),
-                                Square::new(3, 4),
+                                MoveFrom::Board(Square::new(3, 3).unwrap()),
+                                Square::new(3, 4).unwrap(),
                                 PieceType::Pawn
                             ),
-                            time: Some(Duration::from_secs(6))
+                            time: Some(Duration::from_secs(6)),
+                            comments: vec![],
                         },
                         MoveRecord {
                             action: Action::Chudan,
-                            time: None
+                            time: None,
+                            comments: vec![
+                                "---------------------------------------------------------"
+                                    .to_string()
+                            ],
                         }
                     ],
+                    variations: vec![],
                 }
             ))
         )
@@ -4,18 +4,40 @@ mod time;
 use std::error::Error;
 use std::fmt;
 
-use self::game::game_record;
-use crate::value::GameRecord;
+use nom::branch::alt;
+use nom::character::complete::space1;
+use nom::combinator::{all_consuming, eof};
+use nom::error::ErrorKind;
+use nom::sequence::terminated;
 
-#[derive(Debug)]
+use self::game::{evaluation, game_record, move_line};
+pub(crate) use self::game::{line_sep, move_records, position};
+use crate::value::{Action, Evaluation, GameRecord, MoveRecord};
+
+#[derive(Debug, PartialEq, Eq)]
 pub enum CsaError {
     ParseError(),
+    InvalidResult(),
+    InvalidMove(Action),
+    /// A square outside 1..=9, such as `09` on the line `+0900FU`.
+    InvalidSquare {
+        square: String,
+        line: String,
+    },
 }
 
 impl fmt::Display for CsaError {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         match *self {
             CsaError::ParseError() => write!(f, "failed to parse"),
+            CsaError::InvalidResult() => {
+                write!(f, "the result cannot be expressed by a special move")
+            }
+            CsaError::InvalidMove(ref action) => write!(f, "invalid move: {action}"),
+            CsaError::InvalidSquare {
+                ref square,
+                ref line,
+            } => write!(f, "invalid square {square} in {line}"),
         }
     }
 }
@@ -25,10 +47,44 @@ impl Error for CsaError {}
 ////////////////////////////////////////////////////////////////////////////////
 
 pub fn parse_csa(s: &str) -> Result<GameRecord, CsaError> {
-    if let Ok((_, record)) = game_record(s.as_bytes()) {
-        Ok(record)
-    } else {
-        Err(CsaError::ParseError())
+    game_record(s.as_bytes())
+        .map(|(_, record)| record)
+        .map_err(|e| parse_error(s, e))
+}
+
+/// Parses a single move line of the server protocol such as `+7776FU,T12` or `+7776FU,'* 30 -3334FU`.
+///
+/// The comment after `'`, if any, becomes the only comment of the record.
+pub fn parse_move_line(s: &str) -> Result<MoveRecord, CsaError> {
+    all_consuming(move_line)(s.as_bytes())
+        .map(|(_, record)| record)
+        .map_err(|e| parse_error(s, e))
+}
+
+/// Parses an engine evaluation comment such as `* 30 -3334FU +2726FU`, without the leading `'`.
+///
+/// Anything following the score and the moves of the PV after a space, e.g. search info, is ignored.
+pub fn parse_evaluation(s: &str) -> Result<Evaluation, CsaError> {
+    terminated(evaluation, alt((eof, space1)))(s.as_bytes())
+        .map(|(_, evaluation)| evaluation)
+        .map_err(|_| CsaError::ParseError())
+}
+
+/// Converts a parser error, naming the square and its line for a square out of range.
+fn parse_error(s: &str, e: nom::Err<nom::error::Error<&[u8]>>) -> CsaError {
+    match e {
+        nom::Err::Failure(e) if e.code == ErrorKind::Verify => {
+            let offset = s.len() - e.input.len();
+            let start = s[..offset].rfind(['\r', '\n']).map_or(0, |i| i + 1);
+            let end = s[offset..]
+                .find(['\r', '\n'])
+                .map_or(s.len(), |i| offset + i);
+            CsaError::InvalidSquare {
+                square: s[offset..offset + 2].to_string(),
+                line: s[start..end].to_string(),
+            }
+        }
+        _ => CsaError::ParseError(),
     }
 }
 
@@ -60,6 +116,32 @@ mod tests {
             let res = parse_csa(&contents);
 
             assert!(res.is_ok());
+
+            let record = res.unwrap();
+            assert_eq!(parse_csa(&record.to_string()).unwrap(), record);
         }
     }
+    #[test]
+    fn evaluation() {
+        let score = |s| parse_evaluation(s).map(|e| e.score);
+        assert_eq!(score("** 30 -3334FU +2726FU"), Ok(30));
+        assert_eq!(score("*-120"), Ok(-120));
+        assert_eq!(score("* 0 (depth 5)"), Ok(0));
+        assert_eq!(score("*30x"), Err(CsaError::ParseError()));
+        assert_eq!(score("*jouseki"), Err(CsaError::ParseError()));
+        assert_eq!(score("30"), Err(CsaError::ParseError()));
+        assert_eq!(
+            parse_evaluation("* 30 -3334FU bestmove").unwrap().pv.len(),
+            1
+        );
+    }
+
+    #[test]
+    fn move_line() {
+        let record = parse_move_line("+7776FU,T12,'* 30 -3334FU").unwrap();
+        assert_eq!(record.time, Some(std::time::Duration::from_secs(12)));
+        assert_eq!(record.evaluation().unwrap().score, 30);
+        assert_eq!(record.to_line(), "+7776FU,T12,'* 30 -3334FU");
+        assert!(parse_move_line("+7776FU,T12 junk").is_err());
+    }
 }
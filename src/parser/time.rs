@@ -1,12 +1,13 @@
+use nom::branch::alt;
 use nom::bytes::complete::{tag, take};
-use nom::character::complete::digit1;
-use nom::combinator::{map_res, opt, verify};
-use nom::sequence::preceded;
+use nom::character::complete::{digit1, one_of};
+use nom::combinator::{map_res, opt, value, verify};
+use nom::sequence::{preceded, tuple};
 use nom::*;
 use std::convert::TryFrom;
 use std::str;
 use std::time::Duration;
-use time::{Date as NativeDate, Month, Time as NativeTime};
+use time::{Date as NativeDate, Month, Time as NativeTime, UtcOffset};
 
 use crate::value::{Time, TimeLimit};
 
@@ -72,25 +73,79 @@ fn time(input: &[u8]) -> IResult<&[u8], NativeTime> {
     ))
 }
 
+fn utc_offset(input: &[u8]) -> IResult<&[u8], UtcOffset> {
+    let numeric = map_res(
+        tuple((one_of("+-"), hour, opt(tag(":")), minutes)),
+        |(sign, hours, _, minutes)| {
+            let sign = if sign == '-' { -1 } else { 1 };
+            UtcOffset::from_hms((sign * hours) as i8, (sign * minutes) as i8, 0)
+        },
+    );
+
+    alt((value(UtcOffset::UTC, tag("Z")), numeric))(input)
+}
+
 pub fn datetime(input: &[u8]) -> IResult<&[u8], Time> {
     let (input, date) = date(input)?;
     let (input, time) = opt(preceded(tag(" "), time))(input)?;
+    let (input, offset) = match time {
+        Some(_) => opt(preceded(opt(tag(" ")), utc_offset))(input)?,
+        None => (input, None),
+    };
 
-    Ok((input, Time { date, time }))
+    Ok((input, Time { date, time, offset }))
+}
+
+fn decimal_seconds(input: &[u8]) -> IResult<&[u8], Duration> {
+    let (input, secs) =
+        map_res(map_res(digit1, str::from_utf8), |s: &str| s.parse::<u64>())(input)?;
+    let (input, fraction) = opt(preceded(
+        tag("."),
+        verify(map_res(digit1, str::from_utf8), |s: &str| s.len() <= 9),
+    ))(input)?;
+
+    let nanos = fraction.map_or(0, |s| {
+        let padded = format!("{s:0<9}");
+        padded.parse::<u32>().unwrap()
+    });
+
+    Ok((input, Duration::new(secs, nanos)))
 }
 
 pub fn timelimit(input: &[u8]) -> IResult<&[u8], TimeLimit> {
     let (input, hour) = take_n_digits(input)?;
     let (input, _) = tag(":")(input)?;
     let (input, minutes) = minutes(input)?;
+    let (input, seconds) = opt(preceded(tag(":"), seconds))(input)?;
     let (input, _) = tag("+")(input)?;
     let (input, byoyomi) = take_n_digits(input)?;
 
+    let main_time = hour as u64 * 60 * 60 + minutes as u64 * 60 + seconds.unwrap_or(0) as u64;
+
     Ok((
         input,
         TimeLimit {
-            main_time: Duration::from_secs(hour as u64 * 60 * 60 + minutes as u64 * 60),
+            main_time: Duration::from_secs(main_time),
             byoyomi: Duration::from_secs(byoyomi as u64),
+            increment: Duration::ZERO,
+        },
+    ))
+}
+
+/// Parses the value of `$TIME` introduced in CSA V3.0, `<main time>+<byoyomi>+<increment>` in seconds.
+pub fn timelimit_v3(input: &[u8]) -> IResult<&[u8], TimeLimit> {
+    let (input, main_time) = decimal_seconds(input)?;
+    let (input, _) = tag("+")(input)?;
+    let (input, byoyomi) = decimal_seconds(input)?;
+    let (input, _) = tag("+")(input)?;
+    let (input, increment) = decimal_seconds(input)?;
+
+    Ok((
+        input,
+        TimeLimit {
+            main_time,
+            byoyomi,
+            increment,
         },
     ))
 }
@@ -174,7 +229,8 @@ mod tests {
                 &b""[..],
                 Time {
                     date: NativeDate::from_calendar_date(2002, Month::January, 1).unwrap(),
-                    time: None
+                    time: None,
+                    offset: None,
                 }
             ))
         );
@@ -184,7 +240,41 @@ mod tests {
                 &b""[..],
                 Time {
                     date: NativeDate::from_calendar_date(2002, Month::January, 1).unwrap(),
-                    time: Some(NativeTime::from_hms(19, 0, 0).unwrap())
+                    time: Some(NativeTime::from_hms(19, 0, 0).unwrap()),
+                    offset: None,
+                }
+            ))
+        );
+        assert_eq!(
+            datetime(b"2002/01/01 19:00:00+09:00"),
+            Result::Ok((
+                &b""[..],
+                Time {
+                    date: NativeDate::from_calendar_date(2002, Month::January, 1).unwrap(),
+                    time: Some(NativeTime::from_hms(19, 0, 0).unwrap()),
+                    offset: Some(UtcOffset::from_hms(9, 0, 0).unwrap()),
+                }
+            ))
+        );
+        assert_eq!(
+            datetime(b"2002/01/01 19:00:00 -0530"),
+            Result::Ok((
+                &b""[..],
+                Time {
+                    date: NativeDate::from_calendar_date(2002, Month::January, 1).unwrap(),
+                    time: Some(NativeTime::from_hms(19, 0, 0).unwrap()),
+                    offset: Some(UtcOffset::from_hms(-5, -30, 0).unwrap()),
+                }
+            ))
+        );
+        assert_eq!(
+            datetime(b"2002/01/01 19:00:00Z"),
+            Result::Ok((
+                &b""[..],
+                Time {
+                    date: NativeDate::from_calendar_date(2002, Month::January, 1).unwrap(),
+                    time: Some(NativeTime::from_hms(19, 0, 0).unwrap()),
+                    offset: Some(UtcOffset::UTC),
                 }
             ))
         );
@@ -198,7 +288,8 @@ mod tests {
                 &b""[..],
                 TimeLimit {
                     main_time: Duration::from_secs(25 * 60),
-                    byoyomi: Duration::from_secs(0)
+                    byoyomi: Duration::from_secs(0),
+                    increment: Duration::ZERO,
                 }
             ))
         );
@@ -208,7 +299,8 @@ mod tests {
                 &b""[..],
                 TimeLimit {
                     main_time: Duration::from_secs(30 * 60),
-                    byoyomi: Duration::from_secs(30)
+                    byoyomi: Duration::from_secs(30),
+                    increment: Duration::ZERO,
                 }
             ))
         );
@@ -218,9 +310,48 @@ mod tests {
                 &b""[..],
                 TimeLimit {
                     main_time: Duration::from_secs(0),
-                    byoyomi: Duration::from_secs(30)
+                    byoyomi: Duration::from_secs(30),
+                    increment: Duration::ZERO,
+                }
+            ))
+        );
+        assert_eq!(
+            timelimit(b"01:30:15+10"),
+            Result::Ok((
+                &b""[..],
+                TimeLimit {
+                    main_time: Duration::from_secs(5415),
+                    byoyomi: Duration::from_secs(10),
+                    increment: Duration::ZERO,
+                }
+            ))
+        );
+    }
+
+    #[test]
+    fn parse_timelimit_v3() {
+        assert_eq!(
+            timelimit_v3(b"600+10+0"),
+            Result::Ok((
+                &b""[..],
+                TimeLimit {
+                    main_time: Duration::from_secs(600),
+                    byoyomi: Duration::from_secs(10),
+                    increment: Duration::ZERO,
+                }
+            ))
+        );
+        assert_eq!(
+            timelimit_v3(b"300.5+0+2.25"),
+            Result::Ok((
+                &b""[..],
+                TimeLimit {
+                    main_time: Duration::from_millis(300_500),
+                    byoyomi: Duration::ZERO,
+                    increment: Duration::from_millis(2_250),
                 }
             ))
         );
+        assert!(timelimit_v3(b"600+10").is_err());
     }
 }
@@ -0,0 +1,373 @@
+use std::future::Future;
+use std::pin::Pin;
+use std::task::{ready, Context, Poll};
+use std::time::Duration;
+
+use futures_core::Stream;
+use tokio::io::{AsyncBufReadExt, AsyncRead, AsyncWrite, AsyncWriteExt, BufReader};
+use tokio::time::{timeout, Instant};
+
+use super::client::{
+    check_agree, check_game_summary, check_login, check_login_x1, check_logout, check_reject,
+};
+use super::{ClientMessage, GameSummary, MessageDecoder, ProtocolError, ServerMessage};
+use crate::value::{Action, Evaluation};
+
+/// An asynchronous counterpart of [`Client`](super::Client) for tokio streams.
+///
+/// While waiting for a message, an empty line is sent every `keep_alive` interval if set,
+/// and [`ProtocolError::TimedOut`] is returned if nothing arrives within the read timeout.
+pub struct AsyncClient<S: AsyncRead + AsyncWrite + Unpin> {
+    stream: BufReader<S>,
+    decoder: MessageDecoder,
+    buf: Vec<u8>,
+    keep_alive: Option<Duration>,
+    read_timeout: Option<Duration>,
+}
+
+impl<S: AsyncRead + AsyncWrite + Unpin> AsyncClient<S> {
+    pub fn new(stream: S) -> AsyncClient<S> {
+        AsyncClient {
+            stream: BufReader::new(stream),
+            decoder: MessageDecoder::default(),
+            buf: Vec::new(),
+            keep_alive: None,
+            read_timeout: None,
+        }
+    }
+
+    pub fn get_ref(&self) -> &S {
+        self.stream.get_ref()
+    }
+
+    pub fn into_inner(self) -> S {
+        self.stream.into_inner()
+    }
+
+    /// Sets the interval of empty lines sent to keep the connection alive while waiting.
+    pub fn set_keep_alive(&mut self, interval: Option<Duration>) {
+        self.keep_alive = interval;
+    }
+
+    /// Sets how long to wait for a line from the server, including keep-alive lines.
+    pub fn set_read_timeout(&mut self, timeout: Option<Duration>) {
+        self.read_timeout = timeout;
+    }
+
+    /// Logs in and waits for the server to accept it.
+    pub async fn login(&mut self, name: &str, password: &str) -> Result<(), ProtocolError> {
+        self.send(&ClientMessage::Login {
+            name: name.to_string(),
+            password: password.to_string(),
+        })
+        .await?;
+        check_login(self.next_event_skipping_keep_alive().await?)
+    }
+
+    /// Logs in with the x1 extended mode, whose replies arrive as [`ServerMessage::X1`].
+    pub async fn login_x1(&mut self, name: &str, password: &str) -> Result<(), ProtocolError> {
+        self.send(&ClientMessage::LoginX1 {
+            name: name.to_string(),
+            password: password.to_string(),
+        })
+        .await?;
+        check_login(self.next_event_skipping_keep_alive().await?)?;
+        check_login_x1(self.next_event_skipping_keep_alive().await?)
+    }
+
+    pub async fn logout(&mut self) -> Result<(), ProtocolError> {
+        self.send(&ClientMessage::Logout).await?;
+        check_logout(self.next_event_skipping_keep_alive().await?)
+    }
+
+    /// Waits until the server offers a game.
+    pub async fn wait_game_summary(&mut self) -> Result<GameSummary, ProtocolError> {
+        check_game_summary(self.next_event_skipping_keep_alive().await?)
+    }
+
+    /// Accepts the offered game and waits for it to start.
+    pub async fn agree(&mut self, game_id: &str) -> Result<(), ProtocolError> {
+        self.send(&ClientMessage::Agree(game_id.to_string()))
+            .await?;
+        check_agree(game_id, self.next_event_skipping_keep_alive().await?)
+    }
+
+    /// Declines the offered game.
+    pub async fn reject(&mut self, game_id: &str) -> Result<(), ProtocolError> {
+        self.send(&ClientMessage::Reject(game_id.to_string()))
+            .await?;
+        check_reject(game_id, self.next_event_skipping_keep_alive().await?)
+    }
+
+    /// Sends a move or a special move such as `%TORYO`.
+    pub async fn send_move(&mut self, action: &Action) -> Result<(), ProtocolError> {
+        self.send(&ClientMessage::Move(*action)).await
+    }
+
+    /// Sends a move with the engine's evaluation, which servers such as Floodgate write to the record.
+    pub async fn send_move_with_evaluation(
+        &mut self,
+        action: &Action,
+        evaluation: &Evaluation,
+    ) -> Result<(), ProtocolError> {
+        self.send(&ClientMessage::MoveWithEvaluation(
+            *action,
+            evaluation.clone(),
+        ))
+        .await
+    }
+
+    pub async fn send(&mut self, message: &ClientMessage) -> Result<(), ProtocolError> {
+        self.write_line(&message.to_string()).await
+    }
+
+    /// Receives the next message, including keep-alive empty lines sent by the server.
+    ///
+    /// The method is cancel safe: a partially received line is kept for the next call.
+    pub async fn next_event(&mut self) -> Result<ServerMessage, ProtocolError> {
+        loop {
+            let line = self.read_line().await?;
+            if let Some(message) = self.decoder.decode(&line)? {
+                return Ok(message);
+            }
+        }
+    }
+
+    /// Turns the client into a [`Stream`] of the messages returned by [`AsyncClient::next_event`].
+    ///
+    /// The stream ends when the server closes the connection, or after yielding any other error.
+    pub fn into_events(self) -> Events<S>
+    where
+        S: Send + 'static,
+    {
+        Events {
+            client: Some(self),
+            next: None,
+        }
+    }
+
+    async fn next_event_skipping_keep_alive(&mut self) -> Result<ServerMessage, ProtocolError> {
+        loop {
+            match self.next_event().await? {
+                ServerMessage::KeepAlive => continue,
+                message => return Ok(message),
+            }
+        }
+    }
+
+    async fn write_line(&mut self, line: &str) -> Result<(), ProtocolError> {
+        let stream = self.stream.get_mut();
+        stream.write_all(format!("{line}\n").as_bytes()).await?;
+        stream.flush().await?;
+        Ok(())
+    }
+
+    async fn read_line(&mut self) -> Result<String, ProtocolError> {
+        let deadline = self.read_timeout.map(|t| Instant::now() + t);
+
+        loop {
+            let left = deadline.map(|d| d.saturating_duration_since(Instant::now()));
+            if left.is_some_and(|left| left.is_zero()) {
+                return Err(ProtocolError::TimedOut);
+            }
+            let wait = match (self.keep_alive, left) {
+                (Some(interval), Some(left)) => Some(interval.min(left)),
+                (interval, left) => interval.or(left),
+            };
+
+            let read = self.stream.read_until(b'\n', &mut self.buf);
+            let n = match wait {
+                Some(wait) => match timeout(wait, read).await {
+                    Ok(n) => n?,
+                    Err(_) => {
+                        if self.keep_alive.is_some() {
+                            self.write_line("").await?;
+                        }
+                        continue;
+                    }
+                },
+                None => read.await?,
+            };
+
+            if n == 0 && self.buf.is_empty() {
+                return Err(ProtocolError::ConnectionClosed);
+            }
+            if n == 0 || self.buf.ends_with(b"\n") {
+                let line = String::from_utf8_lossy(&self.buf)
+                    .trim_end_matches(['\r', '\n'])
+                    .to_string();
+                self.buf.clear();
+                return Ok(line);
+            }
+        }
+    }
+}
+
+type NextEvent<S> =
+    Pin<Box<dyn Future<Output = (AsyncClient<S>, Result<ServerMessage, ProtocolError>)> + Send>>;
+
+/// A stream of server messages, created by [`AsyncClient::into_events`].
+pub struct Events<S: AsyncRead + AsyncWrite + Unpin> {
+    client: Option<AsyncClient<S>>,
+    next: Option<NextEvent<S>>,
+}
+
+impl<S: AsyncRead + AsyncWrite + Unpin + Send + 'static> Stream for Events<S> {
+    type Item = Result<ServerMessage, ProtocolError>;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let this = &mut *self;
+        if this.next.is_none() {
+            let Some(mut client) = this.client.take() else {
+                return Poll::Ready(None);
+            };
+            this.next = Some(Box::pin(async move {
+                let result = client.next_event().await;
+                (client, result)
+            }));
+        }
+
+        let (client, result) = ready!(this.next.as_mut().unwrap().as_mut().poll(cx));
+        this.next = None;
+        match result {
+            Err(ProtocolError::ConnectionClosed) => Poll::Ready(None),
+            Err(e) => Poll::Ready(Some(Err(e))),
+            Ok(message) => {
+                this.client = Some(client);
+                Poll::Ready(Some(Ok(message)))
+            }
+        }
+    }
+}
+
+////////////////////////////////////////////////////////////////////////////////
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::protocol::{GameEndReason, GameOutcome};
+    use crate::value::Color;
+    use tokio::io::{duplex, AsyncReadExt};
+
+    #[tokio::test]
+    async fn session() {
+        let (client_side, mut server_side) = duplex(4096);
+        let mut client = AsyncClient::new(client_side);
+
+        server_side
+            .write_all(
+                b"LOGIN:alice OK\n\
+BEGIN Game_Summary\n\
+Game_ID:g1\n\
+Name+:alice\n\
+Name-:bob\n\
+Your_Turn:+\n\
+BEGIN Position\n\
+PI\n\
++\n\
+END Position\n\
+END Game_Summary\n\
+START:g1\n\
+\n\
+%TORYO,T2\n\
+#RESIGN\n\
+#LOSE\n",
+            )
+            .await
+            .unwrap();
+
+        client.login("alice", "secret").await.unwrap();
+        let summary = client.wait_game_summary().await.unwrap();
+        assert_eq!(summary.your_turn, Color::Black);
+        client.agree("g1").await.unwrap();
+        client.send_move(&Action::Toryo).await.unwrap();
+
+        assert_eq!(client.next_event().await.unwrap(), ServerMessage::KeepAlive);
+        assert!(matches!(
+            client.next_event().await.unwrap(),
+            ServerMessage::Move(ref m) if m.action == Action::Toryo
+        ));
+        assert_eq!(
+            client.next_event().await.unwrap(),
+            ServerMessage::GameEnd(GameEndReason::Resign)
+        );
+        assert_eq!(
+            client.next_event().await.unwrap(),
+            ServerMessage::Result(GameOutcome::Lose)
+        );
+
+        drop(client);
+        let mut sent = String::new();
+        server_side.read_to_string(&mut sent).await.unwrap();
+        assert_eq!(sent, "LOGIN alice secret\nAGREE g1\n%TORYO\n");
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn keep_alive_and_timeout() {
+        let (client_side, mut server_side) = duplex(4096);
+        let mut client = AsyncClient::new(client_side);
+        client.set_keep_alive(Some(Duration::from_secs(30)));
+        client.set_read_timeout(Some(Duration::from_secs(100)));
+
+        server_side.write_all(b"START:").await.unwrap();
+        let task = tokio::spawn(async move {
+            let result = client.next_event().await;
+            (client, result)
+        });
+        tokio::time::sleep(Duration::from_secs(70)).await;
+        server_side.write_all(b"g1\n").await.unwrap();
+
+        let (mut client, result) = task.await.unwrap();
+        assert_eq!(result.unwrap(), ServerMessage::Start("g1".to_string()));
+        let mut pings = [0; 2];
+        server_side.read_exact(&mut pings).await.unwrap();
+        assert_eq!(&pings, b"\n\n");
+
+        assert!(matches!(
+            client.next_event().await,
+            Err(ProtocolError::TimedOut)
+        ));
+    }
+
+    async fn next<S>(events: &mut Events<S>) -> Option<Result<ServerMessage, ProtocolError>>
+    where
+        S: AsyncRead + AsyncWrite + Unpin + Send + 'static,
+    {
+        std::future::poll_fn(|cx| Pin::new(&mut *events).poll_next(cx)).await
+    }
+
+    #[tokio::test]
+    async fn events() {
+        let (client_side, mut server_side) = duplex(4096);
+        let mut events = AsyncClient::new(client_side).into_events();
+
+        server_side
+            .write_all(b"START:g1\n+7776FU,T1\n")
+            .await
+            .unwrap();
+        drop(server_side);
+
+        assert_eq!(
+            next(&mut events).await.unwrap().unwrap(),
+            ServerMessage::Start("g1".to_string())
+        );
+        assert!(matches!(
+            next(&mut events).await.unwrap().unwrap(),
+            ServerMessage::Move(ref m) if m.time == Some(Duration::from_secs(1))
+        ));
+        assert!(next(&mut events).await.is_none());
+        assert!(next(&mut events).await.is_none());
+    }
+
+    #[tokio::test]
+    async fn connection_closed() {
+        let (client_side, server_side) = duplex(64);
+        let mut client = AsyncClient::new(client_side);
+        drop(server_side);
+
+        assert!(matches!(
+            client.next_event().await,
+            Err(ProtocolError::ConnectionClosed)
+        ));
+    }
+}
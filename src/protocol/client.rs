@@ -0,0 +1,410 @@
+use std::collections::VecDeque;
+use std::io::{BufRead, BufReader, Read, Write};
+
+use super::{
+    ClientMessage, GameSummary, MessageDecoder, ProtocolError, ServerMessage, X1Command, X1Reply,
+};
+use crate::value::{Action, Evaluation};
+
+/// A blocking client speaking the CSA server protocol over a stream such as `TcpStream`.
+pub struct Client<S: Read + Write> {
+    stream: BufReader<S>,
+    decoder: MessageDecoder,
+    /// Messages received while waiting for the reply to an extended command.
+    pending: VecDeque<ServerMessage>,
+}
+
+impl<S: Read + Write> Client<S> {
+    pub fn new(stream: S) -> Client<S> {
+        Client {
+            stream: BufReader::new(stream),
+            decoder: MessageDecoder::default(),
+            pending: VecDeque::new(),
+        }
+    }
+
+    pub fn get_ref(&self) -> &S {
+        self.stream.get_ref()
+    }
+
+    pub fn into_inner(self) -> S {
+        self.stream.into_inner()
+    }
+
+    /// Logs in and waits for the server to accept it.
+    pub fn login(&mut self, name: &str, password: &str) -> Result<(), ProtocolError> {
+        self.send(&ClientMessage::Login {
+            name: name.to_string(),
+            password: password.to_string(),
+        })?;
+        check_login(self.recv_skipping_keep_alive()?)
+    }
+
+    /// Logs in with the x1 extended mode, which enables [`Client::command`].
+    pub fn login_x1(&mut self, name: &str, password: &str) -> Result<(), ProtocolError> {
+        self.send(&ClientMessage::LoginX1 {
+            name: name.to_string(),
+            password: password.to_string(),
+        })?;
+        check_login(self.recv_skipping_keep_alive()?)?;
+        check_login_x1(self.recv_skipping_keep_alive()?)
+    }
+
+    pub fn logout(&mut self) -> Result<(), ProtocolError> {
+        self.send(&ClientMessage::Logout)?;
+        check_logout(self.recv_skipping_keep_alive()?)
+    }
+
+    /// Waits until the server offers a game.
+    pub fn wait_game_summary(&mut self) -> Result<GameSummary, ProtocolError> {
+        check_game_summary(self.recv_skipping_keep_alive()?)
+    }
+
+    /// Accepts the offered game and waits for it to start.
+    pub fn agree(&mut self, game_id: &str) -> Result<(), ProtocolError> {
+        self.send(&ClientMessage::Agree(game_id.to_string()))?;
+        check_agree(game_id, self.recv_skipping_keep_alive()?)
+    }
+
+    /// Declines the offered game.
+    pub fn reject(&mut self, game_id: &str) -> Result<(), ProtocolError> {
+        self.send(&ClientMessage::Reject(game_id.to_string()))?;
+        check_reject(game_id, self.recv_skipping_keep_alive()?)
+    }
+
+    /// Sends a move or a special move such as `%TORYO`.
+    ///
+    /// The server echoes the move back with the time spent, which is received by [`Client::recv`].
+    pub fn send_move(&mut self, action: &Action) -> Result<(), ProtocolError> {
+        self.send(&ClientMessage::Move(*action))
+    }
+
+    /// Sends an extended command and collects the lines of its reply, without the final `+OK`.
+    ///
+    /// Other messages arriving in the meantime, such as chat lines, are kept for [`Client::recv`].
+    /// Commands without a reply, `%%CHAT` and `%%GAME`, return an empty list.
+    pub fn command(&mut self, command: &X1Command) -> Result<Vec<X1Reply>, ProtocolError> {
+        self.send(&ClientMessage::X1(command.clone()))?;
+        let Some(tag) = command.reply_tag() else {
+            return Ok(Vec::new());
+        };
+        let subject = match *command {
+            X1Command::Monitor2On(ref game_id) => Some(game_id.as_str()),
+            _ => None,
+        };
+
+        let mut replies = Vec::new();
+        loop {
+            match self.read_message()? {
+                ServerMessage::X1(reply) if reply.tag == "ERROR" => {
+                    return Err(ProtocolError::CommandFailed(reply.body));
+                }
+                ServerMessage::X1(reply)
+                    if reply.tag == tag && reply.subject.as_deref() == subject =>
+                {
+                    // `%%MONITOR2OFF` is answered by a single line.
+                    if reply.is_ok() || tag == "MONITOR2OFF" {
+                        return Ok(replies);
+                    }
+                    replies.push(reply);
+                }
+                ServerMessage::KeepAlive => {}
+                message => self.pending.push_back(message),
+            }
+        }
+    }
+
+    /// Sends a move with the engine's evaluation, which servers such as Floodgate write to the record.
+    pub fn send_move_with_evaluation(
+        &mut self,
+        action: &Action,
+        evaluation: &Evaluation,
+    ) -> Result<(), ProtocolError> {
+        self.send(&ClientMessage::MoveWithEvaluation(
+            *action,
+            evaluation.clone(),
+        ))
+    }
+
+    pub fn send(&mut self, message: &ClientMessage) -> Result<(), ProtocolError> {
+        let stream = self.stream.get_mut();
+        writeln!(stream, "{message}")?;
+        stream.flush()?;
+        Ok(())
+    }
+
+    /// Receives the next message, including keep-alive empty lines.
+    pub fn recv(&mut self) -> Result<ServerMessage, ProtocolError> {
+        match self.pending.pop_front() {
+            Some(message) => Ok(message),
+            None => self.read_message(),
+        }
+    }
+
+    fn read_message(&mut self) -> Result<ServerMessage, ProtocolError> {
+        loop {
+            let line = self.read_line()?;
+            if let Some(message) = self.decoder.decode(&line)? {
+                return Ok(message);
+            }
+        }
+    }
+
+    fn recv_skipping_keep_alive(&mut self) -> Result<ServerMessage, ProtocolError> {
+        loop {
+            match self.recv()? {
+                ServerMessage::KeepAlive => continue,
+                message => return Ok(message),
+            }
+        }
+    }
+
+    fn read_line(&mut self) -> Result<String, ProtocolError> {
+        let mut line = String::new();
+        if self.stream.read_line(&mut line)? == 0 {
+            return Err(ProtocolError::ConnectionClosed);
+        }
+
+        let len = line.trim_end_matches(['\r', '\n']).len();
+        line.truncate(len);
+        Ok(line)
+    }
+}
+
+pub(super) fn check_login(message: ServerMessage) -> Result<(), ProtocolError> {
+    match message {
+        ServerMessage::LoginOk(_) => Ok(()),
+        ServerMessage::LoginIncorrect => Err(ProtocolError::LoginFailed),
+        other => Err(unexpected(other)),
+    }
+}
+
+pub(super) fn check_login_x1(message: ServerMessage) -> Result<(), ProtocolError> {
+    match message {
+        ServerMessage::X1(ref reply) if reply.tag == "LOGIN" && reply.is_ok() => Ok(()),
+        other => Err(unexpected(other)),
+    }
+}
+
+pub(super) fn check_logout(message: ServerMessage) -> Result<(), ProtocolError> {
+    match message {
+        ServerMessage::LogoutCompleted => Ok(()),
+        other => Err(unexpected(other)),
+    }
+}
+
+pub(super) fn check_game_summary(message: ServerMessage) -> Result<GameSummary, ProtocolError> {
+    match message {
+        ServerMessage::GameSummary(summary) => Ok(*summary),
+        other => Err(unexpected(other)),
+    }
+}
+
+pub(super) fn check_agree(game_id: &str, message: ServerMessage) -> Result<(), ProtocolError> {
+    match message {
+        ServerMessage::Start(ref id) if id == game_id => Ok(()),
+        ServerMessage::Reject {
+            game_id: ref id,
+            by,
+        } if id == game_id => Err(ProtocolError::Rejected(by)),
+        other => Err(unexpected(other)),
+    }
+}
+
+pub(super) fn check_reject(game_id: &str, message: ServerMessage) -> Result<(), ProtocolError> {
+    match message {
+        ServerMessage::Reject {
+            game_id: ref id, ..
+        } if id == game_id => Ok(()),
+        other => Err(unexpected(other)),
+    }
+}
+
+fn unexpected(message: ServerMessage) -> ProtocolError {
+    ProtocolError::UnexpectedMessage(format!("{message:?}"))
+}
+
+////////////////////////////////////////////////////////////////////////////////
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::protocol::{GameEndReason, GameOutcome};
+    use crate::value::{Color, MoveFrom, PieceType, Square};
+    use std::io::{self, Cursor};
+    use std::time::Duration;
+
+    struct MockStream {
+        input: Cursor<Vec<u8>>,
+        output: Vec<u8>,
+    }
+
+    impl MockStream {
+        fn new(input: &str) -> MockStream {
+            MockStream {
+                input: Cursor::new(input.as_bytes().to_vec()),
+                output: Vec::new(),
+            }
+        }
+    }
+
+    impl Read for MockStream {
+        fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+            self.input.read(buf)
+        }
+    }
+
+    impl Write for MockStream {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            self.output.write(buf)
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    const SESSION: &str = "\
+LOGIN:alice OK
+BEGIN Game_Summary
+Protocol_Version:1.2
+Game_ID:g1
+Name+:alice
+Name-:bob
+Your_Turn:+
+To_Move:+
+BEGIN Time
+Time_Unit:1sec
+Total_Time:600
+Byoyomi:10
+END Time
+BEGIN Position
+PI
++
+END Position
+END Game_Summary
+START:g1
++7776FU,T3
+
+-3334FU,T5
+%TORYO,T1
+#RESIGN
+#LOSE
+LOGOUT:completed
+";
+
+    #[test]
+    fn session() {
+        let mut client = Client::new(MockStream::new(SESSION));
+
+        client.login("alice", "secret").unwrap();
+        let summary = client.wait_game_summary().unwrap();
+        assert_eq!(summary.game_id, "g1");
+        assert_eq!(summary.your_turn, Color::Black);
+        client.agree(&summary.game_id).unwrap();
+
+        client
+            .send_move(&Action::Move(
+                Color::Black,
+                MoveFrom::Board(Square::new(7, 7).unwrap()),
+                Square::new(7, 6).unwrap(),
+                PieceType::Pawn,
+            ))
+            .unwrap();
+        let mut events = Vec::new();
+        loop {
+            let message = client.recv().unwrap();
+            let done = matches!(message, ServerMessage::Result(_));
+            events.push(message);
+            if done {
+                break;
+            }
+        }
+        client.logout().unwrap();
+
+        assert_eq!(events.len(), 6);
+        assert!(
+            matches!(events[0], ServerMessage::Move(ref m) if m.time == Some(Duration::from_secs(3)))
+        );
+        assert_eq!(events[1], ServerMessage::KeepAlive);
+        assert!(matches!(events[3], ServerMessage::Move(ref m) if m.action == Action::Toryo));
+        assert_eq!(events[4], ServerMessage::GameEnd(GameEndReason::Resign));
+        assert_eq!(events[5], ServerMessage::Result(GameOutcome::Lose));
+        assert_eq!(
+            String::from_utf8(client.into_inner().output).unwrap(),
+            "LOGIN alice secret\nAGREE g1\n+7776FU\nLOGOUT\n"
+        );
+    }
+
+    #[test]
+    fn x1_commands() {
+        let mut client = Client::new(MockStream::new(
+            "\
+LOGIN:alice OK
+##[LOGIN] +OK x1
+##[WHO] alice x1 connected
+##[CHAT][bob] hello
+##[WHO] bob CSA game
+##[WHO] +OK
+##[MONITOR2][g1] PI
+##[MONITOR2][g1] +
+##[MONITOR2][g1] +OK
+##[MONITOR2OFF] g1
+",
+        ));
+
+        client.login_x1("alice", "secret").unwrap();
+        let who = client.command(&X1Command::Who).unwrap();
+        assert_eq!(who.len(), 2);
+        assert_eq!(who[1].body, "bob CSA game");
+        let game = client
+            .command(&X1Command::Monitor2On("g1".to_string()))
+            .unwrap();
+        assert_eq!(game.len(), 2);
+        client
+            .command(&X1Command::Monitor2Off("g1".to_string()))
+            .unwrap();
+        assert_eq!(
+            client.command(&X1Command::Chat("hi".to_string())).unwrap(),
+            []
+        );
+
+        assert_eq!(
+            client.recv().unwrap(),
+            ServerMessage::X1(X1Reply::new("CHAT", Some("bob"), "hello"))
+        );
+        assert!(matches!(
+            client.recv(),
+            Err(ProtocolError::ConnectionClosed)
+        ));
+        assert_eq!(
+            String::from_utf8(client.into_inner().output).unwrap(),
+            "LOGIN alice secret x1\n%%WHO\n%%MONITOR2ON g1\n%%MONITOR2OFF g1\n%%CHAT hi\n"
+        );
+    }
+
+    #[test]
+    fn errors() {
+        let mut client = Client::new(MockStream::new("LOGIN:incorrect\n"));
+        assert!(matches!(
+            client.login("alice", "wrong"),
+            Err(ProtocolError::LoginFailed)
+        ));
+        assert!(matches!(
+            client.recv(),
+            Err(ProtocolError::ConnectionClosed)
+        ));
+
+        let mut client = Client::new(MockStream::new("##[ERROR] cannot find the game\n"));
+        assert!(matches!(
+            client.command(&X1Command::Show("g1".to_string())),
+            Err(ProtocolError::CommandFailed(ref message)) if message == "cannot find the game"
+        ));
+
+        let mut client = Client::new(MockStream::new("REJECT:g1 by bob\n"));
+        assert!(matches!(
+            client.agree("g1"),
+            Err(ProtocolError::Rejected(ref by)) if by == "bob"
+        ));
+    }
+}
@@ -0,0 +1,396 @@
+use std::fmt;
+
+use super::{parse_game_summary, GameSummary, X1Command, X1Reply};
+use crate::parser::{parse_move_line, CsaError};
+use crate::value::{Action, Evaluation, MoveRecord};
+
+/// Why a game ended, announced by the server before its result.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum GameEndReason {
+    /// `#RESIGN`
+    Resign,
+    /// `#TIME_UP`
+    TimeUp,
+    /// `#ILLEGAL_MOVE`
+    IllegalMove,
+    /// `#SENNICHITE`
+    Sennichite,
+    /// `#OUTE_SENNICHITE`, a repetition by perpetual check.
+    OuteSennichite,
+    /// `#JISHOGI`, including a successful declaration by `%KACHI`.
+    Jishogi,
+    /// `#MAX_MOVES`
+    MaxMoves,
+    /// `#CHUDAN`
+    Chudan,
+    /// `#ABNORMAL`, the opponent left the game.
+    Abnormal,
+}
+
+impl GameEndReason {
+    const ALL: [GameEndReason; 9] = [
+        GameEndReason::Resign,
+        GameEndReason::TimeUp,
+        GameEndReason::IllegalMove,
+        GameEndReason::Sennichite,
+        GameEndReason::OuteSennichite,
+        GameEndReason::Jishogi,
+        GameEndReason::MaxMoves,
+        GameEndReason::Chudan,
+        GameEndReason::Abnormal,
+    ];
+}
+
+impl fmt::Display for GameEndReason {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            GameEndReason::Resign => write!(f, "#RESIGN"),
+            GameEndReason::TimeUp => write!(f, "#TIME_UP"),
+            GameEndReason::IllegalMove => write!(f, "#ILLEGAL_MOVE"),
+            GameEndReason::Sennichite => write!(f, "#SENNICHITE"),
+            GameEndReason::OuteSennichite => write!(f, "#OUTE_SENNICHITE"),
+            GameEndReason::Jishogi => write!(f, "#JISHOGI"),
+            GameEndReason::MaxMoves => write!(f, "#MAX_MOVES"),
+            GameEndReason::Chudan => write!(f, "#CHUDAN"),
+            GameEndReason::Abnormal => write!(f, "#ABNORMAL"),
+        }
+    }
+}
+
+/// The result of a game from the receiver's point of view.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum GameOutcome {
+    /// `#WIN`
+    Win,
+    /// `#LOSE`
+    Lose,
+    /// `#DRAW`
+    Draw,
+    /// `#CENSORED`, a draw forced by the server.
+    Censored,
+}
+
+impl GameOutcome {
+    const ALL: [GameOutcome; 4] = [
+        GameOutcome::Win,
+        GameOutcome::Lose,
+        GameOutcome::Draw,
+        GameOutcome::Censored,
+    ];
+}
+
+impl fmt::Display for GameOutcome {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            GameOutcome::Win => write!(f, "#WIN"),
+            GameOutcome::Lose => write!(f, "#LOSE"),
+            GameOutcome::Draw => write!(f, "#DRAW"),
+            GameOutcome::Censored => write!(f, "#CENSORED"),
+        }
+    }
+}
+
+////////////////////////////////////////////////////////////////////////////////
+
+/// A message sent from a CSA server to a client.
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub enum ServerMessage {
+    /// An empty line sent to keep the connection alive.
+    KeepAlive,
+    /// `LOGIN:<name> OK`
+    LoginOk(String),
+    /// `LOGIN:incorrect`
+    LoginIncorrect,
+    /// `LOGOUT:completed`
+    LogoutCompleted,
+    /// A `BEGIN Game_Summary` block.
+    GameSummary(Box<GameSummary>),
+    /// `START:<game_id>`
+    Start(String),
+    /// `REJECT:<game_id> by <name>`
+    Reject {
+        game_id: String,
+        by: String,
+    },
+    /// A move or a special move with the time spent, e.g. `+7776FU,T12`.
+    ///
+    /// A comment such as `'* 30 -3334FU` at the end of the line is kept in [`MoveRecord::comments`].
+    Move(MoveRecord),
+    GameEnd(GameEndReason),
+    Result(GameOutcome),
+    /// A `##[...]` line of the x1 extended mode.
+    X1(X1Reply),
+    /// Any other line.
+    Other(String),
+}
+
+impl ServerMessage {
+    /// Parses a single line without the line terminator.
+    ///
+    /// `BEGIN Game_Summary` spans multiple lines and has to be read by a client,
+    /// so it is returned as [`ServerMessage::Other`] here.
+    pub fn parse(line: &str) -> ServerMessage {
+        if line.is_empty() {
+            return ServerMessage::KeepAlive;
+        }
+
+        if let Some(rest) = line.strip_prefix("LOGIN:") {
+            if rest == "incorrect" {
+                return ServerMessage::LoginIncorrect;
+            }
+            if let Some(name) = rest.strip_suffix(" OK") {
+                return ServerMessage::LoginOk(name.to_string());
+            }
+        } else if line == "LOGOUT:completed" {
+            return ServerMessage::LogoutCompleted;
+        } else if let Some(game_id) = line.strip_prefix("START:") {
+            return ServerMessage::Start(game_id.to_string());
+        } else if let Some(rest) = line.strip_prefix("REJECT:") {
+            if let Some((game_id, by)) = rest.split_once(" by ") {
+                return ServerMessage::Reject {
+                    game_id: game_id.to_string(),
+                    by: by.to_string(),
+                };
+            }
+        } else if let Some(reply) = X1Reply::parse(line) {
+            return ServerMessage::X1(reply);
+        } else if line.starts_with('#') {
+            if let Some(&reason) = GameEndReason::ALL.iter().find(|r| r.to_string() == line) {
+                return ServerMessage::GameEnd(reason);
+            }
+            if let Some(&outcome) = GameOutcome::ALL.iter().find(|o| o.to_string() == line) {
+                return ServerMessage::Result(outcome);
+            }
+        } else if let Ok(record) = parse_move_line(line) {
+            return ServerMessage::Move(record);
+        }
+
+        ServerMessage::Other(line.to_string())
+    }
+}
+
+/// Assembles lines received from a server into messages, collecting multi-line blocks.
+#[derive(Debug, Default)]
+pub(crate) struct MessageDecoder {
+    summary: Option<String>,
+}
+
+impl MessageDecoder {
+    /// Takes a line without the line terminator and returns a message if it completes one.
+    pub(crate) fn decode(&mut self, line: &str) -> Result<Option<ServerMessage>, CsaError> {
+        if let Some(ref mut block) = self.summary {
+            block.push_str(line);
+            block.push('\n');
+            if line != "END Game_Summary" {
+                return Ok(None);
+            }
+
+            let summary = parse_game_summary(&self.summary.take().unwrap())?;
+            return Ok(Some(ServerMessage::GameSummary(Box::new(summary))));
+        }
+
+        if line == "BEGIN Game_Summary" {
+            self.summary = Some(format!("{line}\n"));
+            return Ok(None);
+        }
+
+        Ok(Some(ServerMessage::parse(line)))
+    }
+}
+
+////////////////////////////////////////////////////////////////////////////////
+
+/// A message sent from a client to a CSA server.
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub enum ClientMessage {
+    Login {
+        name: String,
+        password: String,
+    },
+    /// `LOGIN <name> <password> x1`, which enables the extended commands.
+    LoginX1 {
+        name: String,
+        password: String,
+    },
+    Logout,
+    Agree(String),
+    Reject(String),
+    Move(Action),
+    /// A move with the engine's evaluation, as in `+7776FU,'* 30 -3334FU`.
+    MoveWithEvaluation(Action, Evaluation),
+    X1(X1Command),
+}
+
+impl fmt::Display for ClientMessage {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            ClientMessage::Login {
+                ref name,
+                ref password,
+            } => write!(f, "LOGIN {name} {password}"),
+            ClientMessage::LoginX1 {
+                ref name,
+                ref password,
+            } => write!(f, "LOGIN {name} {password} x1"),
+            ClientMessage::Logout => write!(f, "LOGOUT"),
+            ClientMessage::Agree(ref game_id) => write!(f, "AGREE {game_id}"),
+            ClientMessage::Reject(ref game_id) => write!(f, "REJECT {game_id}"),
+            ClientMessage::Move(ref action) => write!(f, "{action}"),
+            ClientMessage::MoveWithEvaluation(ref action, ref evaluation) => {
+                write!(f, "{action},'{evaluation}")
+            }
+            ClientMessage::X1(ref command) => write!(f, "{command}"),
+        }
+    }
+}
+
+////////////////////////////////////////////////////////////////////////////////
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::value::{Color, MoveFrom, PieceType, Square};
+    use std::time::Duration;
+
+    #[test]
+    fn parse_server_message() {
+        let cases = [
+            ("", ServerMessage::KeepAlive),
+            (
+                "LOGIN:alice OK",
+                ServerMessage::LoginOk("alice".to_string()),
+            ),
+            ("LOGIN:incorrect", ServerMessage::LoginIncorrect),
+            ("LOGOUT:completed", ServerMessage::LogoutCompleted),
+            ("START:g1", ServerMessage::Start("g1".to_string())),
+            (
+                "REJECT:g1 by bob",
+                ServerMessage::Reject {
+                    game_id: "g1".to_string(),
+                    by: "bob".to_string(),
+                },
+            ),
+            (
+                "+7776FU,T12",
+                ServerMessage::Move(MoveRecord {
+                    action: Action::Move(
+                        Color::Black,
+                        MoveFrom::Board(Square::new(7, 7).unwrap()),
+                        Square::new(7, 6).unwrap(),
+                        PieceType::Pawn,
+                    ),
+                    time: Some(Duration::from_secs(12)),
+                    comments: vec![],
+                }),
+            ),
+            (
+                "%TORYO",
+                ServerMessage::Move(MoveRecord {
+                    action: Action::Toryo,
+                    time: None,
+                    comments: vec![],
+                }),
+            ),
+            (
+                "-3334FU,T5,'** -30 +2726FU",
+                ServerMessage::Move(MoveRecord {
+                    action: Action::Move(
+                        Color::White,
+                        MoveFrom::Board(Square::new(3, 3).unwrap()),
+                        Square::new(3, 4).unwrap(),
+                        PieceType::Pawn,
+                    ),
+                    time: Some(Duration::from_secs(5)),
+                    comments: vec!["** -30 +2726FU".to_string()],
+                }),
+            ),
+            ("#RESIGN", ServerMessage::GameEnd(GameEndReason::Resign)),
+            (
+                "#OUTE_SENNICHITE",
+                ServerMessage::GameEnd(GameEndReason::OuteSennichite),
+            ),
+            ("#WIN", ServerMessage::Result(GameOutcome::Win)),
+            ("#CENSORED", ServerMessage::Result(GameOutcome::Censored)),
+            (
+                "##[CHAT][bob] hi",
+                ServerMessage::X1(X1Reply::new("CHAT", Some("bob"), "hi")),
+            ),
+            ("+7776FU,Tx", ServerMessage::Other("+7776FU,Tx".to_string())),
+            ("#UNKNOWN", ServerMessage::Other("#UNKNOWN".to_string())),
+        ];
+
+        for (line, expected) in cases {
+            assert_eq!(ServerMessage::parse(line), expected, "{line}");
+        }
+    }
+
+    #[test]
+    fn decode_game_summary() {
+        let mut decoder = MessageDecoder::default();
+        let lines = [
+            "BEGIN Game_Summary",
+            "Game_ID:g1",
+            "Name+:alice",
+            "Name-:bob",
+            "Your_Turn:+",
+            "BEGIN Position",
+            "PI",
+            "+",
+            "END Position",
+        ];
+        for line in lines {
+            assert_eq!(decoder.decode(line), Ok(None));
+        }
+
+        match decoder.decode("END Game_Summary") {
+            Ok(Some(ServerMessage::GameSummary(summary))) => assert_eq!(summary.game_id, "g1"),
+            other => panic!("unexpected {other:?}"),
+        }
+        assert_eq!(
+            decoder.decode("START:g1"),
+            Ok(Some(ServerMessage::Start("g1".to_string())))
+        );
+    }
+
+    #[test]
+    fn format_client_message() {
+        assert_eq!(
+            ClientMessage::Login {
+                name: "alice".to_string(),
+                password: "secret".to_string(),
+            }
+            .to_string(),
+            "LOGIN alice secret"
+        );
+        assert_eq!(
+            ClientMessage::LoginX1 {
+                name: "alice".to_string(),
+                password: "secret".to_string(),
+            }
+            .to_string(),
+            "LOGIN alice secret x1"
+        );
+        assert_eq!(ClientMessage::Logout.to_string(), "LOGOUT");
+        assert_eq!(
+            ClientMessage::Agree("g1".to_string()).to_string(),
+            "AGREE g1"
+        );
+        assert_eq!(
+            ClientMessage::Reject("g1".to_string()).to_string(),
+            "REJECT g1"
+        );
+        assert_eq!(ClientMessage::Move(Action::Kachi).to_string(), "%KACHI");
+        assert_eq!(
+            ClientMessage::MoveWithEvaluation(
+                Action::Toryo,
+                Evaluation {
+                    score: -9999,
+                    pv: vec![],
+                },
+            )
+            .to_string(),
+            "%TORYO,'* -9999"
+        );
+        assert_eq!(ClientMessage::X1(X1Command::Who).to_string(), "%%WHO");
+    }
+}
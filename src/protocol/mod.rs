@@ -0,0 +1,76 @@
+#[cfg(feature = "tokio")]
+mod async_client;
+mod client;
+mod message;
+mod summary;
+mod x1;
+
+#[cfg(feature = "tokio")]
+pub use self::async_client::*;
+pub use self::client::*;
+pub use self::message::*;
+pub use self::summary::*;
+pub use self::x1::*;
+
+use std::error::Error;
+use std::fmt;
+use std::io;
+
+use crate::parser::CsaError;
+
+#[derive(Debug)]
+pub enum ProtocolError {
+    Io(io::Error),
+    /// The server closed the connection.
+    ConnectionClosed,
+    /// The server refused the login.
+    LoginFailed,
+    /// The game was rejected by the named player.
+    Rejected(String),
+    /// The server sent a message which is not allowed in the current state.
+    UnexpectedMessage(String),
+    /// Nothing was received within the read timeout.
+    TimedOut,
+    /// The server answered an extended command with `##[ERROR]`.
+    CommandFailed(String),
+    Record(CsaError),
+}
+
+impl fmt::Display for ProtocolError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            ProtocolError::Io(ref e) => write!(f, "I/O error: {e}"),
+            ProtocolError::ConnectionClosed => write!(f, "connection closed by the server"),
+            ProtocolError::LoginFailed => write!(f, "login failed"),
+            ProtocolError::Rejected(ref name) => write!(f, "the game was rejected by {name}"),
+            ProtocolError::UnexpectedMessage(ref line) => {
+                write!(f, "unexpected message: {line}")
+            }
+            ProtocolError::TimedOut => write!(f, "timed out waiting for the server"),
+            ProtocolError::CommandFailed(ref message) => write!(f, "command failed: {message}"),
+            ProtocolError::Record(ref e) => write!(f, "invalid record: {e}"),
+        }
+    }
+}
+
+impl Error for ProtocolError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match *self {
+            ProtocolError::Io(ref e) => Some(e),
+            ProtocolError::Record(ref e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<io::Error> for ProtocolError {
+    fn from(e: io::Error) -> Self {
+        ProtocolError::Io(e)
+    }
+}
+
+impl From<CsaError> for ProtocolError {
+    fn from(e: CsaError) -> Self {
+        ProtocolError::Record(e)
+    }
+}
@@ -0,0 +1,524 @@
+use std::fmt;
+use std::str;
+use std::time::Duration;
+
+use nom::branch::alt;
+use nom::bytes::complete::{is_not, tag, take_till};
+use nom::character::complete::digit1;
+use nom::combinator::{map, map_res, opt};
+use nom::error::{Error, ErrorKind};
+use nom::multi::many0;
+use nom::sequence::{delimited, pair, separated_pair, terminated};
+use nom::*;
+
+use crate::clock::TimeControl;
+use crate::parser::{line_sep, move_records, position, CsaError};
+use crate::value::{Color, GameRecord, MoveRecord, Position, TimeLimit};
+
+/// Conditions of a game, sent by the server in a `BEGIN Game_Summary` block.
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct GameSummary {
+    pub protocol_version: Option<String>,
+    pub protocol_mode: Option<String>,
+    pub format: Option<String>,
+    pub declaration: Option<String>,
+    pub game_id: String,
+    pub black_player: String,
+    pub white_player: String,
+    /// The color assigned to the receiver of the summary.
+    pub your_turn: Color,
+    pub rematch_on_draw: Option<bool>,
+    /// The side to move after `moves` are played.
+    pub to_move: Color,
+    pub max_moves: Option<u32>,
+    /// The `BEGIN Time` block, where `None` means no time limit.
+    pub time: Option<TimeControl>,
+    pub position: Position,
+    /// Moves already played from `position`, e.g. when a game is resumed.
+    pub moves: Vec<MoveRecord>,
+}
+
+impl GameSummary {
+    /// Creates a summary of a game starting from `position`, sent to Black, with the optional
+    /// fields left out and no time limit.
+    pub fn new(
+        game_id: &str,
+        black_player: &str,
+        white_player: &str,
+        position: Position,
+    ) -> GameSummary {
+        GameSummary {
+            protocol_version: None,
+            protocol_mode: None,
+            format: None,
+            declaration: None,
+            game_id: game_id.to_string(),
+            black_player: black_player.to_string(),
+            white_player: white_player.to_string(),
+            your_turn: Color::Black,
+            rematch_on_draw: None,
+            to_move: position.side_to_move,
+            max_moves: None,
+            time: None,
+            position,
+            moves: Vec::new(),
+        }
+    }
+
+    /// Creates a record of the game so far, with `Game_ID` as its event name.
+    pub fn to_game_record(&self) -> GameRecord {
+        GameRecord {
+            black_player: Some(self.black_player.clone()),
+            white_player: Some(self.white_player.clone()),
+            event: Some(self.game_id.clone()),
+            time_limit: self.time.as_ref().map(|time| TimeLimit {
+                main_time: time.main_time,
+                byoyomi: time.byoyomi,
+                increment: time.increment,
+            }),
+            start_pos: self.position.clone(),
+            moves: self.moves.clone(),
+            ..Default::default()
+        }
+    }
+}
+
+impl fmt::Display for GameSummary {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "BEGIN Game_Summary")?;
+
+        let fields = [
+            ("Protocol_Version", self.protocol_version.clone()),
+            ("Protocol_Mode", self.protocol_mode.clone()),
+            ("Format", self.format.clone()),
+            ("Declaration", self.declaration.clone()),
+            ("Game_ID", Some(self.game_id.clone())),
+            ("Name+", Some(self.black_player.clone())),
+            ("Name-", Some(self.white_player.clone())),
+            ("Your_Turn", Some(self.your_turn.to_string())),
+            (
+                "Rematch_On_Draw",
+                self.rematch_on_draw
+                    .map(|b| if b { "YES" } else { "NO" }.to_string()),
+            ),
+            ("To_Move", Some(self.to_move.to_string())),
+            ("Max_Moves", self.max_moves.map(|n| n.to_string())),
+        ];
+        for (key, value) in &fields {
+            if let Some(ref value) = *value {
+                writeln!(f, "{key}:{value}")?;
+            }
+        }
+
+        if let Some(ref time) = self.time {
+            let amount = |d: Duration| time.to_units(d);
+
+            writeln!(f, "BEGIN Time")?;
+            writeln!(f, "Time_Unit:{}", TimeUnit(time.effective_unit()))?;
+            writeln!(f, "Total_Time:{}", amount(time.main_time))?;
+            writeln!(f, "Byoyomi:{}", amount(time.byoyomi))?;
+            if !time.increment.is_zero() {
+                writeln!(f, "Increment:{}", amount(time.increment))?;
+            }
+            writeln!(
+                f,
+                "Least_Time_Per_Move:{}",
+                amount(time.least_time_per_move)
+            )?;
+            writeln!(f, "END Time")?;
+        }
+
+        writeln!(f, "BEGIN Position")?;
+        write!(f, "{}", self.position)?;
+        for record in &self.moves {
+            match (record.time, &self.time) {
+                (Some(t), Some(time)) => writeln!(f, "{},T{}", record.action, time.to_units(t))?,
+                (Some(t), None) => writeln!(f, "{},T{}", record.action, t.as_secs())?,
+                (None, _) => writeln!(f, "{}", record.action)?,
+            }
+        }
+        writeln!(f, "END Position")?;
+
+        writeln!(f, "END Game_Summary")
+    }
+}
+
+/// Formats a `Time_Unit` value such as `1sec`.
+struct TimeUnit(Duration);
+
+impl fmt::Display for TimeUnit {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let millis = self.0.as_millis();
+        if millis.is_multiple_of(60_000) {
+            write!(f, "{}min", millis / 60_000)
+        } else if millis.is_multiple_of(1000) {
+            write!(f, "{}sec", millis / 1000)
+        } else {
+            write!(f, "{millis}msec")
+        }
+    }
+}
+
+////////////////////////////////////////////////////////////////////////////////
+
+enum SummaryItem<'a> {
+    Field(&'a str, &'a str),
+    Time(Vec<(&'a str, &'a str)>),
+    Position(Box<Position>, Vec<MoveRecord>),
+}
+
+fn field(input: &[u8]) -> IResult<&[u8], (&str, &str)> {
+    terminated(
+        separated_pair(
+            map_res(is_not(":\r\n"), str::from_utf8),
+            tag(":"),
+            map_res(take_till(|c| c == b'\r' || c == b'\n'), str::from_utf8),
+        ),
+        line_sep,
+    )(input)
+}
+
+fn block<'a, O>(
+    name: &'static str,
+    body: impl FnMut(&'a [u8]) -> IResult<&'a [u8], O>,
+) -> impl FnMut(&'a [u8]) -> IResult<&'a [u8], O> {
+    delimited(
+        terminated(pair(tag("BEGIN "), tag(name)), line_sep),
+        body,
+        terminated(pair(tag("END "), tag(name)), opt(line_sep)),
+    )
+}
+
+fn summary_item(input: &[u8]) -> IResult<&[u8], SummaryItem<'_>> {
+    alt((
+        map(block("Time", many0(field)), SummaryItem::Time),
+        map(
+            block("Position", pair(position, move_records)),
+            |(pos, moves)| SummaryItem::Position(Box::new(pos), moves),
+        ),
+        map(field, |(key, value)| SummaryItem::Field(key, value)),
+    ))(input)
+}
+
+fn game_summary(input: &[u8]) -> IResult<&[u8], GameSummary> {
+    let (rest, items) = block("Game_Summary", many0(summary_item))(input)?;
+
+    match build_summary(items) {
+        Some(summary) => Ok((rest, summary)),
+        None => Err(Err::Failure(Error::new(input, ErrorKind::Verify))),
+    }
+}
+
+fn build_summary(items: Vec<SummaryItem>) -> Option<GameSummary> {
+    let mut fields = Vec::new();
+    let mut time = None;
+    let mut start = None;
+
+    for item in items {
+        match item {
+            SummaryItem::Field(key, value) => fields.push((key, value)),
+            SummaryItem::Time(t) => time = Some(build_time_control(&t)?),
+            SummaryItem::Position(pos, moves) => start = Some((*pos, moves)),
+        }
+    }
+
+    let find = |key: &str| fields.iter().find(|f| f.0 == key).map(|f| f.1);
+    let text = |key: &str| find(key).map(|s| s.to_string());
+    let (position, mut moves) = start?;
+    // `T` of the moves counts `Time_Unit`s.
+    if let Some(ref time) = time {
+        for mv in &mut moves {
+            mv.time = mv.time.map(|t| time.from_units(t.as_secs()));
+        }
+    }
+    let to_move = match find("To_Move") {
+        Some(c) => parse_color(c)?,
+        None if moves.len() % 2 == 0 => position.side_to_move,
+        None => position.side_to_move.flip(),
+    };
+    let rematch_on_draw = match find("Rematch_On_Draw") {
+        Some("YES") => Some(true),
+        Some("NO") => Some(false),
+        Some(_) => return None,
+        None => None,
+    };
+    let max_moves = match find("Max_Moves") {
+        Some(s) => Some(s.parse().ok()?),
+        None => None,
+    };
+
+    Some(GameSummary {
+        protocol_version: text("Protocol_Version"),
+        protocol_mode: text("Protocol_Mode"),
+        format: text("Format"),
+        declaration: text("Declaration"),
+        game_id: text("Game_ID")?,
+        black_player: text("Name+")?,
+        white_player: text("Name-")?,
+        your_turn: parse_color(find("Your_Turn")?)?,
+        rematch_on_draw,
+        to_move,
+        max_moves,
+        time,
+        position,
+        moves,
+    })
+}
+
+fn build_time_control(fields: &[(&str, &str)]) -> Option<TimeControl> {
+    let find = |key: &str| fields.iter().find(|f| f.0 == key).map(|f| f.1);
+    let unit = match find("Time_Unit") {
+        Some(s) => parse_time_unit(s)?,
+        None => Duration::from_secs(1),
+    };
+    let amount = |key: &str| -> Option<Duration> {
+        match find(key) {
+            Some(s) => unit.checked_mul(s.parse().ok()?),
+            None => Some(Duration::ZERO),
+        }
+    };
+
+    Some(TimeControl {
+        main_time: amount("Total_Time")?,
+        byoyomi: amount("Byoyomi")?,
+        increment: amount("Increment")?,
+        time_unit: unit,
+        least_time_per_move: amount("Least_Time_Per_Move")?,
+    })
+}
+
+fn parse_time_unit(s: &str) -> Option<Duration> {
+    let (_, (n, unit)): (_, (&[u8], &[u8])) = pair(
+        digit1::<_, Error<_>>,
+        alt((tag("msec"), tag("sec"), tag("min"), tag(""))),
+    )(s.as_bytes())
+    .ok()?;
+    let n = str::from_utf8(n).ok()?.parse().ok()?;
+
+    match unit {
+        b"msec" => Some(Duration::from_millis(n)),
+        b"min" => Some(Duration::from_secs(n * 60)),
+        _ => Some(Duration::from_secs(n)),
+    }
+}
+
+fn parse_color(s: &str) -> Option<Color> {
+    match s {
+        "+" => Some(Color::Black),
+        "-" => Some(Color::White),
+        _ => None,
+    }
+}
+
+/// Parses a `BEGIN Game_Summary` ... `END Game_Summary` block sent by a CSA server.
+pub fn parse_game_summary(s: &str) -> Result<GameSummary, CsaError> {
+    if let Ok((_, summary)) = game_summary(s.as_bytes()) {
+        Ok(summary)
+    } else {
+        Err(CsaError::ParseError())
+    }
+}
+
+////////////////////////////////////////////////////////////////////////////////
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::value::{Action, Handicap, MoveFrom, PieceType, Square};
+
+    fn summary() -> GameSummary {
+        GameSummary {
+            protocol_version: Some("1.2".to_string()),
+            protocol_mode: Some("Server".to_string()),
+            format: Some("Shogi 1.0".to_string()),
+            declaration: Some("Jishogi 1.1".to_string()),
+            your_turn: Color::White,
+            rematch_on_draw: Some(false),
+            to_move: Color::White,
+            max_moves: Some(256),
+            time: Some(TimeControl {
+                main_time: Duration::from_secs(300),
+                increment: Duration::from_secs(10),
+                ..TimeControl::default()
+            }),
+            moves: vec![MoveRecord {
+                action: Action::Move(
+                    Color::Black,
+                    MoveFrom::Board(Square::new(2, 7).unwrap()),
+                    Square::new(2, 6).unwrap(),
+                    PieceType::Pawn,
+                ),
+                time: Some(Duration::from_secs(3)),
+                comments: vec![],
+            }],
+            ..GameSummary::new(
+                "wdoor+floodgate-300-10F+a+b+20240101120000",
+                "a",
+                "b",
+                Position::from_handicap(Handicap::Even),
+            )
+        }
+    }
+
+    #[test]
+    fn format() {
+        let expected = "\
+BEGIN Game_Summary
+Protocol_Version:1.2
+Protocol_Mode:Server
+Format:Shogi 1.0
+Declaration:Jishogi 1.1
+Game_ID:wdoor+floodgate-300-10F+a+b+20240101120000
+Name+:a
+Name-:b
+Your_Turn:-
+Rematch_On_Draw:NO
+To_Move:-
+Max_Moves:256
+BEGIN Time
+Time_Unit:1sec
+Total_Time:300
+Byoyomi:0
+Increment:10
+Least_Time_Per_Move:0
+END Time
+BEGIN Position
+PI
++
++2726FU,T3
+END Position
+END Game_Summary
+";
+        assert_eq!(summary().to_string(), expected);
+        assert_eq!(parse_game_summary(expected).unwrap(), summary());
+    }
+
+    #[test]
+    fn format_in_time_units() {
+        let summary = GameSummary {
+            time: Some(TimeControl {
+                main_time: Duration::from_secs(600),
+                time_unit: Duration::from_secs(60),
+                ..TimeControl::default()
+            }),
+            moves: vec![MoveRecord {
+                time: Some(Duration::from_secs(120)),
+                ..summary().moves[0].clone()
+            }],
+            ..summary()
+        };
+        let s = summary.to_string();
+
+        assert!(s.contains("Time_Unit:1min\nTotal_Time:10\n"));
+        assert!(s.contains("+2726FU,T2\n"));
+        assert_eq!(parse_game_summary(&s).unwrap(), summary);
+    }
+
+    #[test]
+    fn format_time_unit() {
+        assert_eq!(TimeUnit(Duration::from_secs(1)).to_string(), "1sec");
+        assert_eq!(TimeUnit(Duration::from_secs(120)).to_string(), "2min");
+        assert_eq!(
+            TimeUnit(Duration::from_millis(1500)).to_string(),
+            "1500msec"
+        );
+    }
+
+    #[test]
+    fn to_game_record() {
+        let record = summary().to_game_record();
+
+        assert_eq!(record.black_player.as_deref(), Some("a"));
+        assert_eq!(record.white_player.as_deref(), Some("b"));
+        assert_eq!(
+            record.event.as_deref(),
+            Some("wdoor+floodgate-300-10F+a+b+20240101120000")
+        );
+        assert_eq!(
+            record.time_limit,
+            Some(TimeLimit {
+                main_time: Duration::from_secs(300),
+                byoyomi: Duration::ZERO,
+                increment: Duration::from_secs(10),
+            })
+        );
+        assert_eq!(record.moves.len(), 1);
+    }
+
+    #[test]
+    fn parse() {
+        let input = b"\
+BEGIN Game_Summary
+Protocol_Version:1.2
+Protocol_Mode:Server
+Format:Shogi 1.0
+Declaration:Jishogi 1.1
+Game_ID:20060505-CSA14-3-5-7
+Name+:TANUKI
+Name-:KITSUNE
+Your_Turn:-
+Rematch_On_Draw:NO
+To_Move:-
+BEGIN Time
+Time_Unit:1sec
+Total_Time:600
+Byoyomi:10
+Least_Time_Per_Move:1
+END Time
+BEGIN Position
+PI
++
++7776FU,T12
+END Position
+END Game_Summary
+";
+        let (rest, summary) = game_summary(input).unwrap();
+
+        assert!(rest.is_empty());
+        assert_eq!(summary.protocol_version.as_deref(), Some("1.2"));
+        assert_eq!(summary.game_id, "20060505-CSA14-3-5-7");
+        assert_eq!(summary.black_player, "TANUKI");
+        assert_eq!(summary.white_player, "KITSUNE");
+        assert_eq!(summary.your_turn, Color::White);
+        assert_eq!(summary.to_move, Color::White);
+        assert_eq!(summary.rematch_on_draw, Some(false));
+        assert_eq!(summary.max_moves, None);
+        assert_eq!(
+            summary.time,
+            Some(TimeControl {
+                main_time: Duration::from_secs(600),
+                byoyomi: Duration::from_secs(10),
+                increment: Duration::ZERO,
+                time_unit: Duration::from_secs(1),
+                least_time_per_move: Duration::from_secs(1),
+            })
+        );
+        assert_eq!(summary.position, Position::from_handicap(Handicap::Even));
+        assert_eq!(summary.moves.len(), 1);
+        assert_eq!(summary.moves[0].time, Some(Duration::from_secs(12)));
+    }
+
+    #[test]
+    fn parse_missing_fields() {
+        let input = b"\
+BEGIN Game_Summary
+Name+:TANUKI
+Name-:KITSUNE
+Your_Turn:+
+BEGIN Position
+PI
++
+END Position
+END Game_Summary
+";
+        assert!(game_summary(input).is_err());
+    }
+
+    #[test]
+    fn parse_time_units() {
+        assert_eq!(parse_time_unit("1sec"), Some(Duration::from_secs(1)));
+        assert_eq!(parse_time_unit("1min"), Some(Duration::from_secs(60)));
+        assert_eq!(parse_time_unit("100msec"), Some(Duration::from_millis(100)));
+        assert_eq!(parse_time_unit("sec"), None);
+    }
+}
@@ -0,0 +1,345 @@
+use std::fmt;
+
+use crate::parser::{parse_csa, CsaError};
+use crate::value::{Color, GameRecord};
+
+/// An extended command of shogi-server, available after logging in with `x1`.
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub enum X1Command {
+    /// `%%WHO`, lists the logged in players.
+    Who,
+    /// `%%LIST`, lists the games in progress.
+    List,
+    /// `%%SHOW <game_id>`
+    Show(String),
+    /// `%%MONITOR2ON <game_id>`, sends the game so far and then each move as it is played.
+    Monitor2On(String),
+    /// `%%MONITOR2OFF <game_id>`
+    Monitor2Off(String),
+    /// `%%CHAT <message>`
+    Chat(String),
+    /// `%%GAME <game_name> <+|-|*>`, waits for an opponent with the preferred color, if any.
+    Game {
+        game_name: String,
+        color: Option<Color>,
+    },
+    /// `%%RATING`
+    Rating,
+}
+
+impl X1Command {
+    /// Parses a command line such as `%%SHOW <game_id>`.
+    pub fn parse(line: &str) -> Option<X1Command> {
+        let rest = line.strip_prefix("%%")?;
+        let (name, arg) = rest.split_once(' ').unwrap_or((rest, ""));
+
+        match (name, arg) {
+            ("WHO", "") => Some(X1Command::Who),
+            ("LIST", "") => Some(X1Command::List),
+            ("RATING", "") => Some(X1Command::Rating),
+            ("SHOW", id) if !id.is_empty() => Some(X1Command::Show(id.to_string())),
+            ("MONITOR2ON", id) if !id.is_empty() => Some(X1Command::Monitor2On(id.to_string())),
+            ("MONITOR2OFF", id) if !id.is_empty() => Some(X1Command::Monitor2Off(id.to_string())),
+            ("CHAT", message) => Some(X1Command::Chat(message.to_string())),
+            ("GAME", arg) => {
+                let (game_name, color) = arg.split_once(' ')?;
+                let color = match color {
+                    "+" => Some(Color::Black),
+                    "-" => Some(Color::White),
+                    "*" => None,
+                    _ => return None,
+                };
+                Some(X1Command::Game {
+                    game_name: game_name.to_string(),
+                    color,
+                })
+            }
+            _ => None,
+        }
+    }
+
+    /// Returns the tag of the `##[...]` lines answering the command, if the server replies.
+    pub fn reply_tag(&self) -> Option<&'static str> {
+        match *self {
+            X1Command::Who => Some("WHO"),
+            X1Command::List => Some("LIST"),
+            X1Command::Show(_) => Some("SHOW"),
+            X1Command::Monitor2On(_) => Some("MONITOR2"),
+            X1Command::Monitor2Off(_) => Some("MONITOR2OFF"),
+            X1Command::Rating => Some("RATING"),
+            X1Command::Chat(_) | X1Command::Game { .. } => None,
+        }
+    }
+}
+
+impl fmt::Display for X1Command {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            X1Command::Who => write!(f, "%%WHO"),
+            X1Command::List => write!(f, "%%LIST"),
+            X1Command::Show(ref id) => write!(f, "%%SHOW {id}"),
+            X1Command::Monitor2On(ref id) => write!(f, "%%MONITOR2ON {id}"),
+            X1Command::Monitor2Off(ref id) => write!(f, "%%MONITOR2OFF {id}"),
+            X1Command::Chat(ref message) => write!(f, "%%CHAT {message}"),
+            X1Command::Game {
+                ref game_name,
+                color,
+            } => {
+                let color = match color {
+                    Some(Color::Black) => "+",
+                    Some(Color::White) => "-",
+                    None => "*",
+                };
+                write!(f, "%%GAME {game_name} {color}")
+            }
+            X1Command::Rating => write!(f, "%%RATING"),
+        }
+    }
+}
+
+////////////////////////////////////////////////////////////////////////////////
+
+/// A line of a reply to an extended command, framed as `##[<tag>][<subject>] <body>`.
+///
+/// The subject is present in `MONITOR2` lines, holding the game ID, and in `CHAT` lines, holding the sender.
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct X1Reply {
+    pub tag: String,
+    pub subject: Option<String>,
+    pub body: String,
+}
+
+impl X1Reply {
+    pub fn new(tag: &str, subject: Option<&str>, body: &str) -> X1Reply {
+        X1Reply {
+            tag: tag.to_string(),
+            subject: subject.map(|s| s.to_string()),
+            body: body.to_string(),
+        }
+    }
+
+    pub fn parse(line: &str) -> Option<X1Reply> {
+        let (tag, rest) = line.strip_prefix("##[")?.split_once(']')?;
+        let (subject, rest) = match rest.strip_prefix('[') {
+            Some(rest) => {
+                let (subject, rest) = rest.split_once(']')?;
+                (Some(subject.to_string()), rest)
+            }
+            None => (None, rest),
+        };
+        let body = match rest {
+            "" => "",
+            _ => rest.strip_prefix(' ')?,
+        };
+
+        Some(X1Reply {
+            tag: tag.to_string(),
+            subject,
+            body: body.to_string(),
+        })
+    }
+
+    /// Returns `true` for `+OK`, which terminates a multi-line reply.
+    pub fn is_ok(&self) -> bool {
+        self.body == "+OK" || self.body.starts_with("+OK ")
+    }
+}
+
+impl fmt::Display for X1Reply {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "##[{}]", self.tag)?;
+        if let Some(ref subject) = self.subject {
+            write!(f, "[{subject}]")?;
+        }
+        if !self.body.is_empty() {
+            write!(f, " {}", self.body)?;
+        }
+        Ok(())
+    }
+}
+
+////////////////////////////////////////////////////////////////////////////////
+
+/// An entry of the `%%WHO` reply, `<name> <protocol> <status>`.
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct WhoEntry {
+    pub name: String,
+    /// `x1` or `CSA`.
+    pub protocol: String,
+    /// e.g. `connected`, `game_waiting` or `game`.
+    pub status: String,
+}
+
+impl WhoEntry {
+    pub fn parse(body: &str) -> Option<WhoEntry> {
+        let mut fields = body.split(' ');
+        let entry = WhoEntry {
+            name: fields.next()?.to_string(),
+            protocol: fields.next()?.to_string(),
+            status: fields.next()?.to_string(),
+        };
+
+        fields.next().is_none().then_some(entry)
+    }
+}
+
+impl fmt::Display for WhoEntry {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{} {} {}", self.name, self.protocol, self.status)
+    }
+}
+
+/// An entry of the `%%RATING` reply, `<name> <rate> <wins> <losses>`.
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct RatingEntry {
+    pub name: String,
+    pub rate: i32,
+    pub wins: u32,
+    pub losses: u32,
+}
+
+impl RatingEntry {
+    pub fn parse(body: &str) -> Option<RatingEntry> {
+        let fields = body.split(' ').collect::<Vec<_>>();
+        match fields[..] {
+            [name, rate, wins, losses] => Some(RatingEntry {
+                name: name.to_string(),
+                rate: rate.parse().ok()?,
+                wins: wins.parse().ok()?,
+                losses: losses.parse().ok()?,
+            }),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for RatingEntry {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{} {} {} {}",
+            self.name, self.rate, self.wins, self.losses
+        )
+    }
+}
+
+/// Parses the game sent in reply to `%%SHOW` or `%%MONITOR2ON`, whose bodies are the lines of a CSA record.
+pub fn parse_x1_record(replies: &[X1Reply]) -> Result<GameRecord, CsaError> {
+    let csa = replies
+        .iter()
+        .filter(|reply| !reply.is_ok())
+        .map(|reply| format!("{}\n", reply.body))
+        .collect::<String>();
+
+    parse_csa(&csa)
+}
+
+////////////////////////////////////////////////////////////////////////////////
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn command() {
+        let commands = [
+            ("%%WHO", X1Command::Who),
+            ("%%LIST", X1Command::List),
+            ("%%SHOW g1", X1Command::Show("g1".to_string())),
+            ("%%MONITOR2ON g1", X1Command::Monitor2On("g1".to_string())),
+            ("%%MONITOR2OFF g1", X1Command::Monitor2Off("g1".to_string())),
+            (
+                "%%CHAT hello, world",
+                X1Command::Chat("hello, world".to_string()),
+            ),
+            (
+                "%%GAME floodgate-300-10F +",
+                X1Command::Game {
+                    game_name: "floodgate-300-10F".to_string(),
+                    color: Some(Color::Black),
+                },
+            ),
+            (
+                "%%GAME test *",
+                X1Command::Game {
+                    game_name: "test".to_string(),
+                    color: None,
+                },
+            ),
+            ("%%RATING", X1Command::Rating),
+        ];
+
+        for (line, command) in commands {
+            assert_eq!(X1Command::parse(line), Some(command.clone()));
+            assert_eq!(command.to_string(), line);
+        }
+        assert_eq!(X1Command::parse("%%SHOW"), None);
+        assert_eq!(X1Command::parse("%%GAME test"), None);
+        assert_eq!(X1Command::parse("%%UNKNOWN"), None);
+    }
+
+    #[test]
+    fn reply() {
+        let replies = [
+            (
+                "##[WHO] alice x1 connected",
+                X1Reply::new("WHO", None, "alice x1 connected"),
+            ),
+            ("##[WHO] +OK", X1Reply::new("WHO", None, "+OK")),
+            (
+                "##[MONITOR2][g1] +7776FU,T3",
+                X1Reply::new("MONITOR2", Some("g1"), "+7776FU,T3"),
+            ),
+            (
+                "##[CHAT][bob] hi there",
+                X1Reply::new("CHAT", Some("bob"), "hi there"),
+            ),
+            ("##[LOGIN] +OK x1", X1Reply::new("LOGIN", None, "+OK x1")),
+            ("##[MONITOR2OFF]", X1Reply::new("MONITOR2OFF", None, "")),
+        ];
+
+        for (line, reply) in replies {
+            assert_eq!(X1Reply::parse(line), Some(reply.clone()));
+            assert_eq!(reply.to_string(), line);
+        }
+        assert!(X1Reply::parse("##[LOGIN] +OK x1").unwrap().is_ok());
+        assert!(!X1Reply::parse("##[WHO] +OKAY").unwrap().is_ok());
+        assert_eq!(X1Reply::parse("##WHO"), None);
+        assert_eq!(X1Reply::parse("##[WHO]x"), None);
+    }
+
+    #[test]
+    fn entries() {
+        assert_eq!(
+            WhoEntry::parse("alice x1 game_waiting"),
+            Some(WhoEntry {
+                name: "alice".to_string(),
+                protocol: "x1".to_string(),
+                status: "game_waiting".to_string(),
+            })
+        );
+        assert_eq!(WhoEntry::parse("alice x1"), None);
+        assert_eq!(
+            RatingEntry::parse("bob 1532 3 1"),
+            Some(RatingEntry {
+                name: "bob".to_string(),
+                rate: 1532,
+                wins: 3,
+                losses: 1,
+            })
+        );
+        assert_eq!(RatingEntry::parse("bob high 3 1"), None);
+    }
+
+    #[test]
+    fn record() {
+        let replies = ["N+alice", "N-bob", "PI", "+", "+7776FU,T3", "+OK"]
+            .iter()
+            .map(|body| X1Reply::new("MONITOR2", Some("g1"), body))
+            .collect::<Vec<_>>();
+        let record = parse_x1_record(&replies).unwrap();
+
+        assert_eq!(record.black_player.as_deref(), Some("alice"));
+        assert_eq!(record.moves.len(), 1);
+    }
+}
@@ -0,0 +1,416 @@
+use std::time::Duration;
+
+use crate::board::Board;
+use crate::clock::{Clock, TimeControl};
+use crate::parser::CsaError;
+use crate::protocol::{GameEndReason, GameSummary};
+use crate::value::{Action, Color, Evaluation, GameRecord, MoveRecord, Time};
+
+/// How a game ended.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub(crate) struct Ending {
+    /// The line announcing the reason, if the protocol has one.
+    pub(crate) reason: Option<GameEndReason>,
+    /// `None` for a draw.
+    pub(crate) winner: Option<Color>,
+}
+
+/// The state of a game in progress, which judges the actions sent by the players.
+pub(crate) struct Game {
+    board: Board,
+    clock: Clock,
+    max_moves: Option<u32>,
+    /// Positions after each move with whether the side to move is in check, starting from the initial position.
+    history: Vec<(Board, bool)>,
+    record: GameRecord,
+}
+
+impl Game {
+    /// Starts a game after the moves of the summary.
+    ///
+    /// Returns an error if one of the moves is not legal.
+    pub(crate) fn new(summary: &GameSummary, control: TimeControl) -> Result<Game, CsaError> {
+        let mut record = summary.to_game_record();
+        record.start_time = Some(Time::now());
+        let mut game = Game {
+            board: Board::from_position(&summary.position),
+            clock: Clock::new(control),
+            max_moves: summary.max_moves,
+            history: Vec::new(),
+            record,
+        };
+        game.push_history();
+
+        for mv in &summary.moves {
+            if !game.board.is_legal(&mv.action) {
+                return Err(CsaError::InvalidMove(mv.action));
+            }
+            game.board.apply(&mv.action)?;
+            game.push_history();
+        }
+
+        Ok(game)
+    }
+
+    pub(crate) fn side_to_move(&self) -> Color {
+        self.board.side_to_move()
+    }
+
+    /// Returns how long the side to move can think, or `None` if the clock is unlimited.
+    pub(crate) fn time_available(&self) -> Option<Duration> {
+        self.clock.time_available(self.side_to_move())
+    }
+
+    pub(crate) fn board(&self) -> &Board {
+        &self.board
+    }
+
+    pub(crate) fn clock(&self) -> &Clock {
+        &self.clock
+    }
+
+    pub(crate) fn record(&self) -> &GameRecord {
+        &self.record
+    }
+
+    /// Plays an action sent by the side to move after thinking for `elapsed`.
+    ///
+    /// The evaluation sent with the action is recorded as a `'**` comment.
+    /// Returns the line to relay to both players, if any, and the ending if the game is over.
+    pub(crate) fn play(
+        &mut self,
+        action: Action,
+        evaluation: Option<&Evaluation>,
+        elapsed: Duration,
+    ) -> (Option<String>, Option<Ending>) {
+        let color = self.side_to_move();
+        if !self.clock.consume(color, elapsed) {
+            return (None, Some(self.time_up()));
+        }
+        let time = self.clock.control().charge(elapsed);
+        let line = format!("{action},T{}", self.clock.control().to_units(time));
+        let comments = evaluation.map(|e| format!("*{e}")).into_iter().collect();
+
+        match action {
+            Action::Toryo => {
+                self.push(action, Some(time), comments);
+                (
+                    Some(line),
+                    Some(self.end(Some(GameEndReason::Resign), Some(color.flip()))),
+                )
+            }
+            Action::Kachi if self.board.can_declare_win() => {
+                self.push(action, Some(time), comments);
+                (
+                    Some(line),
+                    Some(self.end(Some(GameEndReason::Jishogi), Some(color))),
+                )
+            }
+            Action::Move(..) if self.board.is_legal(&action) => {
+                self.board.apply(&action).unwrap();
+                self.push(action, Some(time), comments);
+                self.push_history();
+
+                (Some(line), self.judge())
+            }
+            _ => (None, Some(self.illegal())),
+        }
+    }
+
+    /// Ends the game as the side to move ran out of time.
+    pub(crate) fn time_up(&mut self) -> Ending {
+        self.push(Action::TimeUp, None, vec![]);
+        self.end(
+            Some(GameEndReason::TimeUp),
+            Some(self.side_to_move().flip()),
+        )
+    }
+
+    /// Ends the game as the given side left.
+    pub(crate) fn abandon(&mut self, color: Color) -> Ending {
+        self.push(Action::Chudan, None, vec![]);
+        self.end(Some(GameEndReason::Abnormal), Some(color.flip()))
+    }
+
+    /// Ends the game as the side to move sent an illegal move or an unknown command.
+    pub(crate) fn illegal(&mut self) -> Ending {
+        let color = self.side_to_move();
+        self.push(Action::IllegalAction(color), None, vec![]);
+        self.end(Some(GameEndReason::IllegalMove), Some(color.flip()))
+    }
+
+    /// Checks whether the last move ended the game.
+    fn judge(&mut self) -> Option<Ending> {
+        let (current, _) = self.history.last().unwrap();
+        let occurrences = self
+            .history
+            .iter()
+            .enumerate()
+            .filter(|(_, (board, _))| board == current)
+            .map(|(i, _)| i)
+            .collect::<Vec<_>>();
+
+        if occurrences.len() >= 4 {
+            let cycle = &self.history[occurrences[0] + 1..];
+            for color in [Color::Black, Color::White] {
+                // Positions reached by `color` have the opponent to move.
+                let all_checks = cycle
+                    .iter()
+                    .filter(|(board, _)| board.side_to_move() == color.flip())
+                    .all(|&(_, in_check)| in_check);
+                if all_checks {
+                    self.push(Action::IllegalAction(color), None, vec![]);
+                    return Some(self.end(Some(GameEndReason::OuteSennichite), Some(color.flip())));
+                }
+            }
+
+            self.push(Action::Sennichite, None, vec![]);
+            return Some(self.end(Some(GameEndReason::Sennichite), None));
+        }
+
+        if self.board.is_checkmate() {
+            self.push(Action::Tsumi, None, vec![]);
+            return Some(self.end(None, Some(self.side_to_move().flip())));
+        }
+
+        match self.max_moves {
+            Some(max) if self.history.len() > max as usize => {
+                self.push(Action::Hikiwake, None, vec![]);
+                Some(self.end(Some(GameEndReason::MaxMoves), None))
+            }
+            _ => None,
+        }
+    }
+
+    fn push_history(&mut self) {
+        let in_check = self.board.is_in_check(self.side_to_move());
+        self.history.push((self.board.clone(), in_check));
+    }
+
+    fn push(&mut self, action: Action, time: Option<Duration>, comments: Vec<String>) {
+        self.record.moves.push(MoveRecord {
+            action,
+            time,
+            comments,
+        });
+    }
+
+    fn end(&mut self, reason: Option<GameEndReason>, winner: Option<Color>) -> Ending {
+        self.record.end_time = Some(Time::now());
+        Ending { reason, winner }
+    }
+}
+
+////////////////////////////////////////////////////////////////////////////////
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::value::{Handicap, MoveFrom, PieceType, Position, Square};
+
+    fn summary() -> GameSummary {
+        GameSummary {
+            max_moves: Some(256),
+            ..GameSummary::new("test", "b", "w", Position::from_handicap(Handicap::Even))
+        }
+    }
+
+    fn mv(color: Color, from: (u8, u8), to: (u8, u8), pt: PieceType) -> Action {
+        Action::Move(
+            color,
+            MoveFrom::Board(Square::new(from.0, from.1).unwrap()),
+            Square::new(to.0, to.1).unwrap(),
+            pt,
+        )
+    }
+
+    fn secs(s: u64) -> Duration {
+        Duration::from_secs(s)
+    }
+
+    #[test]
+    fn resign() {
+        let mut game = Game::new(&summary(), TimeControl::default()).unwrap();
+
+        let evaluation = Evaluation {
+            score: 30,
+            pv: vec![mv(Color::White, (3, 3), (3, 4), PieceType::Pawn)],
+        };
+        let (line, ending) = game.play(
+            mv(Color::Black, (7, 7), (7, 6), PieceType::Pawn),
+            Some(&evaluation),
+            secs(3),
+        );
+        assert_eq!(line.as_deref(), Some("+7776FU,T3"));
+        assert_eq!(game.record().moves[0].comments, ["** 30 -3334FU"]);
+        assert_eq!(ending, None);
+        assert_eq!(game.side_to_move(), Color::White);
+
+        let (line, ending) = game.play(Action::Toryo, None, secs(1));
+        assert_eq!(line.as_deref(), Some("%TORYO,T1"));
+        assert_eq!(
+            ending,
+            Some(Ending {
+                reason: Some(GameEndReason::Resign),
+                winner: Some(Color::Black),
+            })
+        );
+        assert_eq!(game.record().moves.len(), 2);
+        assert!(game.record().end_time.is_some());
+    }
+
+    #[test]
+    fn summary_moves() {
+        let summary = GameSummary {
+            moves: vec![MoveRecord {
+                action: mv(Color::Black, (7, 7), (7, 6), PieceType::Pawn),
+                time: Some(secs(3)),
+                comments: vec![],
+            }],
+            ..summary()
+        };
+        let game = Game::new(&summary, TimeControl::default()).unwrap();
+        assert_eq!(game.side_to_move(), Color::White);
+
+        let action = mv(Color::Black, (7, 7), (7, 5), PieceType::Pawn);
+        let summary = GameSummary {
+            moves: vec![MoveRecord {
+                action,
+                time: None,
+                comments: vec![],
+            }],
+            ..summary
+        };
+        assert!(matches!(
+            Game::new(&summary, TimeControl::default()),
+            Err(CsaError::InvalidMove(a)) if a == action
+        ));
+    }
+
+    #[test]
+    fn time_unit() {
+        let control = TimeControl {
+            time_unit: secs(10),
+            ..TimeControl::sudden_death(secs(600))
+        };
+        let mut game = Game::new(&summary(), control).unwrap();
+
+        let (line, _) = game.play(
+            mv(Color::Black, (7, 7), (7, 6), PieceType::Pawn),
+            None,
+            secs(25),
+        );
+        assert_eq!(line.as_deref(), Some("+7776FU,T2"));
+        assert_eq!(game.record().moves[0].time, Some(secs(20)));
+    }
+
+    #[test]
+    fn illegal_move() {
+        let mut game = Game::new(&summary(), TimeControl::default()).unwrap();
+
+        let (line, ending) = game.play(
+            mv(Color::Black, (7, 7), (7, 5), PieceType::Pawn),
+            None,
+            secs(0),
+        );
+        assert_eq!(line, None);
+        assert_eq!(
+            ending,
+            Some(Ending {
+                reason: Some(GameEndReason::IllegalMove),
+                winner: Some(Color::White),
+            })
+        );
+
+        let mut game = Game::new(&summary(), TimeControl::default()).unwrap();
+        let (_, ending) = game.play(Action::Kachi, None, secs(0));
+        assert_eq!(ending.unwrap().winner, Some(Color::White));
+    }
+
+    #[test]
+    fn time_up() {
+        let control = TimeControl {
+            byoyomi: secs(10),
+            ..TimeControl::sudden_death(secs(5))
+        };
+        let mut game = Game::new(&summary(), control).unwrap();
+        assert_eq!(game.time_available(), Some(secs(15)));
+
+        let (_, ending) = game.play(
+            mv(Color::Black, (7, 7), (7, 6), PieceType::Pawn),
+            None,
+            secs(15),
+        );
+        assert_eq!(
+            ending,
+            Some(Ending {
+                reason: Some(GameEndReason::TimeUp),
+                winner: Some(Color::White),
+            })
+        );
+        assert_eq!(game.record().moves.last().unwrap().action, Action::TimeUp);
+    }
+
+    #[test]
+    fn sennichite() {
+        let mut game = Game::new(&summary(), TimeControl::default()).unwrap();
+        let cycle = [
+            mv(Color::Black, (2, 8), (1, 8), PieceType::Rook),
+            mv(Color::White, (8, 2), (9, 2), PieceType::Rook),
+            mv(Color::Black, (1, 8), (2, 8), PieceType::Rook),
+            mv(Color::White, (9, 2), (8, 2), PieceType::Rook),
+        ];
+
+        for (i, action) in cycle.iter().cycle().take(12).enumerate() {
+            let (_, ending) = game.play(*action, None, secs(0));
+            if i < 11 {
+                assert_eq!(ending, None);
+            } else {
+                assert_eq!(
+                    ending,
+                    Some(Ending {
+                        reason: Some(GameEndReason::Sennichite),
+                        winner: None,
+                    })
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn checkmate() {
+        let mut grid = [[None; 9]; 9];
+        grid[0][8] = Some((Color::White, PieceType::King));
+        grid[1][6] = Some((Color::Black, PieceType::Gold));
+        grid[3][7] = Some((Color::Black, PieceType::Knight));
+        grid[8][4] = Some((Color::Black, PieceType::King));
+        let summary = GameSummary {
+            position: Position {
+                bulk: Some(grid),
+                add_pieces: vec![(Color::Black, None, PieceType::Gold)],
+                ..Position::default()
+            },
+            ..summary()
+        };
+        let mut game = Game::new(&summary, TimeControl::default()).unwrap();
+
+        let (_, ending) = game.play(
+            Action::Move(
+                Color::Black,
+                MoveFrom::Hand,
+                Square::new(1, 2).unwrap(),
+                PieceType::Gold,
+            ),
+            None,
+            secs(0),
+        );
+        assert_eq!(
+            ending,
+            Some(Ending {
+                reason: None,
+                winner: Some(Color::Black),
+            })
+        );
+        assert_eq!(game.record().moves.last().unwrap().action, Action::Tsumi);
+    }
+}
@@ -0,0 +1,763 @@
+pub(crate) mod game;
+mod x1;
+
+use std::collections::HashMap;
+use std::fmt;
+use std::fs;
+use std::io::{self, BufRead, BufReader, ErrorKind, Write};
+use std::net::{SocketAddr, TcpListener, TcpStream, ToSocketAddrs};
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use time::OffsetDateTime;
+
+use self::game::{Ending, Game};
+use self::x1::{handle_command, update_ratings, LiveGame, Rating, Session, Status};
+use crate::clock::TimeControl;
+use crate::parser::parse_move_line;
+use crate::protocol::{GameEndReason, GameOutcome, GameSummary, X1Command};
+use crate::value::{Color, Position};
+
+/// Settings shared by all games hosted by a [`Server`].
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct ServerConfig {
+    pub time_control: TimeControl,
+    /// Number of moves after which a game ends in a draw.
+    pub max_moves: Option<u32>,
+    pub position: Position,
+    /// Directory to write finished games to, as `<Game_ID>.csa`.
+    pub record_dir: Option<PathBuf>,
+}
+
+impl Default for ServerConfig {
+    fn default() -> Self {
+        ServerConfig {
+            time_control: TimeControl {
+                byoyomi: Duration::from_secs(10),
+                ..TimeControl::sudden_death(Duration::from_secs(600))
+            },
+            max_moves: Some(256),
+            position: Position::default(),
+            record_dir: None,
+        }
+    }
+}
+
+////////////////////////////////////////////////////////////////////////////////
+
+struct Player {
+    name: String,
+    /// Whether the player logged in with the x1 extended mode.
+    x1: bool,
+    reader: BufReader<TcpStream>,
+    writer: TcpStream,
+}
+
+impl Player {
+    fn send(&mut self, line: &str) -> io::Result<()> {
+        write_lines(&mut self.writer, &[line])
+    }
+
+    /// Reads a line without the terminator, skipping empty keep-alive lines.
+    ///
+    /// Returns `Ok(None)` if the connection is closed.
+    fn read_line(&mut self, timeout: Option<Duration>) -> io::Result<Option<String>> {
+        let deadline = timeout.map(|t| Instant::now() + t);
+        loop {
+            let timeout = match deadline {
+                Some(deadline) => {
+                    let left = deadline.saturating_duration_since(Instant::now());
+                    if left.is_zero() {
+                        return Err(io::Error::from(ErrorKind::TimedOut));
+                    }
+                    Some(left)
+                }
+                None => None,
+            };
+            self.reader.get_ref().set_read_timeout(timeout)?;
+
+            let mut line = String::new();
+            if self.reader.read_line(&mut line)? == 0 {
+                return Ok(None);
+            }
+            let line = line.trim_end_matches(['\r', '\n']);
+            if !line.is_empty() {
+                return Ok(Some(line.to_string()));
+            }
+        }
+    }
+
+    /// Returns `false` if the connection of a player waiting in the lobby was closed,
+    /// skipping keep-alive lines without blocking.
+    fn is_connected(&mut self) -> bool {
+        if self.reader.get_ref().set_nonblocking(true).is_err() {
+            return false;
+        }
+        let connected = loop {
+            match self.reader.fill_buf() {
+                Ok([]) => break false,
+                Ok(buf) => {
+                    let blank = buf
+                        .iter()
+                        .take_while(|&&c| c == b'\r' || c == b'\n')
+                        .count();
+                    // Anything else is left for the game to read.
+                    if blank == 0 {
+                        break true;
+                    }
+                    self.reader.consume(blank);
+                }
+                Err(e) if e.kind() == ErrorKind::WouldBlock => break true,
+                Err(_) => break false,
+            }
+        };
+        let restored = self.reader.get_ref().set_nonblocking(false).is_ok();
+        connected && restored
+    }
+}
+
+/// Writes lines with a single call, so that lines pushed from other threads are not interleaved.
+fn write_lines<T: fmt::Display>(writer: &mut TcpStream, lines: &[T]) -> io::Result<()> {
+    let buf = lines
+        .iter()
+        .map(|line| format!("{line}\n"))
+        .collect::<String>();
+    writer.write_all(buf.as_bytes())?;
+    writer.flush()
+}
+
+/// Interval at which players waiting in the lobby are checked for disconnection.
+const LOBBY_CHECK_INTERVAL: Duration = Duration::from_secs(1);
+
+/// A player waiting in the lobby with its preferred color.
+type Waiting = (Option<Color>, Player);
+
+/// State shared by the threads of a server.
+struct Shared {
+    config: ServerConfig,
+    /// Players waiting for an opponent with their preferred color, by game name.
+    lobby: Mutex<HashMap<String, Vec<Waiting>>>,
+    sessions: Mutex<HashMap<String, Session>>,
+    /// Games in progress by Game_ID.
+    games: Mutex<HashMap<String, LiveGame>>,
+    ratings: Mutex<HashMap<String, Rating>>,
+}
+
+impl Shared {
+    fn set_status(&self, name: &str, status: Status) {
+        if let Some(session) = self.sessions.lock().unwrap().get_mut(name) {
+            session.status = status;
+        }
+    }
+
+    fn logout(&self, name: &str) {
+        self.sessions.lock().unwrap().remove(name);
+    }
+
+    /// Logs out the players who disconnected while waiting for an opponent.
+    fn prune_lobby(&self) {
+        let mut lobby = self.lobby.lock().unwrap();
+        for waiting in lobby.values_mut() {
+            waiting.retain_mut(|(_, player)| {
+                let connected = player.is_connected();
+                if !connected {
+                    self.logout(&player.name);
+                }
+                connected
+            });
+        }
+        lobby.retain(|_, waiting| !waiting.is_empty());
+    }
+}
+
+/// A minimal shogi-server which pairs players logging in with the same game name.
+///
+/// Players log in with `LOGIN <name> <game_name>[,<password>]`. The first player waiting for
+/// a game name plays Black against the next one. Moves are checked for legality and the clock
+/// is enforced, and finished games are written to [`ServerConfig::record_dir`].
+///
+/// Players logging in with `LOGIN <name> <password> x1` can send the extended commands of
+/// [`X1Command`] instead, e.g. to monitor games with `%%MONITOR2ON`, and join a game with `%%GAME`.
+/// Commands are not answered while waiting for an opponent. Players whose connection closes
+/// while waiting are logged out.
+pub struct Server {
+    listener: TcpListener,
+    shared: Arc<Shared>,
+}
+
+impl Server {
+    pub fn bind<A: ToSocketAddrs>(addr: A, config: ServerConfig) -> io::Result<Server> {
+        Ok(Server {
+            listener: TcpListener::bind(addr)?,
+            shared: Arc::new(Shared {
+                config,
+                lobby: Mutex::new(HashMap::new()),
+                sessions: Mutex::new(HashMap::new()),
+                games: Mutex::new(HashMap::new()),
+                ratings: Mutex::new(HashMap::new()),
+            }),
+        })
+    }
+
+    pub fn local_addr(&self) -> io::Result<SocketAddr> {
+        self.listener.local_addr()
+    }
+
+    /// Accepts connections forever, handling each of them and each game on its own thread.
+    pub fn run(&self) -> io::Result<()> {
+        let shared = Arc::downgrade(&self.shared);
+        thread::spawn(move || {
+            while let Some(shared) = shared.upgrade() {
+                shared.prune_lobby();
+                drop(shared);
+                thread::sleep(LOBBY_CHECK_INTERVAL);
+            }
+        });
+
+        for stream in self.listener.incoming() {
+            let stream = stream?;
+            let shared = Arc::clone(&self.shared);
+            thread::spawn(move || match login(&shared, stream) {
+                Ok(Some((player, Some(game_name)))) => {
+                    enter_lobby(&shared, game_name, None, player)
+                }
+                Ok(Some((player, None))) => serve_x1(&shared, player),
+                _ => {}
+            });
+        }
+
+        Ok(())
+    }
+}
+
+/// Reads the login line and returns the player with its game name, which is `None` in x1 mode.
+fn login(shared: &Shared, stream: TcpStream) -> io::Result<Option<(Player, Option<String>)>> {
+    let mut player = Player {
+        name: String::new(),
+        x1: false,
+        reader: BufReader::new(stream.try_clone()?),
+        writer: stream,
+    };
+    let Some(line) = player.read_line(None)? else {
+        return Ok(None);
+    };
+
+    let args = line.split(' ').collect::<Vec<_>>();
+    let (name, game_name) = match args[..] {
+        ["LOGIN", name, _, "x1"] if !name.is_empty() => (name, None),
+        ["LOGIN", name, password] if !name.is_empty() && !password.is_empty() => {
+            (name, Some(password.split(',').next().unwrap().to_string()))
+        }
+        _ => {
+            player.send("LOGIN:incorrect")?;
+            return Ok(None);
+        }
+    };
+
+    player.name = name.to_string();
+    player.x1 = game_name.is_none();
+    // A player who disconnected while waiting may be logging in again.
+    shared.prune_lobby();
+    {
+        let mut sessions = shared.sessions.lock().unwrap();
+        if sessions.contains_key(name) {
+            drop(sessions);
+            player.send("LOGIN:incorrect")?;
+            return Ok(None);
+        }
+        sessions.insert(
+            player.name.clone(),
+            Session {
+                x1: player.x1,
+                status: Status::Connected,
+                writer: player.writer.try_clone()?,
+            },
+        );
+    }
+
+    let mut lines = vec![format!("LOGIN:{name} OK")];
+    if player.x1 {
+        lines.push("##[LOGIN] +OK x1".to_string());
+    }
+    if let Err(e) = write_lines(&mut player.writer, &lines) {
+        shared.logout(name);
+        return Err(e);
+    }
+    Ok(Some((player, game_name)))
+}
+
+/// Answers the extended commands of an x1 player until it logs out or asks for a game.
+fn serve_x1(shared: &Arc<Shared>, mut player: Player) {
+    while let Ok(Some(line)) = player.read_line(None) {
+        let result = match X1Command::parse(&line) {
+            Some(X1Command::Game { game_name, color }) => {
+                return enter_lobby(shared, game_name, color, player);
+            }
+            Some(command) => handle_command(shared, &mut player, command),
+            None if line == "LOGOUT" => {
+                let _ = player.send("LOGOUT:completed");
+                break;
+            }
+            None => player.send("##[ERROR] unknown command"),
+        };
+        if result.is_err() {
+            break;
+        }
+    }
+
+    shared.logout(&player.name);
+}
+
+/// Waits for a player in CSA mode to log out after a game.
+fn wait_logout(shared: &Shared, mut player: Player) {
+    if let Ok(Some(line)) = player.read_line(None) {
+        if line == "LOGOUT" {
+            let _ = player.send("LOGOUT:completed");
+        }
+    }
+
+    shared.logout(&player.name);
+}
+
+/// Pairs the player with a waiting one, or leaves it waiting.
+fn enter_lobby(shared: &Arc<Shared>, game_name: String, color: Option<Color>, player: Player) {
+    shared.prune_lobby();
+    let (opponent_color, opponent) = {
+        let mut lobby = shared.lobby.lock().unwrap();
+        let waiting = lobby.entry(game_name.clone()).or_default();
+        // Two players preferring the same color cannot play each other.
+        match waiting.iter().position(|&(c, _)| c.is_none() || c != color) {
+            Some(i) => {
+                let opponent = waiting.remove(i);
+                if waiting.is_empty() {
+                    lobby.remove(&game_name);
+                }
+                opponent
+            }
+            None => {
+                shared.set_status(&player.name, Status::GameWaiting);
+                waiting.push((color, player));
+                return;
+            }
+        }
+    };
+
+    let players = if opponent_color == Some(Color::White) || color == Some(Color::Black) {
+        [player, opponent]
+    } else {
+        [opponent, player]
+    };
+    let names = players.each_ref().map(|player| player.name.clone());
+    for name in &names {
+        shared.set_status(name, Status::Game);
+    }
+
+    match play(shared, &game_name, players) {
+        Ok(players) => {
+            for player in players {
+                shared.set_status(&player.name, Status::Connected);
+                let shared = Arc::clone(shared);
+                thread::spawn(move || {
+                    if player.x1 {
+                        serve_x1(&shared, player);
+                    } else {
+                        wait_logout(&shared, player);
+                    }
+                });
+            }
+        }
+        Err(_) => {
+            for name in &names {
+                shared.logout(name);
+            }
+        }
+    }
+}
+
+/// Plays a game between Black and White and returns the players afterwards.
+fn play(shared: &Shared, game_name: &str, mut players: [Player; 2]) -> io::Result<[Player; 2]> {
+    let config = &shared.config;
+    let now = OffsetDateTime::now_utc();
+    let timestamp = format!(
+        "{:04}{:02}{:02}{:02}{:02}{:02}",
+        now.year(),
+        now.month() as u8,
+        now.day(),
+        now.hour(),
+        now.minute(),
+        now.second()
+    );
+    let mut summary = GameSummary {
+        protocol_version: Some("1.2".to_string()),
+        protocol_mode: Some("Server".to_string()),
+        format: Some("Shogi 1.0".to_string()),
+        declaration: Some("Jishogi 1.1".to_string()),
+        rematch_on_draw: Some(false),
+        max_moves: config.max_moves,
+        time: Some(config.time_control.clone()),
+        ..GameSummary::new(
+            &format!(
+                "{game_name}+{}+{}+{timestamp}",
+                players[0].name, players[1].name
+            ),
+            &players[0].name,
+            &players[1].name,
+            config.position.clone(),
+        )
+    };
+    let mut game = Game::new(&summary, config.time_control.clone())
+        .map_err(|e| io::Error::new(ErrorKind::InvalidData, e))?;
+
+    for (player, color) in players.iter_mut().zip([Color::Black, Color::White]) {
+        summary.your_turn = color;
+        player.writer.write_all(summary.to_string().as_bytes())?;
+        player.writer.flush()?;
+    }
+
+    let game_id = summary.game_id.clone();
+    let mut rejected_by = None;
+    for player in players.iter_mut() {
+        match player.read_line(None)? {
+            Some(line) if line == format!("AGREE {game_id}") => {}
+            _ => {
+                rejected_by.get_or_insert(player.name.clone());
+            }
+        }
+    }
+    if let Some(name) = rejected_by {
+        for player in players.iter_mut() {
+            let _ = player.send(&format!("REJECT:{game_id} by {name}"));
+        }
+        return Ok(players);
+    }
+
+    for player in players.iter_mut() {
+        player.send(&format!("START:{game_id}"))?;
+    }
+
+    shared.games.lock().unwrap().insert(
+        game_id.clone(),
+        LiveGame {
+            record: game.record().clone(),
+            monitors: HashMap::new(),
+        },
+    );
+
+    let ending = loop {
+        let color = game.side_to_move();
+        let started = Instant::now();
+        let mut evaluation = None;
+        let (line, ending) = loop {
+            let player = &mut players[color as usize];
+            let timeout = game
+                .time_available()
+                .map(|t| t.saturating_sub(started.elapsed()));
+            match player.read_line(timeout) {
+                // Extended commands can be sent while thinking, on the player's own clock.
+                Ok(Some(line)) if player.x1 && line.starts_with("%%") => {
+                    let _ = match X1Command::parse(&line) {
+                        Some(command) => handle_command(shared, player, command),
+                        None => player.send("##[ERROR] unknown command"),
+                    };
+                }
+                Ok(Some(line)) => match parse_move_line(&line) {
+                    Ok(record) => {
+                        evaluation = record.evaluation();
+                        break game.play(record.action, evaluation.as_ref(), started.elapsed());
+                    }
+                    Err(_) => break (None, Some(game.illegal())),
+                },
+                Err(e) if e.kind() == ErrorKind::WouldBlock || e.kind() == ErrorKind::TimedOut => {
+                    break (None, Some(game.time_up()))
+                }
+                Ok(None) | Err(_) => break (None, Some(game.abandon(color))),
+            }
+        };
+
+        let mut lines = Vec::new();
+        if let Some(line) = line {
+            for player in players.iter_mut() {
+                let _ = player.send(&line);
+            }
+            lines.push(line);
+            // Monitors also receive the evaluation, which is kept from the opponent.
+            if let Some(ref evaluation) = evaluation {
+                lines.push(format!("'*{evaluation}"));
+            }
+        }
+        if let Some(reason) = ending.and_then(|ending| ending.reason) {
+            lines.push(reason.to_string());
+        }
+        if let Some(live) = shared.games.lock().unwrap().get_mut(&game_id) {
+            live.record = game.record().clone();
+            live.push(&game_id, &lines);
+        }
+
+        if let Some(ending) = ending {
+            break ending;
+        }
+    };
+
+    shared.games.lock().unwrap().remove(&game_id);
+    update_ratings(
+        &mut shared.ratings.lock().unwrap(),
+        &players[0].name,
+        &players[1].name,
+        ending.winner,
+    );
+    announce(&mut players, ending);
+
+    if let Some(ref dir) = config.record_dir {
+        fs::write(
+            dir.join(format!("{game_id}.csa")),
+            game.record().to_string(),
+        )?;
+    }
+
+    Ok(players)
+}
+
+fn announce(players: &mut [Player; 2], ending: Ending) {
+    for (player, color) in players.iter_mut().zip([Color::Black, Color::White]) {
+        if let Some(reason) = ending.reason {
+            let _ = player.send(&reason.to_string());
+        }
+        let outcome = match ending.winner {
+            Some(winner) if winner == color => GameOutcome::Win,
+            Some(_) => GameOutcome::Lose,
+            None if ending.reason == Some(GameEndReason::MaxMoves) => GameOutcome::Censored,
+            None => GameOutcome::Draw,
+        };
+        let _ = player.send(&outcome.to_string());
+    }
+}
+
+////////////////////////////////////////////////////////////////////////////////
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::parse_csa;
+    use crate::protocol::{
+        parse_x1_record, Client, ProtocolError, RatingEntry, ServerMessage, X1Reply,
+    };
+    use crate::value::{Action, Evaluation, MoveFrom, PieceType, Square};
+
+    fn recv_until_result(client: &mut Client<TcpStream>) -> Vec<ServerMessage> {
+        let mut messages = Vec::new();
+        loop {
+            let message = client.recv().unwrap();
+            let done = matches!(message, ServerMessage::Result(_));
+            messages.push(message);
+            if done {
+                return messages;
+            }
+        }
+    }
+
+    #[test]
+    fn play_game() {
+        let dir = std::env::temp_dir().join(format!("csa-server-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let server = Server::bind(
+            "127.0.0.1:0",
+            ServerConfig {
+                record_dir: Some(dir.clone()),
+                ..ServerConfig::default()
+            },
+        )
+        .unwrap();
+        let addr = server.local_addr().unwrap();
+        thread::spawn(move || server.run());
+
+        let mut black = Client::new(TcpStream::connect(addr).unwrap());
+        black.login("alice", "test-game,pass").unwrap();
+        let black = thread::spawn(move || {
+            let summary = black.wait_game_summary().unwrap();
+            assert_eq!(summary.your_turn, Color::Black);
+            black.agree(&summary.game_id).unwrap();
+            black
+                .send_move(&Action::Move(
+                    Color::Black,
+                    MoveFrom::Board(Square::new(7, 7).unwrap()),
+                    Square::new(7, 6).unwrap(),
+                    PieceType::Pawn,
+                ))
+                .unwrap();
+            let messages = recv_until_result(&mut black);
+            black.logout().unwrap();
+            messages
+        });
+
+        let mut white = Client::new(TcpStream::connect(addr).unwrap());
+        white.login("bob", "test-game").unwrap();
+        let summary = white.wait_game_summary().unwrap();
+        assert_eq!(summary.your_turn, Color::White);
+        assert_eq!(summary.black_player, "alice");
+        white.agree(&summary.game_id).unwrap();
+        assert!(matches!(white.recv().unwrap(), ServerMessage::Move(_)));
+        white.send_move(&Action::Toryo).unwrap();
+        let messages = recv_until_result(&mut white);
+        assert_eq!(
+            messages[1..],
+            [
+                ServerMessage::GameEnd(GameEndReason::Resign),
+                ServerMessage::Result(GameOutcome::Lose),
+            ]
+        );
+        white.logout().unwrap();
+
+        let messages = black.join().unwrap();
+        assert_eq!(messages.len(), 4);
+        assert_eq!(messages[3], ServerMessage::Result(GameOutcome::Win));
+
+        let path = dir.join(format!("{}.csa", summary.game_id));
+        let record = parse_csa(&fs::read_to_string(&path).unwrap()).unwrap();
+        assert_eq!(record.black_player.as_deref(), Some("alice"));
+        assert_eq!(record.moves.len(), 2);
+        assert_eq!(record.moves[1].action, Action::Toryo);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn x1_monitor() {
+        let server = Server::bind("127.0.0.1:0", ServerConfig::default()).unwrap();
+        let addr = server.local_addr().unwrap();
+        thread::spawn(move || server.run());
+
+        let mut black = Client::new(TcpStream::connect(addr).unwrap());
+        black.login_x1("alice", "pass").unwrap();
+        black
+            .command(&X1Command::Game {
+                game_name: "test-game".to_string(),
+                color: Some(Color::Black),
+            })
+            .unwrap();
+        let black = thread::spawn(move || {
+            let summary = black.wait_game_summary().unwrap();
+            black.agree(&summary.game_id).unwrap();
+            black
+        });
+
+        let mut white = Client::new(TcpStream::connect(addr).unwrap());
+        white.login("bob", "test-game").unwrap();
+        let summary = white.wait_game_summary().unwrap();
+        assert_eq!(summary.black_player, "alice");
+        white.agree(&summary.game_id).unwrap();
+        let mut black = black.join().unwrap();
+
+        let mut monitor = Client::new(TcpStream::connect(addr).unwrap());
+        monitor.login_x1("carol", "pass").unwrap();
+        let who = monitor
+            .command(&X1Command::Who)
+            .unwrap()
+            .iter()
+            .map(|reply| reply.body.clone())
+            .collect::<Vec<_>>();
+        assert_eq!(who, ["alice x1 game", "bob CSA game", "carol x1 connected"]);
+        let list = monitor.command(&X1Command::List).unwrap();
+        assert_eq!(list[0].body, summary.game_id);
+        let game = monitor
+            .command(&X1Command::Monitor2On(summary.game_id.clone()))
+            .unwrap();
+        let record = parse_x1_record(&game).unwrap();
+        assert_eq!(record.event.as_deref(), Some(summary.game_id.as_str()));
+        assert!(record.moves.is_empty());
+
+        black
+            .send_move_with_evaluation(
+                &Action::Move(
+                    Color::Black,
+                    MoveFrom::Board(Square::new(7, 7).unwrap()),
+                    Square::new(7, 6).unwrap(),
+                    PieceType::Pawn,
+                ),
+                &Evaluation {
+                    score: 30,
+                    pv: vec![],
+                },
+            )
+            .unwrap();
+        assert!(matches!(white.recv().unwrap(), ServerMessage::Move(_)));
+        white.send_move(&Action::Toryo).unwrap();
+        recv_until_result(&mut black);
+        recv_until_result(&mut white);
+
+        let pushed = (0..6)
+            .map(|_| match monitor.recv().unwrap() {
+                ServerMessage::X1(reply) => reply.body,
+                other => panic!("unexpected {other:?}"),
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(
+            pushed,
+            ["+7776FU,T0", "'** 30", "+OK", "%TORYO,T0", "#RESIGN", "+OK"]
+        );
+
+        let rating = monitor
+            .command(&X1Command::Rating)
+            .unwrap()
+            .iter()
+            .map(|reply| RatingEntry::parse(&reply.body).unwrap())
+            .collect::<Vec<_>>();
+        assert_eq!(rating[0].to_string(), "alice 1516 1 0");
+        assert_eq!(rating[1].to_string(), "bob 1484 0 1");
+
+        monitor
+            .command(&X1Command::Chat("good game".to_string()))
+            .unwrap();
+        assert_eq!(
+            monitor.recv().unwrap(),
+            ServerMessage::X1(X1Reply::new("CHAT", Some("carol"), "good game"))
+        );
+        assert!(matches!(
+            monitor.command(&X1Command::Show(summary.game_id.clone())),
+            Err(ProtocolError::CommandFailed(_))
+        ));
+        monitor.logout().unwrap();
+    }
+
+    #[test]
+    fn login_incorrect() {
+        let server = Server::bind("127.0.0.1:0", ServerConfig::default()).unwrap();
+        let addr = server.local_addr().unwrap();
+        thread::spawn(move || server.run());
+
+        let mut client = Client::new(TcpStream::connect(addr).unwrap());
+        assert!(client.login("alice", "").is_err());
+    }
+
+    #[test]
+    fn disconnect_while_waiting() {
+        let server = Server::bind("127.0.0.1:0", ServerConfig::default()).unwrap();
+        let addr = server.local_addr().unwrap();
+        thread::spawn(move || server.run());
+
+        let mut monitor = Client::new(TcpStream::connect(addr).unwrap());
+        monitor.login_x1("carol", "pass").unwrap();
+        let mut wait_for = |expected: &[&str]| loop {
+            let who = monitor.command(&X1Command::Who).unwrap();
+            if who.iter().map(|reply| &reply.body).eq(expected) {
+                break;
+            }
+            thread::sleep(Duration::from_millis(10));
+        };
+
+        let mut alice = Client::new(TcpStream::connect(addr).unwrap());
+        alice.login("alice", "test-game").unwrap();
+        wait_for(&["alice CSA game_waiting", "carol x1 connected"]);
+        drop(alice);
+        wait_for(&["carol x1 connected"]);
+
+        // The name is free again, and the next opponent meets the new connection.
+        let mut alice = Client::new(TcpStream::connect(addr).unwrap());
+        alice.login("alice", "test-game").unwrap();
+        let mut bob = Client::new(TcpStream::connect(addr).unwrap());
+        bob.login("bob", "test-game").unwrap();
+        let summary = alice.wait_game_summary().unwrap();
+        assert_eq!(summary.white_player, "bob");
+        assert_eq!(bob.wait_game_summary().unwrap().game_id, summary.game_id);
+    }
+}
@@ -0,0 +1,244 @@
+use std::collections::HashMap;
+use std::fmt;
+use std::io;
+use std::net::TcpStream;
+
+use super::{write_lines, Player, Shared};
+use crate::protocol::{RatingEntry, WhoEntry, X1Command, X1Reply};
+use crate::value::{Color, GameRecord};
+
+/// A logged in player, as listed by `%%WHO`.
+pub(super) struct Session {
+    pub(super) x1: bool,
+    pub(super) status: Status,
+    /// A handle to push chat lines to x1 players.
+    pub(super) writer: TcpStream,
+}
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub(super) enum Status {
+    Connected,
+    GameWaiting,
+    Game,
+}
+
+impl fmt::Display for Status {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Status::Connected => write!(f, "connected"),
+            Status::GameWaiting => write!(f, "game_waiting"),
+            Status::Game => write!(f, "game"),
+        }
+    }
+}
+
+/// A game in progress, as shown by `%%SHOW` and followed by `%%MONITOR2ON`.
+pub(super) struct LiveGame {
+    pub(super) record: GameRecord,
+    /// Monitoring players by name.
+    pub(super) monitors: HashMap<String, TcpStream>,
+}
+
+impl LiveGame {
+    /// Sends lines to the monitors followed by `+OK`, dropping monitors which left.
+    pub(super) fn push(&mut self, game_id: &str, lines: &[String]) {
+        let lines = monitor_lines(game_id, lines);
+        self.monitors
+            .retain(|_, writer| write_lines(writer, &lines).is_ok());
+    }
+}
+
+fn monitor_lines(game_id: &str, lines: &[String]) -> Vec<X1Reply> {
+    lines
+        .iter()
+        .map(String::as_str)
+        .chain(["+OK"])
+        .map(|line| X1Reply::new("MONITOR2", Some(game_id), line))
+        .collect()
+}
+
+/// The Elo rating of a player with its record, starting at 1500.
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub(super) struct Rating {
+    rate: f64,
+    wins: u32,
+    losses: u32,
+}
+
+impl Default for Rating {
+    fn default() -> Self {
+        Rating {
+            rate: 1500.0,
+            wins: 0,
+            losses: 0,
+        }
+    }
+}
+
+/// Updates the ratings of both players after a game, where `winner` is `None` for a draw.
+pub(super) fn update_ratings(
+    ratings: &mut HashMap<String, Rating>,
+    black: &str,
+    white: &str,
+    winner: Option<Color>,
+) {
+    const K: f64 = 32.0;
+
+    let b = ratings.get(black).copied().unwrap_or_default();
+    let w = ratings.get(white).copied().unwrap_or_default();
+    let expected = 1.0 / (1.0 + 10f64.powf((w.rate - b.rate) / 400.0));
+    let score = match winner {
+        Some(Color::Black) => 1.0,
+        Some(Color::White) => 0.0,
+        None => 0.5,
+    };
+
+    let black_rating = ratings.entry(black.to_string()).or_default();
+    black_rating.rate += K * (score - expected);
+    match winner {
+        Some(Color::Black) => black_rating.wins += 1,
+        Some(Color::White) => black_rating.losses += 1,
+        None => {}
+    }
+
+    let white_rating = ratings.entry(white.to_string()).or_default();
+    white_rating.rate -= K * (score - expected);
+    match winner {
+        Some(Color::White) => white_rating.wins += 1,
+        Some(Color::Black) => white_rating.losses += 1,
+        None => {}
+    }
+}
+
+/// Answers an extended command other than `%%GAME`, which is handled by the caller.
+pub(super) fn handle_command(
+    shared: &Shared,
+    player: &mut Player,
+    command: X1Command,
+) -> io::Result<()> {
+    let reply = |tag: &str, body: &str| X1Reply::new(tag, None, body);
+    let with_ok = |tag: &str, mut lines: Vec<X1Reply>| {
+        lines.push(reply(tag, "+OK"));
+        lines
+    };
+
+    let lines = match command {
+        X1Command::Who => {
+            let sessions = shared.sessions.lock().unwrap();
+            let mut entries = sessions
+                .iter()
+                .map(|(name, session)| WhoEntry {
+                    name: name.clone(),
+                    protocol: if session.x1 { "x1" } else { "CSA" }.to_string(),
+                    status: session.status.to_string(),
+                })
+                .collect::<Vec<_>>();
+            entries.sort_by(|a, b| a.name.cmp(&b.name));
+
+            let lines = entries
+                .iter()
+                .map(|entry| reply("WHO", &entry.to_string()))
+                .collect();
+            with_ok("WHO", lines)
+        }
+        X1Command::List => {
+            let mut game_ids = shared
+                .games
+                .lock()
+                .unwrap()
+                .keys()
+                .cloned()
+                .collect::<Vec<_>>();
+            game_ids.sort();
+
+            let lines = game_ids.iter().map(|id| reply("LIST", id)).collect();
+            with_ok("LIST", lines)
+        }
+        X1Command::Show(ref game_id) => match shared.games.lock().unwrap().get(game_id) {
+            Some(game) => {
+                let lines = game
+                    .record
+                    .to_string()
+                    .lines()
+                    .map(|line| reply("SHOW", line))
+                    .collect();
+                with_ok("SHOW", lines)
+            }
+            None => vec![reply("ERROR", "cannot find the game")],
+        },
+        X1Command::Monitor2On(ref game_id) => {
+            // The game stays locked until the monitor is registered, so that no move is missed.
+            let mut games = shared.games.lock().unwrap();
+            let Some(game) = games.get_mut(game_id) else {
+                return write_lines(
+                    &mut player.writer,
+                    &[reply("ERROR", "cannot find the game")],
+                );
+            };
+            let record = game.record.to_string();
+            let lines = record.lines().map(str::to_string).collect::<Vec<_>>();
+            write_lines(&mut player.writer, &monitor_lines(game_id, &lines))?;
+            game.monitors
+                .insert(player.name.clone(), player.writer.try_clone()?);
+            return Ok(());
+        }
+        X1Command::Monitor2Off(ref game_id) => {
+            if let Some(game) = shared.games.lock().unwrap().get_mut(game_id) {
+                game.monitors.remove(&player.name);
+            }
+            vec![reply("MONITOR2OFF", game_id)]
+        }
+        X1Command::Chat(ref message) => {
+            let line = X1Reply::new("CHAT", Some(&player.name), message);
+            let mut sessions = shared.sessions.lock().unwrap();
+            for session in sessions.values_mut().filter(|session| session.x1) {
+                let _ = write_lines(&mut session.writer, &[&line]);
+            }
+            return Ok(());
+        }
+        X1Command::Game { .. } => vec![reply("ERROR", "cannot start a game now")],
+        X1Command::Rating => {
+            let ratings = shared.ratings.lock().unwrap();
+            let mut entries = ratings
+                .iter()
+                .map(|(name, rating)| RatingEntry {
+                    name: name.clone(),
+                    rate: rating.rate.round() as i32,
+                    wins: rating.wins,
+                    losses: rating.losses,
+                })
+                .collect::<Vec<_>>();
+            entries.sort_by(|a, b| b.rate.cmp(&a.rate).then_with(|| a.name.cmp(&b.name)));
+
+            let lines = entries
+                .iter()
+                .map(|entry| reply("RATING", &entry.to_string()))
+                .collect();
+            with_ok("RATING", lines)
+        }
+    };
+
+    write_lines(&mut player.writer, &lines)
+}
+
+////////////////////////////////////////////////////////////////////////////////
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn ratings() {
+        let mut ratings = HashMap::new();
+
+        update_ratings(&mut ratings, "alice", "bob", Some(Color::Black));
+        assert_eq!(ratings["alice"].rate, 1516.0);
+        assert_eq!(ratings["bob"].rate, 1484.0);
+        assert_eq!((ratings["alice"].wins, ratings["bob"].losses), (1, 1));
+
+        update_ratings(&mut ratings, "bob", "alice", None);
+        assert!(ratings["bob"].rate > 1484.0);
+        assert!(ratings["alice"].rate < 1516.0);
+        assert_eq!((ratings["bob"].wins, ratings["bob"].losses), (0, 1));
+    }
+}
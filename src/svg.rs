@@ -0,0 +1,502 @@
+use std::fmt::Write;
+
+use crate::board::Board;
+use crate::notation::RANKS;
+use crate::parser::CsaError;
+use crate::value::{Action, Color, GameRecord, MoveFrom, PieceType, Square};
+
+/// Appearance of SVG board images.
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct SvgOptions {
+    /// Draws the board from White's side.
+    pub flipped: bool,
+    /// Size of a square in pixels.
+    pub square_size: u32,
+    pub show_coordinates: bool,
+    pub background_color: String,
+    pub board_color: String,
+    pub line_color: String,
+    pub piece_color: String,
+    pub text_color: String,
+    pub promoted_color: String,
+    pub highlight_color: String,
+    pub arrow_color: String,
+    pub font_family: String,
+}
+
+impl Default for SvgOptions {
+    fn default() -> Self {
+        SvgOptions {
+            flipped: false,
+            square_size: 40,
+            show_coordinates: true,
+            background_color: "#ffffff".to_string(),
+            board_color: "#f3d38b".to_string(),
+            line_color: "#333333".to_string(),
+            piece_color: "#fbeed3".to_string(),
+            text_color: "#000000".to_string(),
+            promoted_color: "#c00000".to_string(),
+            highlight_color: "#f9a55c".to_string(),
+            arrow_color: "#1f5fbf".to_string(),
+            font_family: "serif".to_string(),
+        }
+    }
+}
+
+/// A position with annotations to be rendered as an SVG image.
+#[derive(Debug, Clone)]
+pub struct SvgDiagram<'a> {
+    pub board: &'a Board,
+    /// Move whose squares are highlighted.
+    pub last_move: Option<Action>,
+    /// Moves drawn as arrows, e.g. a principal variation.
+    pub arrows: Vec<Action>,
+    pub black_player: Option<&'a str>,
+    pub white_player: Option<&'a str>,
+}
+
+impl<'a> SvgDiagram<'a> {
+    pub fn new(board: &'a Board) -> SvgDiagram<'a> {
+        SvgDiagram {
+            board,
+            last_move: None,
+            arrows: Vec::new(),
+            black_player: None,
+            white_player: None,
+        }
+    }
+
+    /// Renders the diagram to a self-contained SVG document.
+    pub fn render(&self, options: &SvgOptions) -> String {
+        let layout = Layout::new(options);
+        let mut svg = String::new();
+
+        writeln!(
+            svg,
+            r#"<svg xmlns="http://www.w3.org/2000/svg" width="{w}" height="{h}" viewBox="0 0 {w} {h}" font-family="{}">"#,
+            escape(&options.font_family),
+            w = layout.width,
+            h = layout.height,
+        )
+        .unwrap();
+        writeln!(
+            svg,
+            r#"<defs><marker id="arrowhead" markerWidth="4" markerHeight="4" refX="2" refY="2" orient="auto"><path d="M0,0 L4,2 L0,4 z" fill="{}"/></marker></defs>"#,
+            escape(&options.arrow_color)
+        )
+        .unwrap();
+        writeln!(
+            svg,
+            r#"<rect width="{}" height="{}" fill="{}"/>"#,
+            layout.width,
+            layout.height,
+            escape(&options.background_color)
+        )
+        .unwrap();
+
+        self.render_board(&mut svg, &layout, options);
+        self.render_hands(&mut svg, &layout, options);
+        self.render_arrows(&mut svg, &layout, options);
+
+        svg.push_str("</svg>\n");
+        svg
+    }
+
+    fn render_board(&self, svg: &mut String, layout: &Layout, options: &SvgOptions) {
+        let s = layout.square;
+        writeln!(
+            svg,
+            r#"<rect x="{}" y="{}" width="{}" height="{}" fill="{}" stroke="{}" stroke-width="2"/>"#,
+            layout.board_x,
+            layout.board_y,
+            s * 9.0,
+            s * 9.0,
+            escape(&options.board_color),
+            escape(&options.line_color)
+        )
+        .unwrap();
+
+        if let Some(Action::Move(_, from, to, _)) = self.last_move {
+            let squares = match from {
+                MoveFrom::Board(from) => vec![from, to],
+                MoveFrom::Hand => vec![to],
+            };
+            for sq in squares {
+                let (x, y) = layout.square_origin(sq);
+                writeln!(
+                    svg,
+                    r#"<rect x="{x}" y="{y}" width="{s}" height="{s}" fill="{}"/>"#,
+                    escape(&options.highlight_color)
+                )
+                .unwrap();
+            }
+        }
+
+        for i in 1..9 {
+            let offset = s * i as f64;
+            writeln!(
+                svg,
+                r#"<line x1="{x}" y1="{}" x2="{x}" y2="{}" stroke="{c}"/><line x1="{}" y1="{y}" x2="{}" y2="{y}" stroke="{c}"/>"#,
+                layout.board_y,
+                layout.board_y + s * 9.0,
+                layout.board_x,
+                layout.board_x + s * 9.0,
+                x = layout.board_x + offset,
+                y = layout.board_y + offset,
+                c = escape(&options.line_color)
+            )
+            .unwrap();
+        }
+
+        if options.show_coordinates {
+            for i in 0..9u8 {
+                let (file, rank) = if options.flipped {
+                    (i + 1, 9 - i)
+                } else {
+                    (9 - i, i + 1)
+                };
+                let offset = s * (i as f64 + 0.5);
+                writeln!(
+                    svg,
+                    r#"<text x="{}" y="{}" font-size="{}" text-anchor="middle" dominant-baseline="central" fill="{c}">{file}</text>"#,
+                    layout.board_x + offset,
+                    layout.board_y - layout.label / 2.0,
+                    layout.label * 0.7,
+                    c = escape(&options.text_color)
+                )
+                .unwrap();
+                writeln!(
+                    svg,
+                    r#"<text x="{}" y="{}" font-size="{}" text-anchor="middle" dominant-baseline="central" fill="{c}">{}</text>"#,
+                    layout.board_x + s * 9.0 + layout.label / 2.0,
+                    layout.board_y + offset,
+                    layout.label * 0.7,
+                    RANKS[rank as usize - 1],
+                    c = escape(&options.text_color)
+                )
+                .unwrap();
+            }
+        }
+
+        for (sq, color, pt) in self.board.pieces() {
+            let (x, y) = layout.square_origin(sq);
+            render_piece(
+                svg,
+                (x + s / 2.0, y + s / 2.0),
+                s,
+                layout.is_upside_down(color),
+                pt,
+                options,
+            );
+        }
+    }
+
+    fn render_hands(&self, svg: &mut String, layout: &Layout, options: &SvgOptions) {
+        let s = layout.square;
+
+        for color in [Color::Black, Color::White] {
+            let name = match color {
+                Color::Black => self.black_player,
+                Color::White => self.white_player,
+            };
+            let mark = match color {
+                Color::Black => '☗',
+                Color::White => '☖',
+            };
+            let (x, y, anchor) = if layout.is_upside_down(color) {
+                (layout.pad, layout.pad + layout.label / 2.0, "start")
+            } else {
+                (
+                    layout.width - layout.pad,
+                    layout.height - layout.pad - layout.label / 2.0,
+                    "end",
+                )
+            };
+            writeln!(
+                svg,
+                r#"<text x="{x}" y="{y}" font-size="{}" text-anchor="{anchor}" dominant-baseline="central" fill="{}">{mark}{}</text>"#,
+                layout.label * 0.7,
+                escape(&options.text_color),
+                escape(name.unwrap_or_default())
+            )
+            .unwrap();
+
+            for (i, (pt, n)) in self.board.hand_pieces(color).enumerate() {
+                let (cx, cy) = layout.hand_center(color, i);
+                render_piece(svg, (cx, cy), s, layout.is_upside_down(color), pt, options);
+                if n > 1 {
+                    writeln!(
+                        svg,
+                        r#"<text x="{}" y="{}" font-size="{}" text-anchor="middle" dominant-baseline="central" fill="{}">{n}</text>"#,
+                        cx + s * 0.4,
+                        cy + s * 0.35,
+                        s * 0.3,
+                        escape(&options.text_color)
+                    )
+                    .unwrap();
+                }
+            }
+        }
+    }
+
+    fn render_arrows(&self, svg: &mut String, layout: &Layout, options: &SvgOptions) {
+        let s = layout.square;
+
+        for action in &self.arrows {
+            let Action::Move(color, from, to, pt) = *action else {
+                continue;
+            };
+            let (x1, y1) = match from {
+                MoveFrom::Board(from) => layout.square_center(from),
+                MoveFrom::Hand => {
+                    // A piece which is not in hand yet, e.g. captured earlier in a PV, has
+                    // no slot to draw the arrow from.
+                    let Some(index) = self.board.hand_pieces(color).position(|(p, _)| p == pt)
+                    else {
+                        continue;
+                    };
+                    layout.hand_center(color, index)
+                }
+            };
+            let (x2, y2) = layout.square_center(to);
+            writeln!(
+                svg,
+                r#"<line x1="{x1}" y1="{y1}" x2="{x2}" y2="{y2}" stroke="{}" stroke-width="{}" stroke-opacity="0.8" stroke-linecap="round" marker-end="url(#arrowhead)"/>"#,
+                escape(&options.arrow_color),
+                s / 8.0
+            )
+            .unwrap();
+        }
+    }
+}
+
+impl GameRecord {
+    /// Renders the position after the first `ply` records of the mainline as an SVG image,
+    /// highlighting the last move and drawing `pv` as arrows.
+    pub fn to_svg(
+        &self,
+        ply: usize,
+        pv: &[Action],
+        options: &SvgOptions,
+    ) -> Result<String, CsaError> {
+        let mut board = Board::from_position(&self.start_pos);
+        let mut last_move = None;
+
+        for record in self.moves.iter().take(ply) {
+            board.apply(&record.action)?;
+            if let Action::Move(..) = record.action {
+                last_move = Some(record.action);
+            }
+        }
+
+        let diagram = SvgDiagram {
+            last_move,
+            arrows: pv.to_vec(),
+            black_player: self.black_player.as_deref(),
+            white_player: self.white_player.as_deref(),
+            ..SvgDiagram::new(&board)
+        };
+        Ok(diagram.render(options))
+    }
+}
+
+////////////////////////////////////////////////////////////////////////////////
+
+struct Layout {
+    flipped: bool,
+    square: f64,
+    pad: f64,
+    label: f64,
+    board_x: f64,
+    board_y: f64,
+    width: f64,
+    height: f64,
+}
+
+impl Layout {
+    fn new(options: &SvgOptions) -> Layout {
+        let square = options.square_size as f64;
+        let pad = square / 4.0;
+        let label = square / 2.0;
+        let board_x = pad + square + pad;
+        let board_y = pad + label * 2.0;
+
+        Layout {
+            flipped: options.flipped,
+            square,
+            pad,
+            label,
+            board_x,
+            board_y,
+            width: board_x + square * 9.0 + label + pad + square + pad,
+            height: board_y + square * 9.0 + label * 2.0 + pad,
+        }
+    }
+
+    fn is_upside_down(&self, color: Color) -> bool {
+        (color == Color::White) != self.flipped
+    }
+
+    fn square_origin(&self, sq: Square) -> (f64, f64) {
+        let (col, row) = if self.flipped {
+            (sq.file() - 1, 9 - sq.rank())
+        } else {
+            (9 - sq.file(), sq.rank() - 1)
+        };
+
+        (
+            self.board_x + self.square * col as f64,
+            self.board_y + self.square * row as f64,
+        )
+    }
+
+    fn square_center(&self, sq: Square) -> (f64, f64) {
+        let (x, y) = self.square_origin(sq);
+        (x + self.square / 2.0, y + self.square / 2.0)
+    }
+
+    /// Returns the center of the `index`-th piece in the hand of the given side.
+    ///
+    /// The upper hand is listed downwards on the left, the lower one upwards on the right.
+    fn hand_center(&self, color: Color, index: usize) -> (f64, f64) {
+        let offset = self.square * (index as f64 + 0.5);
+
+        if self.is_upside_down(color) {
+            (self.pad + self.square / 2.0, self.board_y + offset)
+        } else {
+            (
+                self.width - self.pad - self.square / 2.0,
+                self.board_y + self.square * 9.0 - offset,
+            )
+        }
+    }
+}
+
+fn render_piece(
+    svg: &mut String,
+    (cx, cy): (f64, f64),
+    size: f64,
+    upside_down: bool,
+    pt: PieceType,
+    options: &SvgOptions,
+) {
+    let outline = [
+        (0.0, -0.42),
+        (0.3, -0.3),
+        (0.38, 0.42),
+        (-0.38, 0.42),
+        (-0.3, -0.3),
+    ]
+    .iter()
+    .map(|(x, y)| format!("{},{}", cx + x * size, cy + y * size))
+    .collect::<Vec<_>>()
+    .join(" ");
+    let rotation = if upside_down { 180 } else { 0 };
+    let text_color = if pt.is_promoted() {
+        &options.promoted_color
+    } else {
+        &options.text_color
+    };
+
+    writeln!(
+        svg,
+        r#"<g transform="rotate({rotation} {cx} {cy})"><polygon points="{outline}" fill="{}" stroke="{}"/><text x="{cx}" y="{}" font-size="{}" text-anchor="middle" dominant-baseline="central" fill="{}">{}</text></g>"#,
+        escape(&options.piece_color),
+        escape(&options.line_color),
+        cy + size * 0.04,
+        size * 0.55,
+        escape(text_color),
+        pt.kanji_char().unwrap_or('？')
+    )
+    .unwrap();
+}
+
+fn escape(s: &str) -> String {
+    s.chars()
+        .map(|c| match c {
+            '&' => "&amp;".to_string(),
+            '<' => "&lt;".to_string(),
+            '>' => "&gt;".to_string(),
+            '"' => "&quot;".to_string(),
+            '\'' => "&apos;".to_string(),
+            c => c.to_string(),
+        })
+        .collect()
+}
+
+////////////////////////////////////////////////////////////////////////////////
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn record() -> GameRecord {
+        let csa = "\
+N+Black & Co.
+N-<White>
+PI
++
++7776FU
+-3334FU
++8822UM
+-3122GI
+";
+        crate::parse_csa(csa).unwrap()
+    }
+
+    #[test]
+    fn render() {
+        let g = record();
+        let svg = g.to_svg(2, &[], &SvgOptions::default()).unwrap();
+
+        assert!(svg.starts_with("<svg xmlns=\"http://www.w3.org/2000/svg\""));
+        assert!(svg.ends_with("</svg>\n"));
+        assert_eq!(svg.matches("<polygon").count(), 40);
+        assert_eq!(svg.matches("rotate(180 ").count(), 20);
+        assert_eq!(svg.matches("fill=\"#f9a55c\"").count(), 2);
+        assert!(svg.contains(">☗Black &amp; Co.</text>"));
+        assert!(svg.contains(">☖&lt;White&gt;</text>"));
+        assert!(!svg.contains("marker-end"));
+    }
+
+    #[test]
+    fn hands_and_arrows() {
+        let g = record();
+        let pv = crate::parse_csa("PI\n+\n+0055KA\n+0044HI\n")
+            .unwrap()
+            .moves
+            .into_iter()
+            .map(|record| record.action)
+            .collect::<Vec<_>>();
+        let svg = g.to_svg(3, &pv, &SvgOptions::default()).unwrap();
+
+        // The horse is written in the promoted colour, the captured bishop is in hand.
+        assert!(svg.contains("fill=\"#c00000\">馬</text>"));
+        assert_eq!(svg.matches(">角</text>").count(), 1);
+        // The rook is not in hand, so only the bishop drop gets an arrow.
+        assert_eq!(svg.matches("marker-end=\"url(#arrowhead)\"").count(), 1);
+    }
+
+    #[test]
+    fn flipped() {
+        let board = Board::from_position(&crate::value::Position::default());
+        let normal = SvgDiagram::new(&board).render(&SvgOptions::default());
+        let flipped = SvgDiagram::new(&board).render(&SvgOptions {
+            flipped: true,
+            show_coordinates: false,
+            ..SvgOptions::default()
+        });
+
+        assert_ne!(normal, flipped);
+        assert_eq!(flipped.matches("rotate(180 ").count(), 20);
+        assert!(!flipped.contains(">一</text>"));
+
+        // Black's king is at the top when flipped.
+        let layout = Layout::new(&SvgOptions {
+            flipped: true,
+            ..SvgOptions::default()
+        });
+        let (_, y) = layout.square_center(Square::new(5, 9).unwrap());
+        assert!(y < layout.height / 2.0);
+    }
+}
@@ -0,0 +1,363 @@
+use std::io::{BufRead, Read, Write};
+use std::time::Duration;
+
+use super::{GoParams, UsiEngine, UsiError, UsiInfo, UsiScore, STARTPOS_SFEN};
+use crate::board::Board;
+use crate::clock::{Clock, TimeControl};
+use crate::protocol::{Client, GameOutcome, GameSummary, ServerMessage};
+use crate::value::{Action, Color, Evaluation, MoveRecord};
+
+/// The score sent for a forced mate, with the sign of the winning side.
+const MATE_SCORE: i32 = 100_000;
+
+/// Settings of [`play_usi_game`].
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct BridgeConfig {
+    /// Lets the engine think on the opponent's time about the reply it expects.
+    pub ponder: bool,
+    /// Time kept back from the engine's byoyomi, or from its main time without byoyomi,
+    /// for network delays.
+    pub byoyomi_margin: Duration,
+    /// Byoyomi given to the engine in games without a time limit.
+    pub unlimited_byoyomi: Duration,
+}
+
+impl Default for BridgeConfig {
+    fn default() -> Self {
+        BridgeConfig {
+            ponder: false,
+            byoyomi_margin: Duration::from_secs(1),
+            unlimited_byoyomi: Duration::from_secs(10),
+        }
+    }
+}
+
+/// Agrees to the game described by `summary` and plays it with a USI engine until the end.
+///
+/// Moves are converted between CSA and USI, the clock is tracked from the times sent by the
+/// server, and the engine's score and PV are sent as `'*` comments. The engine is told the
+/// result with `gameover`, which is also returned.
+pub fn play_usi_game<S, R, W>(
+    client: &mut Client<S>,
+    engine: &mut UsiEngine<R, W>,
+    summary: &GameSummary,
+    config: &BridgeConfig,
+) -> Result<GameOutcome, UsiError>
+where
+    S: Read + Write,
+    R: BufRead,
+    W: Write,
+{
+    let mut game = GameState::new(summary)?;
+    engine.is_ready()?;
+    engine.new_game()?;
+    client.agree(&summary.game_id)?;
+
+    // Whether our move was sent and its echo is awaited.
+    let mut sent = false;
+    // The pondered reply of the opponent, and whether it was played.
+    let mut pondering: Option<String> = None;
+    let mut ponder_hit = false;
+
+    loop {
+        if game.board.side_to_move() == summary.your_turn && !sent {
+            if ponder_hit {
+                ponder_hit = false;
+                engine.ponder_hit()?;
+            } else {
+                engine.set_position(game.start_sfen.as_deref(), &game.moves)?;
+                engine.go(&game.go_params(config))?;
+            }
+            let best = engine.wait_bestmove()?;
+
+            let action = match best.best.as_str() {
+                "resign" => Action::Toryo,
+                "win" => Action::Kachi,
+                usi => game.board.parse_usi_move(usi)?,
+            };
+            match best.info.and_then(|info| evaluation(&game.board, &info)) {
+                Some(evaluation) => client.send_move_with_evaluation(&action, &evaluation)?,
+                None => client.send_move(&action)?,
+            }
+            sent = true;
+
+            if let (true, Action::Move(..), Some(ponder)) = (config.ponder, action, best.ponder) {
+                let mut moves = game.moves.clone();
+                moves.extend([best.best, ponder.clone()]);
+                engine.set_position(game.start_sfen.as_deref(), &moves)?;
+                engine.go(&GoParams {
+                    ponder: true,
+                    ..game.go_params(config)
+                })?;
+                pondering = Some(ponder);
+            }
+        }
+
+        match client.recv()? {
+            // The echo of a special move is followed by the end of the game.
+            ServerMessage::Move(mut record) if matches!(record.action, Action::Move(..)) => {
+                // `T` of move lines counts `Time_Unit`s.
+                if let Some(ref time) = summary.time {
+                    record.time = record.time.map(|t| time.from_units(t.as_secs()));
+                }
+                let color = game.board.side_to_move();
+                let usi = game.play(&record)?;
+                if color == summary.your_turn {
+                    sent = false;
+                } else if let Some(ponder) = pondering.take() {
+                    if ponder == usi {
+                        ponder_hit = true;
+                    } else {
+                        engine.stop()?;
+                        engine.wait_bestmove()?;
+                    }
+                }
+            }
+            ServerMessage::Result(outcome) => {
+                if pondering.is_some() {
+                    engine.stop()?;
+                    engine.wait_bestmove()?;
+                }
+                engine.game_over(outcome)?;
+                return Ok(outcome);
+            }
+            _ => {}
+        }
+    }
+}
+
+/// The game as seen by the bridge.
+struct GameState {
+    board: Board,
+    /// The SFEN of the starting position, `None` for `startpos`.
+    start_sfen: Option<String>,
+    /// Moves played from the starting position in USI.
+    moves: Vec<String>,
+    /// `None` without a time limit.
+    clock: Option<Clock>,
+}
+
+impl GameState {
+    fn new(summary: &GameSummary) -> Result<GameState, UsiError> {
+        let board = Board::from_position(&summary.position);
+        let sfen = board.to_sfen(1);
+        let control = summary
+            .time
+            .clone()
+            .filter(|control| !control.is_unlimited());
+
+        let mut game = GameState {
+            board,
+            start_sfen: (sfen != STARTPOS_SFEN).then_some(sfen),
+            moves: Vec::new(),
+            clock: control.map(Clock::new),
+        };
+        for record in &summary.moves {
+            game.play(record)?;
+        }
+
+        Ok(game)
+    }
+
+    /// Plays a move echoed by the server and returns it in USI.
+    fn play(&mut self, record: &MoveRecord) -> Result<String, UsiError> {
+        let color = self.board.side_to_move();
+        let invalid = || UsiError::InvalidMove(record.action.to_string());
+
+        let usi = self.board.usi_move(&record.action).ok_or_else(invalid)?;
+        self.board.apply(&record.action).map_err(|_| invalid())?;
+        self.moves.push(usi.clone());
+        if let (Some(clock), Some(time)) = (self.clock.as_mut(), record.time) {
+            clock.consume(color, time);
+        }
+
+        Ok(usi)
+    }
+
+    fn go_params(&self, config: &BridgeConfig) -> GoParams {
+        go_params(
+            self.clock.as_ref(),
+            self.board.side_to_move(),
+            config.byoyomi_margin,
+            config.unlimited_byoyomi,
+        )
+    }
+}
+
+/// Builds the time parameters of `go` for the side to move, keeping `margin` back.
+pub(super) fn go_params(
+    clock: Option<&Clock>,
+    side_to_move: Color,
+    margin: Duration,
+    unlimited_byoyomi: Duration,
+) -> GoParams {
+    let Some(clock) = clock.filter(|clock| !clock.control().is_unlimited()) else {
+        return GoParams {
+            byoyomi: unlimited_byoyomi,
+            ..GoParams::default()
+        };
+    };
+    let &TimeControl {
+        byoyomi, increment, ..
+    } = clock.control();
+
+    let mut params = GoParams {
+        btime: clock.remaining(Color::Black),
+        wtime: clock.remaining(Color::White),
+        byoyomi: byoyomi.saturating_sub(margin),
+        binc: increment,
+        winc: increment,
+        ponder: false,
+    };
+    if byoyomi.is_zero() {
+        let own = match side_to_move {
+            Color::Black => &mut params.btime,
+            Color::White => &mut params.wtime,
+        };
+        *own = own.saturating_sub(margin);
+    }
+
+    params
+}
+
+/// Converts the engine's score and PV, following its best move, to an evaluation from Black's point of view.
+pub(super) fn evaluation(board: &Board, info: &UsiInfo) -> Option<Evaluation> {
+    let score = match info.score? {
+        UsiScore::Cp(cp) => cp,
+        UsiScore::Mate(plies) if plies > 0 => MATE_SCORE,
+        UsiScore::Mate(_) => -MATE_SCORE,
+    };
+    let score = match board.side_to_move() {
+        Color::Black => score,
+        Color::White => -score,
+    };
+
+    let mut board = board.clone();
+    let mut pv = Vec::new();
+    for usi in &info.pv {
+        match board.parse_usi_move(usi) {
+            Ok(action) if board.apply(&action).is_ok() => pv.push(action),
+            _ => break,
+        }
+    }
+    pv.drain(..pv.len().min(1));
+
+    Some(Evaluation { score, pv })
+}
+
+////////////////////////////////////////////////////////////////////////////////
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::value::{Handicap, Position};
+    use std::io::{self, Cursor};
+
+    struct MockStream {
+        input: Cursor<Vec<u8>>,
+        output: Vec<u8>,
+    }
+
+    impl Read for MockStream {
+        fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+            self.input.read(buf)
+        }
+    }
+
+    impl Write for MockStream {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            self.output.write(buf)
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    fn summary() -> GameSummary {
+        GameSummary {
+            time: Some(TimeControl {
+                byoyomi: Duration::from_secs(10),
+                ..TimeControl::sudden_death(Duration::from_secs(600))
+            }),
+            ..GameSummary::new(
+                "g1",
+                "engine",
+                "human",
+                Position::from_handicap(Handicap::Even),
+            )
+        }
+    }
+
+    #[test]
+    fn play_with_ponder() {
+        let mut client = Client::new(MockStream {
+            input: Cursor::new(
+                b"\
+START:g1
++7776FU,T3
+-3334FU,T2
++2726FU,T1
+-8384FU,T4
+%TORYO,T0
+#RESIGN
+#LOSE
+"
+                .to_vec(),
+            ),
+            output: Vec::new(),
+        });
+        let mut engine_input = Vec::new();
+        let mut engine = UsiEngine::new(
+            Cursor::new(
+                "\
+readyok
+info depth 2 score cp 30 pv 7g7f 3c3d
+bestmove 7g7f ponder 3c3d
+info depth 1 score mate 3 pv 2g2f
+bestmove 2g2f
+bestmove resign
+",
+            ),
+            &mut engine_input,
+        );
+        let config = BridgeConfig {
+            ponder: true,
+            ..BridgeConfig::default()
+        };
+
+        let outcome = play_usi_game(&mut client, &mut engine, &summary(), &config).unwrap();
+        assert_eq!(outcome, GameOutcome::Lose);
+        drop(engine);
+
+        assert_eq!(
+            String::from_utf8(client.into_inner().output).unwrap(),
+            "AGREE g1\n+7776FU,'* 30 -3334FU\n+2726FU,'* 100000\n%TORYO\n"
+        );
+        assert_eq!(
+            String::from_utf8(engine_input).unwrap(),
+            "\
+isready
+usinewgame
+position startpos
+go btime 600000 wtime 600000 byoyomi 9000
+position startpos moves 7g7f 3c3d
+go ponder btime 600000 wtime 600000 byoyomi 9000
+ponderhit
+position startpos moves 7g7f 3c3d 2g2f 8c8d
+go btime 596000 wtime 594000 byoyomi 9000
+gameover lose
+"
+        );
+    }
+
+    #[test]
+    fn evaluation_from_white() {
+        let mut board = Board::from_position(&Position::from_handicap(Handicap::Even));
+        board.apply(&board.parse_usi_move("7g7f").unwrap()).unwrap();
+        let info = UsiInfo::parse("info score cp 120 pv 3c3d 2g2f x").unwrap();
+
+        let evaluation = evaluation(&board, &info).unwrap();
+        assert_eq!(evaluation.to_string(), "* -120 +2726FU");
+    }
+}
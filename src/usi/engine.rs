@@ -0,0 +1,483 @@
+use std::fmt;
+use std::io::{self, BufRead, BufReader, Write};
+use std::process::{Child, ChildStdin, ChildStdout, Command, Stdio};
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use super::UsiError;
+use crate::protocol::GameOutcome;
+
+/// A score reported by a USI engine from the point of view of the side to move.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum UsiScore {
+    /// `score cp <x>`
+    Cp(i32),
+    /// `score mate <plies>`, negative when being mated.
+    ///
+    /// An unknown distance, `mate +` or `mate -`, is represented as `i32::MAX` or `-i32::MAX`.
+    Mate(i32),
+}
+
+/// The fields of an `info` line which the bridge makes use of.
+#[derive(Debug, PartialEq, Eq, Clone, Default)]
+pub struct UsiInfo {
+    pub depth: Option<u32>,
+    pub nodes: Option<u64>,
+    pub score: Option<UsiScore>,
+    /// The principal variation as USI moves.
+    pub pv: Vec<String>,
+}
+
+impl UsiInfo {
+    /// Parses an `info` line, ignoring unknown fields and a trailing `string`.
+    pub fn parse(line: &str) -> Option<UsiInfo> {
+        let mut tokens = line.split_whitespace();
+        if tokens.next()? != "info" {
+            return None;
+        }
+
+        let mut info = UsiInfo::default();
+        while let Some(token) = tokens.next() {
+            match token {
+                "depth" => info.depth = tokens.next().and_then(|s| s.parse().ok()),
+                "nodes" => info.nodes = tokens.next().and_then(|s| s.parse().ok()),
+                "score" => {
+                    info.score = match (tokens.next(), tokens.next()) {
+                        (Some("cp"), Some(cp)) => cp.parse().ok().map(UsiScore::Cp),
+                        (Some("mate"), Some("+")) => Some(UsiScore::Mate(i32::MAX)),
+                        (Some("mate"), Some("-")) => Some(UsiScore::Mate(-i32::MAX)),
+                        (Some("mate"), Some(plies)) => plies.parse().ok().map(UsiScore::Mate),
+                        _ => None,
+                    };
+                }
+                "pv" => info.pv = tokens.by_ref().map(str::to_string).collect(),
+                "string" => break,
+                _ => {}
+            }
+        }
+
+        Some(info)
+    }
+}
+
+/// The result of a search, `bestmove <move> [ponder <move>]`.
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct BestMove {
+    /// A USI move, `resign` or `win`.
+    pub best: String,
+    pub ponder: Option<String>,
+    /// The last `info` line with a score received during the search.
+    pub info: Option<UsiInfo>,
+}
+
+/// Time parameters of a `go` command.
+#[derive(Debug, PartialEq, Eq, Clone, Default)]
+pub struct GoParams {
+    pub btime: Duration,
+    pub wtime: Duration,
+    pub byoyomi: Duration,
+    pub binc: Duration,
+    pub winc: Duration,
+    /// Starts pondering, which ends with `ponderhit` or `stop`.
+    pub ponder: bool,
+}
+
+impl fmt::Display for GoParams {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "go")?;
+        if self.ponder {
+            write!(f, " ponder")?;
+        }
+        write!(
+            f,
+            " btime {} wtime {}",
+            self.btime.as_millis(),
+            self.wtime.as_millis()
+        )?;
+        if self.binc.is_zero() && self.winc.is_zero() {
+            write!(f, " byoyomi {}", self.byoyomi.as_millis())
+        } else {
+            write!(
+                f,
+                " binc {} winc {}",
+                self.binc.as_millis(),
+                self.winc.as_millis()
+            )
+        }
+    }
+}
+
+/// Where the output of an engine is read from.
+enum Input<R> {
+    Reader(R),
+    /// Lines read by a thread, used once a read with a timeout has been made.
+    Thread(Receiver<io::Result<String>>),
+}
+
+/// A USI engine, usually a subprocess started by [`UsiEngine::spawn`].
+pub struct UsiEngine<R: BufRead, W: Write> {
+    input: Input<R>,
+    writer: W,
+    child: Option<Child>,
+    name: Option<String>,
+}
+
+impl UsiEngine<BufReader<ChildStdout>, ChildStdin> {
+    /// Starts the engine and waits for `usiok`.
+    pub fn spawn(command: &mut Command) -> Result<Self, UsiError> {
+        let mut child = command
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .spawn()?;
+        let writer = child.stdin.take().unwrap();
+        let reader = BufReader::new(child.stdout.take().unwrap());
+
+        let mut engine = UsiEngine::new(reader, writer);
+        engine.child = Some(child);
+        engine.usi()?;
+        Ok(engine)
+    }
+}
+
+impl<R: BufRead, W: Write> UsiEngine<R, W> {
+    /// Wraps the streams of an engine without sending anything.
+    pub fn new(reader: R, writer: W) -> UsiEngine<R, W> {
+        UsiEngine {
+            input: Input::Reader(reader),
+            writer,
+            child: None,
+            name: None,
+        }
+    }
+
+    /// The name reported by `id name`, once [`UsiEngine::usi`] has been called.
+    pub fn name(&self) -> Option<&str> {
+        self.name.as_deref()
+    }
+
+    /// Sends `usi` and waits for `usiok`.
+    pub fn usi(&mut self) -> Result<(), UsiError> {
+        self.send("usi")?;
+        loop {
+            let line = self.read_line()?;
+            if line == "usiok" {
+                return Ok(());
+            }
+            if let Some(name) = line.strip_prefix("id name ") {
+                self.name = Some(name.to_string());
+            }
+        }
+    }
+
+    pub fn set_option(&mut self, name: &str, value: &str) -> Result<(), UsiError> {
+        self.send(&format!("setoption name {name} value {value}"))
+    }
+
+    /// Sends `isready` and waits for `readyok`.
+    pub fn is_ready(&mut self) -> Result<(), UsiError> {
+        self.send("isready")?;
+        while self.read_line()? != "readyok" {}
+        Ok(())
+    }
+
+    pub fn new_game(&mut self) -> Result<(), UsiError> {
+        self.send("usinewgame")
+    }
+
+    /// Sets the position from an SFEN, or `None` for `startpos`, followed by USI moves.
+    pub fn set_position(&mut self, sfen: Option<&str>, moves: &[String]) -> Result<(), UsiError> {
+        let mut command = match sfen {
+            Some(sfen) => format!("position sfen {sfen}"),
+            None => "position startpos".to_string(),
+        };
+        if !moves.is_empty() {
+            command.push_str(" moves ");
+            command.push_str(&moves.join(" "));
+        }
+        self.send(&command)
+    }
+
+    /// Starts a search, whose result is read by [`UsiEngine::wait_bestmove`].
+    pub fn go(&mut self, params: &GoParams) -> Result<(), UsiError> {
+        self.send(&params.to_string())
+    }
+
+    /// Tells the engine that the opponent played the pondered move.
+    pub fn ponder_hit(&mut self) -> Result<(), UsiError> {
+        self.send("ponderhit")
+    }
+
+    pub fn stop(&mut self) -> Result<(), UsiError> {
+        self.send("stop")
+    }
+
+    /// Reads `info` lines until `bestmove`.
+    pub fn wait_bestmove(&mut self) -> Result<BestMove, UsiError> {
+        // Without a deadline, the line is always read.
+        self.read_bestmove(None).map(Option::unwrap)
+    }
+
+    /// Sends `gameover win`, `gameover lose` or `gameover draw`.
+    pub fn game_over(&mut self, outcome: GameOutcome) -> Result<(), UsiError> {
+        let result = match outcome {
+            GameOutcome::Win => "win",
+            GameOutcome::Lose => "lose",
+            GameOutcome::Draw | GameOutcome::Censored => "draw",
+        };
+        self.send(&format!("gameover {result}"))
+    }
+
+    /// Kills the subprocess, if any, without waiting for it to quit.
+    pub fn kill(&mut self) -> Result<(), UsiError> {
+        if let Some(mut child) = self.child.take() {
+            child.kill()?;
+            child.wait()?;
+        }
+        Ok(())
+    }
+
+    /// Sends `quit` and waits for the subprocess, if any, to exit.
+    ///
+    /// An engine which already exited, e.g. after [`UsiEngine::kill`], is left as it is.
+    pub fn quit(mut self) -> Result<(), UsiError> {
+        match self.send("quit") {
+            Err(UsiError::Io(e)) if e.kind() == io::ErrorKind::BrokenPipe => return Ok(()),
+            result => result?,
+        }
+        if let Some(mut child) = self.child.take() {
+            child.wait()?;
+        }
+        Ok(())
+    }
+
+    fn send(&mut self, command: &str) -> Result<(), UsiError> {
+        writeln!(self.writer, "{command}")?;
+        self.writer.flush()?;
+        Ok(())
+    }
+
+    fn read_line(&mut self) -> Result<String, UsiError> {
+        // Without a deadline, the line is always read.
+        self.read_line_until(None).map(Option::unwrap)
+    }
+
+    /// Reads a line, or returns `Ok(None)` once the deadline has passed.
+    ///
+    /// The deadline is only honored once the output is read by a thread.
+    fn read_line_until(&mut self, deadline: Option<Instant>) -> Result<Option<String>, UsiError> {
+        let line = match self.input {
+            Input::Reader(ref mut reader) => {
+                let mut line = String::new();
+                if reader.read_line(&mut line)? == 0 {
+                    return Err(UsiError::EngineExited);
+                }
+                line
+            }
+            Input::Thread(ref lines) => {
+                let received = match deadline {
+                    Some(deadline) => {
+                        lines.recv_timeout(deadline.saturating_duration_since(Instant::now()))
+                    }
+                    None => lines.recv().map_err(|_| RecvTimeoutError::Disconnected),
+                };
+                match received {
+                    Ok(line) => line?,
+                    Err(RecvTimeoutError::Timeout) => return Ok(None),
+                    Err(RecvTimeoutError::Disconnected) => return Err(UsiError::EngineExited),
+                }
+            }
+        };
+        Ok(Some(line.trim_end_matches(['\r', '\n']).to_string()))
+    }
+
+    /// Reads `info` lines until `bestmove`, or returns `Ok(None)` once the deadline has passed.
+    fn read_bestmove(&mut self, deadline: Option<Instant>) -> Result<Option<BestMove>, UsiError> {
+        let mut last_info = None;
+        loop {
+            let Some(line) = self.read_line_until(deadline)? else {
+                return Ok(None);
+            };
+            if let Some(rest) = line.strip_prefix("bestmove ") {
+                let mut tokens = rest.split_whitespace();
+                let best = tokens.next().unwrap_or_default().to_string();
+                let ponder = match (tokens.next(), tokens.next()) {
+                    (Some("ponder"), Some(mv)) => Some(mv.to_string()),
+                    _ => None,
+                };
+                return Ok(Some(BestMove {
+                    best,
+                    ponder,
+                    info: last_info,
+                }));
+            }
+            if let Some(info) = UsiInfo::parse(&line).filter(|info| info.score.is_some()) {
+                last_info = Some(info);
+            }
+        }
+    }
+}
+
+impl<R: BufRead + Send + 'static, W: Write> UsiEngine<R, W> {
+    /// Reads `info` lines until `bestmove`, or returns `Ok(None)` if it is not received
+    /// within the timeout.
+    ///
+    /// From the first call on, the output of the engine is read by a thread.
+    pub fn wait_bestmove_timeout(
+        &mut self,
+        timeout: Duration,
+    ) -> Result<Option<BestMove>, UsiError> {
+        if let Input::Reader(_) = self.input {
+            let (sender, receiver) = mpsc::channel();
+            if let Input::Reader(mut reader) =
+                std::mem::replace(&mut self.input, Input::Thread(receiver))
+            {
+                thread::spawn(move || loop {
+                    let mut line = String::new();
+                    let result = match reader.read_line(&mut line) {
+                        Ok(0) => break,
+                        result => result.map(|_| line),
+                    };
+                    let failed = result.is_err();
+                    if sender.send(result).is_err() || failed {
+                        break;
+                    }
+                });
+            }
+        }
+
+        self.read_bestmove(Instant::now().checked_add(timeout))
+    }
+}
+
+impl<R: BufRead, W: Write> Drop for UsiEngine<R, W> {
+    fn drop(&mut self) {
+        if let Some(mut child) = self.child.take() {
+            let _ = child.kill();
+            let _ = child.wait();
+        }
+    }
+}
+
+////////////////////////////////////////////////////////////////////////////////
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Cursor;
+
+    #[test]
+    fn parse_info() {
+        assert_eq!(
+            UsiInfo::parse(
+                "info depth 12 seldepth 20 score cp -45 nodes 123456 nps 1000 pv 3c3d 2g2f"
+            ),
+            Some(UsiInfo {
+                depth: Some(12),
+                nodes: Some(123456),
+                score: Some(UsiScore::Cp(-45)),
+                pv: vec!["3c3d".to_string(), "2g2f".to_string()],
+            })
+        );
+        assert_eq!(
+            UsiInfo::parse("info score mate -3 lowerbound")
+                .unwrap()
+                .score,
+            Some(UsiScore::Mate(-3))
+        );
+        assert_eq!(
+            UsiInfo::parse("info score mate + pv 5b5a").unwrap().score,
+            Some(UsiScore::Mate(i32::MAX))
+        );
+        assert_eq!(
+            UsiInfo::parse("info string depth 3 pv 7g7f"),
+            Some(UsiInfo::default())
+        );
+        assert_eq!(UsiInfo::parse("bestmove 7g7f"), None);
+    }
+
+    #[test]
+    fn go_params() {
+        let params = GoParams {
+            btime: Duration::from_secs(60),
+            wtime: Duration::from_millis(59500),
+            byoyomi: Duration::from_secs(10),
+            ..GoParams::default()
+        };
+        assert_eq!(
+            params.to_string(),
+            "go btime 60000 wtime 59500 byoyomi 10000"
+        );
+
+        let params = GoParams {
+            ponder: true,
+            binc: Duration::from_secs(5),
+            winc: Duration::from_secs(5),
+            ..params
+        };
+        assert_eq!(
+            params.to_string(),
+            "go ponder btime 60000 wtime 59500 binc 5000 winc 5000"
+        );
+    }
+
+    #[test]
+    fn search() {
+        let output = "\
+id name Mock 1.0
+id author someone
+option name USI_Hash type spin default 256
+usiok
+readyok
+info depth 1 score cp 10 pv 2g2f
+info depth 2 score cp 30 pv 7g7f 3c3d
+info string thinking
+bestmove 7g7f ponder 3c3d
+";
+        let mut input = Vec::new();
+        let mut engine = UsiEngine::new(Cursor::new(output), &mut input);
+
+        engine.usi().unwrap();
+        assert_eq!(engine.name(), Some("Mock 1.0"));
+        engine.set_option("USI_Hash", "1024").unwrap();
+        engine.is_ready().unwrap();
+        engine.new_game().unwrap();
+        engine
+            .set_position(None, &["2g2f".to_string(), "8c8d".to_string()])
+            .unwrap();
+        engine.go(&GoParams::default()).unwrap();
+
+        let best = engine.wait_bestmove().unwrap();
+        assert_eq!(best.best, "7g7f");
+        assert_eq!(best.ponder.as_deref(), Some("3c3d"));
+        assert_eq!(best.info.unwrap().score, Some(UsiScore::Cp(30)));
+        assert!(matches!(
+            engine.wait_bestmove(),
+            Err(UsiError::EngineExited)
+        ));
+        engine.game_over(GameOutcome::Censored).unwrap();
+        drop(engine);
+
+        assert_eq!(
+            String::from_utf8(input).unwrap(),
+            "\
+usi
+setoption name USI_Hash value 1024
+isready
+usinewgame
+position startpos moves 2g2f 8c8d
+go btime 0 wtime 0 byoyomi 0
+gameover draw
+"
+        );
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn quit_after_kill() {
+        let mut engine =
+            UsiEngine::spawn(Command::new("sh").args(["-c", "echo usiok; exec sleep 60"])).unwrap();
+        engine.kill().unwrap();
+        engine.quit().unwrap();
+    }
+}
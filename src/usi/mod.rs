@@ -0,0 +1,60 @@
+mod bridge;
+mod engine;
+mod sfen;
+mod tournament;
+
+pub use self::bridge::*;
+pub use self::engine::*;
+pub use self::sfen::*;
+pub use self::tournament::*;
+
+use std::error::Error;
+use std::fmt;
+use std::io;
+
+use crate::protocol::ProtocolError;
+
+#[derive(Debug)]
+pub enum UsiError {
+    Io(io::Error),
+    /// The engine closed its output.
+    EngineExited,
+    InvalidSfen(String),
+    /// A move which cannot be played in the current position.
+    InvalidMove(String),
+    Protocol(ProtocolError),
+}
+
+impl fmt::Display for UsiError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            UsiError::Io(ref e) => write!(f, "I/O error: {e}"),
+            UsiError::EngineExited => write!(f, "the engine exited"),
+            UsiError::InvalidSfen(ref sfen) => write!(f, "invalid SFEN: {sfen}"),
+            UsiError::InvalidMove(ref mv) => write!(f, "invalid move: {mv}"),
+            UsiError::Protocol(ref e) => write!(f, "protocol error: {e}"),
+        }
+    }
+}
+
+impl Error for UsiError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match *self {
+            UsiError::Io(ref e) => Some(e),
+            UsiError::Protocol(ref e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<io::Error> for UsiError {
+    fn from(e: io::Error) -> Self {
+        UsiError::Io(e)
+    }
+}
+
+impl From<ProtocolError> for UsiError {
+    fn from(e: ProtocolError) -> Self {
+        UsiError::Protocol(e)
+    }
+}
@@ -0,0 +1,274 @@
+use super::UsiError;
+use crate::board::{Board, HAND_PIECE_TYPES};
+use crate::value::{Action, Color, MoveFrom, PieceType, Square};
+
+/// The SFEN of the standard starting position, sent as `startpos` in USI.
+pub const STARTPOS_SFEN: &str = "lnsgkgsnl/1r5b1/ppppppppp/9/9/9/PPPPPPPPP/1B5R1/LNSGKGSNL b - 1";
+
+impl Board {
+    /// Formats the position in SFEN with the given move number.
+    pub fn to_sfen(&self, move_number: u32) -> String {
+        let mut sfen = String::new();
+
+        for rank in 1..=9 {
+            if rank > 1 {
+                sfen.push('/');
+            }
+            let mut empty = 0;
+            for file in (1..=9).rev() {
+                match self.get(Square::new(file, rank).unwrap()) {
+                    Some((color, pt)) => {
+                        if empty > 0 {
+                            sfen.push_str(&empty.to_string());
+                            empty = 0;
+                        }
+                        sfen.push_str(&piece_str(color, pt));
+                    }
+                    None => empty += 1,
+                }
+            }
+            if empty > 0 {
+                sfen.push_str(&empty.to_string());
+            }
+        }
+
+        sfen.push_str(match self.side_to_move() {
+            Color::Black => " b ",
+            Color::White => " w ",
+        });
+
+        let mut hand = String::new();
+        for color in [Color::Black, Color::White] {
+            for (pt, n) in self.hand_pieces(color) {
+                if n > 1 {
+                    hand.push_str(&n.to_string());
+                }
+                hand.push_str(&piece_str(color, pt));
+            }
+        }
+        if hand.is_empty() {
+            hand.push('-');
+        }
+        sfen.push_str(&hand);
+
+        format!("{sfen} {move_number}")
+    }
+
+    /// Reads a position in SFEN, where the move number is optional.
+    pub fn from_sfen(sfen: &str) -> Result<Board, UsiError> {
+        let invalid = || UsiError::InvalidSfen(sfen.to_string());
+        let fields = sfen.split_whitespace().collect::<Vec<_>>();
+        let (grid, side, hand) = match fields[..] {
+            [grid, side, hand] | [grid, side, hand, _] => (grid, side, hand),
+            _ => return Err(invalid()),
+        };
+
+        let mut board = Board::default();
+        let rows = grid.split('/').collect::<Vec<_>>();
+        if rows.len() != 9 {
+            return Err(invalid());
+        }
+        for (rank, row) in (1..=9).zip(rows) {
+            let mut file = 10u8;
+            let mut promoted = false;
+            for c in row.chars() {
+                match c {
+                    '+' => promoted = true,
+                    '1'..='9' if !promoted => file = file.saturating_sub(c as u8 - b'0'),
+                    _ => {
+                        let pt = PieceType::from_usi_char(c).ok_or_else(invalid)?;
+                        let pt = if promoted {
+                            pt.promote().ok_or_else(invalid)?
+                        } else {
+                            pt
+                        };
+                        file = file.saturating_sub(1);
+                        let sq = Square::new(file, rank).ok_or_else(invalid)?;
+                        board.set(sq, Some((color_of(c), pt)));
+                        promoted = false;
+                    }
+                }
+            }
+            if file != 1 || promoted {
+                return Err(invalid());
+            }
+        }
+
+        board.set_side_to_move(match side {
+            "b" => Color::Black,
+            "w" => Color::White,
+            _ => return Err(invalid()),
+        });
+
+        if hand != "-" {
+            let mut count = 0u8;
+            for c in hand.chars() {
+                match c.to_digit(10) {
+                    Some(d) => count = count.saturating_mul(10).saturating_add(d as u8),
+                    None => {
+                        let pt = PieceType::from_usi_char(c)
+                            .filter(|pt| HAND_PIECE_TYPES.contains(pt))
+                            .ok_or_else(invalid)?;
+                        board.set_hand(color_of(c), pt, count.max(1));
+                        count = 0;
+                    }
+                }
+            }
+            if count > 0 {
+                return Err(invalid());
+            }
+        }
+
+        Ok(board)
+    }
+
+    /// Reads a USI move such as `7g7f`, `8h2b+` or `P*5e` played by the side to move.
+    ///
+    /// The move is checked against the pieces present, but not against the rules of movement.
+    pub fn parse_usi_move(&self, usi: &str) -> Result<Action, UsiError> {
+        let invalid = || UsiError::InvalidMove(usi.to_string());
+        let color = self.side_to_move();
+        let chars = usi.chars().collect::<Vec<_>>();
+
+        match chars[..] {
+            [pc, '*', file, rank] => {
+                let pt = PieceType::from_usi_char(pc)
+                    .filter(|_| pc.is_ascii_uppercase())
+                    .ok_or_else(invalid)?;
+                let to = usi_square(file, rank).ok_or_else(invalid)?;
+                Ok(Action::Move(color, MoveFrom::Hand, to, pt))
+            }
+            [from_file, from_rank, file, rank, ref promotion @ ..] => {
+                let from = usi_square(from_file, from_rank).ok_or_else(invalid)?;
+                let to = usi_square(file, rank).ok_or_else(invalid)?;
+                let pt = match self.get(from) {
+                    Some((c, pt)) if c == color => pt,
+                    _ => return Err(invalid()),
+                };
+                let pt = match *promotion {
+                    [] => pt,
+                    ['+'] => pt.promote().ok_or_else(invalid)?,
+                    _ => return Err(invalid()),
+                };
+                Ok(Action::Move(color, MoveFrom::Board(from), to, pt))
+            }
+            _ => Err(invalid()),
+        }
+    }
+
+    /// Formats a move in USI, or returns `None` for special moves and moves of missing pieces.
+    pub fn usi_move(&self, action: &Action) -> Option<String> {
+        let Action::Move(_, from, to, pt) = *action else {
+            return None;
+        };
+
+        match from {
+            MoveFrom::Hand => Some(format!("{}*{}", pt.usi_char()?, square_str(to))),
+            MoveFrom::Board(from) => {
+                let (_, before) = self.get(from)?;
+                let promotion = if before != pt { "+" } else { "" };
+                Some(format!("{}{}{promotion}", square_str(from), square_str(to)))
+            }
+        }
+    }
+}
+
+fn piece_str(color: Color, pt: PieceType) -> String {
+    let c = pt.usi_char().unwrap_or('?');
+    let c = match color {
+        Color::Black => c,
+        Color::White => c.to_ascii_lowercase(),
+    };
+    if pt.is_promoted() {
+        format!("+{c}")
+    } else {
+        c.to_string()
+    }
+}
+
+fn color_of(c: char) -> Color {
+    if c.is_ascii_uppercase() {
+        Color::Black
+    } else {
+        Color::White
+    }
+}
+
+fn usi_square(file: char, rank: char) -> Option<Square> {
+    let file = file.to_digit(10)? as u8;
+    if !('a'..='i').contains(&rank) {
+        return None;
+    }
+    Square::new(file, rank as u8 - b'a' + 1)
+}
+
+fn square_str(sq: Square) -> String {
+    format!("{}{}", sq.file(), (b'a' + sq.rank() - 1) as char)
+}
+
+////////////////////////////////////////////////////////////////////////////////
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::value::Position;
+
+    #[test]
+    fn startpos() {
+        let board = Board::from_position(&Position::default());
+
+        assert_eq!(board.to_sfen(1), STARTPOS_SFEN);
+        assert_eq!(Board::from_sfen(STARTPOS_SFEN).unwrap(), board);
+    }
+
+    #[test]
+    fn sfen_round_trip() {
+        let sfen = "ln1g3+Rl/2s1kg3/p1ppppsp1/9/1p5p1/2P6/PP1PPPP1P/2G4K1/LNS2GSNL w B2Pb3p 42";
+        let board = Board::from_sfen(sfen).unwrap();
+
+        assert_eq!(board.side_to_move(), Color::White);
+        assert_eq!(
+            board.get(Square::new(2, 1).unwrap()),
+            Some((Color::Black, PieceType::Dragon))
+        );
+        assert_eq!(board.hand(Color::Black, PieceType::Pawn), 2);
+        assert_eq!(board.hand(Color::White, PieceType::Pawn), 3);
+        assert_eq!(board.to_sfen(42), sfen);
+
+        assert!(Board::from_sfen("9/9/9 b - 1").is_err());
+        assert!(
+            Board::from_sfen("lnsgkgsnl/1r5b1/ppppppppp/9/9/9/PPPPPPPPP/1B5R1/LNSGKGSN b - 1")
+                .is_err()
+        );
+        assert!(Board::from_sfen(
+            "lnsgkgsnl/1r5b1/ppppppppp/9/9/9/PPPPPPPPP/1B5R1/LNSGKGSNL x - 1"
+        )
+        .is_err());
+        assert!(Board::from_sfen(
+            "lnsgkgsnl/1r5b1/ppppppppp/9/9/9/PPPPPPPPP/1B5R1/LNSGKGSNL b K 1"
+        )
+        .is_err());
+    }
+
+    #[test]
+    fn usi_moves() {
+        let mut board = Board::from_position(&Position::default());
+        let moves = ["7g7f", "3c3d", "8h2b+", "3a2b", "B*4e"];
+
+        for usi in moves {
+            let action = board.parse_usi_move(usi).unwrap();
+            assert_eq!(board.usi_move(&action).as_deref(), Some(usi));
+            board.apply(&action).unwrap();
+        }
+        assert_eq!(
+            board.get(Square::new(4, 5).unwrap()),
+            Some((Color::Black, PieceType::Bishop))
+        );
+
+        assert!(board.parse_usi_move("7g7f").is_err());
+        assert!(board.parse_usi_move("3d3e=").is_err());
+        assert!(board.parse_usi_move("p*5e").is_err());
+        assert!(board.parse_usi_move("5a4b+").is_err());
+        assert_eq!(board.usi_move(&Action::Toryo), None);
+    }
+}
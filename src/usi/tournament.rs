@@ -0,0 +1,521 @@
+use std::io::{BufRead, Write};
+use std::process::Command;
+use std::time::{Duration, Instant};
+
+use super::bridge::{evaluation, go_params};
+use super::{UsiEngine, UsiError, STARTPOS_SFEN};
+use crate::board::Board;
+use crate::clock::TimeControl;
+use crate::protocol::{GameOutcome, GameSummary, ProtocolError};
+use crate::server::game::Game;
+use crate::value::{Action, Color, GameRecord, Position, TimeLimit};
+
+/// Settings of [`run_match`].
+#[derive(Debug, PartialEq, Clone)]
+pub struct MatchConfig {
+    /// Number of games, where the engines swap colors after each game.
+    pub games: u32,
+    pub time_limit: TimeLimit,
+    /// Number of moves after which a game ends in a draw.
+    pub max_moves: Option<u32>,
+    /// Starting positions, each played twice in a row with colors swapped.
+    ///
+    /// The even position is used when empty.
+    pub start_positions: Vec<Position>,
+    /// Time kept back from the engines' byoyomi, or from their main time without byoyomi.
+    pub byoyomi_margin: Duration,
+    /// Byoyomi given to the engines when `time_limit` is unlimited.
+    pub unlimited_byoyomi: Duration,
+    /// Time given to an engine to answer `stop` once its time is up, after which it is killed.
+    pub stop_timeout: Duration,
+    /// Stops the match early once the test is decided.
+    pub sprt: Option<Sprt>,
+}
+
+impl Default for MatchConfig {
+    fn default() -> Self {
+        MatchConfig {
+            games: 2,
+            time_limit: TimeLimit {
+                main_time: Duration::ZERO,
+                byoyomi: Duration::from_secs(1),
+                increment: Duration::ZERO,
+            },
+            max_moves: Some(256),
+            start_positions: Vec::new(),
+            byoyomi_margin: Duration::from_millis(100),
+            unlimited_byoyomi: Duration::from_secs(10),
+            stop_timeout: Duration::from_secs(1),
+            sprt: None,
+        }
+    }
+}
+
+/// The games of a match and the score of the first engine.
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct MatchResult {
+    pub records: Vec<GameRecord>,
+    pub summary: MatchSummary,
+}
+
+/// Wins, draws and losses of the first engine against the second.
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Default)]
+pub struct MatchSummary {
+    pub wins: u32,
+    pub draws: u32,
+    pub losses: u32,
+}
+
+impl MatchSummary {
+    pub fn games(&self) -> u32 {
+        self.wins + self.draws + self.losses
+    }
+
+    /// Returns the mean score, counting a draw as half a win.
+    pub fn score(&self) -> Option<f64> {
+        let games = self.games();
+        (games > 0).then(|| (self.wins as f64 + self.draws as f64 / 2.0) / games as f64)
+    }
+
+    /// Returns the Elo difference implied by the score, or `None` without a finite estimate.
+    pub fn elo(&self) -> Option<f64> {
+        self.score().and_then(elo_from_score)
+    }
+
+    /// Returns the half-width of the 95% confidence interval of [`MatchSummary::elo`].
+    pub fn elo_error(&self) -> Option<f64> {
+        let score = self.score()?;
+        let margin = 1.959964 * (self.variance()? / self.games() as f64).sqrt();
+        let high = elo_from_score(score + margin)?;
+        let low = elo_from_score(score - margin)?;
+        Some((high - low) / 2.0)
+    }
+
+    /// Returns the log-likelihood ratio of `elo1` against `elo0` by the normal approximation
+    /// of the trinomial model.
+    pub fn llr(&self, elo0: f64, elo1: f64) -> f64 {
+        let (Some(score), Some(variance)) = (self.score(), self.variance()) else {
+            return 0.0;
+        };
+        if variance == 0.0 {
+            return 0.0;
+        }
+
+        let s0 = score_from_elo(elo0);
+        let s1 = score_from_elo(elo1);
+        (s1 - s0) * (2.0 * score - s0 - s1) / (2.0 * variance / self.games() as f64)
+    }
+
+    /// Returns the per-game variance of the score.
+    fn variance(&self) -> Option<f64> {
+        let score = self.score()?;
+        let sum = self.wins as f64 * (1.0 - score).powi(2)
+            + self.draws as f64 * (0.5 - score).powi(2)
+            + self.losses as f64 * score.powi(2);
+        Some(sum / self.games() as f64)
+    }
+
+    fn add(&mut self, outcome: GameOutcome) {
+        match outcome {
+            GameOutcome::Win => self.wins += 1,
+            GameOutcome::Lose => self.losses += 1,
+            GameOutcome::Draw | GameOutcome::Censored => self.draws += 1,
+        }
+    }
+}
+
+/// A sequential probability ratio test of `elo1` against `elo0`.
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub struct Sprt {
+    pub elo0: f64,
+    pub elo1: f64,
+    /// Probability of accepting `elo1` when `elo0` holds.
+    pub alpha: f64,
+    /// Probability of accepting `elo0` when `elo1` holds.
+    pub beta: f64,
+}
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum SprtDecision {
+    AcceptH0,
+    AcceptH1,
+    Continue,
+}
+
+impl Sprt {
+    /// Returns the lower and upper bounds of the log-likelihood ratio.
+    pub fn bounds(&self) -> (f64, f64) {
+        (
+            (self.beta / (1.0 - self.alpha)).ln(),
+            ((1.0 - self.beta) / self.alpha).ln(),
+        )
+    }
+
+    pub fn decide(&self, summary: &MatchSummary) -> SprtDecision {
+        let llr = summary.llr(self.elo0, self.elo1);
+        let (lower, upper) = self.bounds();
+        if llr >= upper {
+            SprtDecision::AcceptH1
+        } else if llr <= lower {
+            SprtDecision::AcceptH0
+        } else {
+            SprtDecision::Continue
+        }
+    }
+}
+
+fn elo_from_score(score: f64) -> Option<f64> {
+    (score > 0.0 && score < 1.0).then(|| -400.0 * (1.0 / score - 1.0).log10())
+}
+
+fn score_from_elo(elo: f64) -> f64 {
+    1.0 / (1.0 + 10f64.powf(-elo / 400.0))
+}
+
+////////////////////////////////////////////////////////////////////////////////
+
+/// Starts two engines as local processes, plays a match with [`run_match`] and quits them.
+pub fn run_local_match(
+    commands: [&mut Command; 2],
+    config: &MatchConfig,
+) -> Result<MatchResult, UsiError> {
+    let [first, second] = commands;
+    let mut first = UsiEngine::spawn(first)?;
+    let mut second = UsiEngine::spawn(second)?;
+
+    let result = run_match([&mut first, &mut second], config)?;
+    first.quit()?;
+    second.quit()?;
+    Ok(result)
+}
+
+/// Plays a match between two engines which have answered `usi`, with the first engine
+/// playing Black in even games.
+///
+/// Games are adjudicated by the rules of the CSA server: checkmate, sennichite, the
+/// maximum number of moves and declarations. An engine which exceeds its time is sent
+/// `stop` and loses. If it does not answer within [`MatchConfig::stop_timeout`], it is
+/// killed and the match ends after that game.
+pub fn run_match<R: BufRead + Send + 'static, W: Write>(
+    engines: [&mut UsiEngine<R, W>; 2],
+    config: &MatchConfig,
+) -> Result<MatchResult, UsiError> {
+    let [first, second] = engines;
+    let names = [
+        first.name().unwrap_or("engine1").to_string(),
+        second.name().unwrap_or("engine2").to_string(),
+    ];
+    let default_positions = [Position::default()];
+    let positions = if config.start_positions.is_empty() {
+        &default_positions[..]
+    } else {
+        &config.start_positions[..]
+    };
+
+    let mut result = MatchResult {
+        records: Vec::new(),
+        summary: MatchSummary::default(),
+    };
+    for index in 0..config.games {
+        let position = &positions[index as usize / 2 % positions.len()];
+        let swapped = index % 2 == 1;
+        let (black, white) = if swapped {
+            (&mut *second, &mut *first)
+        } else {
+            (&mut *first, &mut *second)
+        };
+        let (black_name, white_name) = if swapped {
+            (&names[1], &names[0])
+        } else {
+            (&names[0], &names[1])
+        };
+
+        let summary = GameSummary {
+            max_moves: config.max_moves,
+            time: Some(TimeControl::from(&config.time_limit)),
+            ..GameSummary::new(
+                &format!("{black_name}+{white_name}+{}", index + 1),
+                black_name,
+                white_name,
+                position.clone(),
+            )
+        };
+        let (record, killed) = play_game([black, white], &summary, config)?;
+
+        let winner = record.result().and_then(|result| result.winner);
+        let first_color = if swapped { Color::White } else { Color::Black };
+        result.summary.add(match winner {
+            Some(color) if color == first_color => GameOutcome::Win,
+            Some(_) => GameOutcome::Lose,
+            None => GameOutcome::Draw,
+        });
+        result.records.push(record);
+
+        if killed {
+            break;
+        }
+        if let Some(ref sprt) = config.sprt {
+            if sprt.decide(&result.summary) != SprtDecision::Continue {
+                break;
+            }
+        }
+    }
+
+    Ok(result)
+}
+
+/// Plays a game between the engines for Black and White.
+///
+/// Returns the record and whether an engine was killed for not answering `stop`.
+fn play_game<R: BufRead + Send + 'static, W: Write>(
+    mut engines: [&mut UsiEngine<R, W>; 2],
+    summary: &GameSummary,
+    config: &MatchConfig,
+) -> Result<(GameRecord, bool), UsiError> {
+    let mut game =
+        Game::new(summary, TimeControl::from(&config.time_limit)).map_err(ProtocolError::from)?;
+    let sfen = Board::from_position(&summary.position).to_sfen(1);
+    let start_sfen = (sfen != STARTPOS_SFEN).then_some(sfen);
+    let mut moves = Vec::new();
+    let mut killed = None;
+
+    for engine in engines.iter_mut() {
+        engine.is_ready()?;
+        engine.new_game()?;
+    }
+
+    let ending = loop {
+        let color = game.side_to_move();
+        let engine = &mut engines[color as usize];
+        engine.set_position(start_sfen.as_deref(), &moves)?;
+        engine.go(&go_params(
+            Some(game.clock()),
+            color,
+            config.byoyomi_margin,
+            config.unlimited_byoyomi,
+        ))?;
+        let started = Instant::now();
+        let best = match game.time_available() {
+            Some(available) => engine.wait_bestmove_timeout(available)?,
+            None => Some(engine.wait_bestmove()?),
+        };
+        let Some(best) = best else {
+            engine.stop()?;
+            if engine.wait_bestmove_timeout(config.stop_timeout)?.is_none() {
+                engine.kill()?;
+                killed = Some(color);
+            }
+            break game.time_up();
+        };
+        let elapsed = started.elapsed();
+
+        let action = match best.best.as_str() {
+            "resign" => Ok(Action::Toryo),
+            "win" => Ok(Action::Kachi),
+            usi => game.board().parse_usi_move(usi),
+        };
+        let Ok(action) = action else {
+            break game.illegal();
+        };
+        let usi = game.board().usi_move(&action);
+        let evaluation = best.info.and_then(|info| evaluation(game.board(), &info));
+
+        let (line, ending) = game.play(action, evaluation.as_ref(), elapsed);
+        if let (Some(_), Some(usi)) = (line, usi) {
+            moves.push(usi);
+        }
+        if let Some(ending) = ending {
+            break ending;
+        }
+    };
+
+    for color in [Color::Black, Color::White] {
+        if killed == Some(color) {
+            continue;
+        }
+        let outcome = match ending.winner {
+            Some(winner) if winner == color => GameOutcome::Win,
+            Some(_) => GameOutcome::Lose,
+            None => GameOutcome::Draw,
+        };
+        engines[color as usize].game_over(outcome)?;
+    }
+
+    Ok((game.record().clone(), killed.is_some()))
+}
+
+////////////////////////////////////////////////////////////////////////////////
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::value::EndReason;
+    use std::io::{BufReader, Cursor};
+
+    #[test]
+    fn summary() {
+        let summary = MatchSummary {
+            wins: 60,
+            draws: 20,
+            losses: 20,
+        };
+        assert_eq!(summary.score(), Some(0.7));
+        assert!((summary.elo().unwrap() - 147.19).abs() < 0.01);
+        assert!((summary.elo_error().unwrap() - 66.0).abs() < 0.1);
+        assert_eq!(MatchSummary::default().elo(), None);
+        assert_eq!(
+            MatchSummary {
+                wins: 3,
+                ..MatchSummary::default()
+            }
+            .elo(),
+            None
+        );
+
+        let sprt = Sprt {
+            elo0: 0.0,
+            elo1: 10.0,
+            alpha: 0.05,
+            beta: 0.05,
+        };
+        let (lower, upper) = sprt.bounds();
+        assert!((lower + 2.944).abs() < 0.001);
+        assert!((upper - 2.944).abs() < 0.001);
+        assert!((summary.llr(0.0, 10.0) - 1.734).abs() < 0.001);
+        assert_eq!(sprt.decide(&summary), SprtDecision::Continue);
+
+        let summary = MatchSummary {
+            wins: 600,
+            draws: 200,
+            losses: 200,
+        };
+        assert_eq!(sprt.decide(&summary), SprtDecision::AcceptH1);
+        assert_eq!(
+            Sprt {
+                elo0: 200.0,
+                elo1: 210.0,
+                ..sprt
+            }
+            .decide(&summary),
+            SprtDecision::AcceptH0
+        );
+        assert_eq!(
+            sprt.decide(&MatchSummary {
+                wins: 5,
+                draws: 2,
+                losses: 5,
+            }),
+            SprtDecision::Continue
+        );
+    }
+
+    #[test]
+    fn match_games() {
+        let mut first_input = Vec::new();
+        let mut second_input = Vec::new();
+        let mut first = UsiEngine::new(
+            Cursor::new(
+                "readyok\nbestmove 7g7f\nreadyok\ninfo score cp -300 pv 3c3d\nbestmove resign\n",
+            ),
+            &mut first_input,
+        );
+        let mut second = UsiEngine::new(
+            Cursor::new("readyok\nbestmove 5a5i\nreadyok\nbestmove 2g2f\n"),
+            &mut second_input,
+        );
+
+        let config = MatchConfig {
+            games: 2,
+            ..MatchConfig::default()
+        };
+        let result = run_match([&mut first, &mut second], &config).unwrap();
+        drop((first, second));
+
+        assert_eq!(
+            result.summary,
+            MatchSummary {
+                wins: 1,
+                draws: 0,
+                losses: 1,
+            }
+        );
+
+        let [game1, game2] = &result.records[..] else {
+            panic!("expected two games");
+        };
+        assert_eq!(game1.black_player.as_deref(), Some("engine1"));
+        assert_eq!(game1.moves.len(), 2);
+        assert_eq!(
+            game1.result().map(|r| (r.winner, r.reason)),
+            Some((Some(Color::Black), EndReason::IllegalAction))
+        );
+        assert_eq!(game2.black_player.as_deref(), Some("engine2"));
+        assert_eq!(game2.moves[1].action, Action::Toryo);
+        assert_eq!(game2.moves[1].comments, ["** 300"]);
+        assert_eq!(game2.result().map(|r| r.winner), Some(Some(Color::Black)));
+
+        assert_eq!(
+            String::from_utf8(first_input).unwrap(),
+            "\
+isready
+usinewgame
+position startpos
+go btime 0 wtime 0 byoyomi 900
+gameover win
+isready
+usinewgame
+position startpos moves 2g2f
+go btime 0 wtime 0 byoyomi 900
+gameover lose
+"
+        );
+    }
+
+    #[test]
+    fn time_up() {
+        let (first_output, mut first_stdout) = std::io::pipe().unwrap();
+        let (second_output, mut second_stdout) = std::io::pipe().unwrap();
+        first_stdout.write_all(b"readyok\n").unwrap();
+        second_stdout.write_all(b"readyok\n").unwrap();
+
+        let mut first_input = Vec::new();
+        let mut second_input = Vec::new();
+        let mut first = UsiEngine::new(BufReader::new(first_output), &mut first_input);
+        let mut second = UsiEngine::new(BufReader::new(second_output), &mut second_input);
+
+        let config = MatchConfig {
+            games: 2,
+            time_limit: TimeLimit {
+                main_time: Duration::ZERO,
+                byoyomi: Duration::from_millis(50),
+                increment: Duration::ZERO,
+            },
+            byoyomi_margin: Duration::ZERO,
+            stop_timeout: Duration::from_millis(50),
+            ..MatchConfig::default()
+        };
+        let result = run_match([&mut first, &mut second], &config).unwrap();
+        drop((first, second, first_stdout, second_stdout));
+
+        // The match ends as the first engine never answered `stop`.
+        assert_eq!(result.records.len(), 1);
+        assert_eq!(result.records[0].moves[0].action, Action::TimeUp);
+        assert_eq!(
+            result.records[0].result().map(|r| (r.winner, r.reason)),
+            Some((Some(Color::White), EndReason::TimeUp))
+        );
+        assert_eq!(
+            String::from_utf8(first_input).unwrap(),
+            "\
+isready
+usinewgame
+position startpos
+go btime 0 wtime 0 byoyomi 50
+stop
+"
+        );
+        assert!(String::from_utf8(second_input)
+            .unwrap()
+            .ends_with("gameover win\n"));
+    }
+}
@@ -0,0 +1,352 @@
+use std::cmp::Ordering;
+use std::fmt;
+
+use crate::clock::TimeControl;
+use crate::value::{Action, Color, GameRecord, PieceType, Time};
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy, PartialOrd, Ord)]
+pub enum Severity {
+    Warning,
+    Error,
+}
+
+/// A problem found in a `GameRecord`.
+///
+/// `move_index` is the index in `GameRecord::moves`, or `None` for the starting position.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum ValidationIssue {
+    /// `AL` used outside `P+00AL` or `P-00AL`.
+    MisplacedAll { move_index: Option<usize> },
+    /// A move played by the side not to move.
+    OutOfTurn { move_index: usize, expected: Color },
+    /// A move whose `T` makes the side run out of the time limit.
+    TimeExceeded { move_index: usize, color: Color },
+    /// A move recorded after a special move ending the game.
+    MoveAfterEnd { move_index: usize },
+    /// `$END_TIME` earlier than `$START_TIME`.
+    EndBeforeStart,
+}
+
+impl ValidationIssue {
+    pub fn severity(&self) -> Severity {
+        match *self {
+            ValidationIssue::TimeExceeded { .. } | ValidationIssue::EndBeforeStart => {
+                Severity::Warning
+            }
+            _ => Severity::Error,
+        }
+    }
+}
+
+impl fmt::Display for ValidationIssue {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let location = |move_index: Option<usize>| match move_index {
+            Some(i) => format!("move {}", i + 1),
+            None => "the starting position".to_string(),
+        };
+
+        match *self {
+            ValidationIssue::MisplacedAll { move_index } => {
+                write!(f, "misplaced AL in {}", location(move_index))
+            }
+            ValidationIssue::OutOfTurn {
+                move_index,
+                expected,
+            } => write!(
+                f,
+                "move {} is expected to be played by {expected}",
+                move_index + 1
+            ),
+            ValidationIssue::TimeExceeded { move_index, color } => write!(
+                f,
+                "{color} exceeds the time limit at move {}",
+                move_index + 1
+            ),
+            ValidationIssue::MoveAfterEnd { move_index } => {
+                write!(
+                    f,
+                    "move {} is recorded after the game ended",
+                    move_index + 1
+                )
+            }
+            ValidationIssue::EndBeforeStart => write!(f, "END_TIME is earlier than START_TIME"),
+        }
+    }
+}
+
+////////////////////////////////////////////////////////////////////////////////
+
+impl GameRecord {
+    /// Checks the record for inconsistencies which the parser accepts.
+    ///
+    /// Squares outside 1..=9 are rejected by the parser with
+    /// [`CsaError::InvalidSquare`](crate::CsaError::InvalidSquare).
+    pub fn validate(&self) -> Vec<ValidationIssue> {
+        let mut issues = Vec::new();
+
+        self.validate_position(&mut issues);
+        self.validate_moves(&mut issues);
+
+        if let Some(ref time_limit) = self.time_limit {
+            if let Some(time_up) = TimeControl::from(time_limit).replay(self).time_up {
+                issues.push(ValidationIssue::TimeExceeded {
+                    move_index: time_up.move_index,
+                    color: time_up.color,
+                });
+            }
+        }
+
+        if let (Some(ref start), Some(ref end)) = (&self.start_time, &self.end_time) {
+            if compare_times(start, end) == Ordering::Greater {
+                issues.push(ValidationIssue::EndBeforeStart);
+            }
+        }
+
+        issues
+    }
+
+    fn validate_position(&self, issues: &mut Vec<ValidationIssue>) {
+        for &(_, pt) in &self.start_pos.drop_pieces {
+            if pt == PieceType::All {
+                issues.push(ValidationIssue::MisplacedAll { move_index: None });
+            }
+        }
+
+        for &(_, sq, pt) in &self.start_pos.add_pieces {
+            if pt == PieceType::All && sq.is_some() {
+                issues.push(ValidationIssue::MisplacedAll { move_index: None });
+            }
+        }
+    }
+
+    fn validate_moves(&self, issues: &mut Vec<ValidationIssue>) {
+        let mut side_to_move = self.start_pos.side_to_move;
+        let mut ended = false;
+
+        for (i, record) in self.moves.iter().enumerate() {
+            if ended {
+                issues.push(ValidationIssue::MoveAfterEnd { move_index: i });
+            }
+
+            match record.action {
+                Action::Move(color, _, _, pt) => {
+                    if color != side_to_move {
+                        issues.push(ValidationIssue::OutOfTurn {
+                            move_index: i,
+                            expected: side_to_move,
+                        });
+                    }
+                    side_to_move = color.flip();
+
+                    if pt == PieceType::All {
+                        issues.push(ValidationIssue::MisplacedAll {
+                            move_index: Some(i),
+                        });
+                    }
+                }
+                action if action.is_terminal() => ended = true,
+                _ => {}
+            }
+        }
+    }
+}
+
+/// Compares two times, ignoring the time of the day unless both have it.
+fn compare_times(a: &Time, b: &Time) -> Ordering {
+    match (a.time, b.time) {
+        (Some(_), Some(_)) => match (a.to_offset_date_time(), b.to_offset_date_time()) {
+            (Some(a), Some(b)) => a.cmp(&b),
+            _ => (a.date, a.time).cmp(&(b.date, b.time)),
+        },
+        _ => a.date.cmp(&b.date),
+    }
+}
+
+////////////////////////////////////////////////////////////////////////////////
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::time::Duration;
+
+    use crate::parser::CsaError;
+    use crate::value::{MoveFrom, MoveRecord, Square, TimeLimit};
+
+    fn pawn_move(color: Color, from: (u8, u8), to: (u8, u8), time: u64) -> MoveRecord {
+        MoveRecord {
+            action: Action::Move(
+                color,
+                MoveFrom::Board(Square::new(from.0, from.1).unwrap()),
+                Square::new(to.0, to.1).unwrap(),
+                PieceType::Pawn,
+            ),
+            time: Some(Duration::from_secs(time)),
+            comments: vec![],
+        }
+    }
+
+    #[test]
+    fn valid_record() {
+        let csa = "\
+V2.2
+$START_TIME:2003/05/03 10:30:00
+$END_TIME:2003/05/03 11:11:05
+$TIME_LIMIT:00:25+00
+PI
+P+00FU
+P-00AL
++
++2726FU
+T12
+-3334FU
+T6
++0055FU
+%TORYO
+";
+        let g = crate::parse_csa(csa).unwrap();
+        assert_eq!(g.validate(), vec![]);
+    }
+
+    #[test]
+    fn invalid_squares() {
+        // Squares outside 1..=9 cannot be represented, so the parser reports them instead.
+        let invalid = |square: &str, line: &str| CsaError::InvalidSquare {
+            square: square.to_string(),
+            line: line.to_string(),
+        };
+        assert_eq!(
+            crate::parse_csa("PI00FU\n+\n"),
+            Err(invalid("00", "PI00FU"))
+        );
+        assert_eq!(
+            crate::parse_csa("PI\nP+09KI\n+\n"),
+            Err(invalid("09", "P+09KI"))
+        );
+        assert_eq!(
+            crate::parse_csa("PI\n+\n+2726FU\n-0900FU\n"),
+            Err(invalid("09", "-0900FU"))
+        );
+        assert_eq!(
+            crate::parse_csa("PI\n+\n+0010FU\n"),
+            Err(invalid("10", "+0010FU"))
+        );
+    }
+
+    #[test]
+    fn misplaced_all() {
+        let csa = "\
+PI55AL
+P-00AL
+P+55AL
++
+";
+        let g = crate::parse_csa(csa).unwrap();
+        assert_eq!(
+            g.validate(),
+            vec![
+                ValidationIssue::MisplacedAll { move_index: None },
+                ValidationIssue::MisplacedAll { move_index: None },
+            ]
+        );
+        assert!(g
+            .validate()
+            .iter()
+            .all(|issue| issue.severity() == Severity::Error));
+    }
+
+    #[test]
+    fn moves() {
+        let mut g = GameRecord::default();
+        g.moves.push(pawn_move(Color::Black, (7, 7), (7, 6), 1));
+        g.moves.push(pawn_move(Color::Black, (2, 7), (2, 6), 1));
+        g.moves.push(pawn_move(Color::White, (3, 3), (3, 4), 1));
+        g.moves.push(MoveRecord {
+            action: Action::Toryo,
+            time: None,
+            comments: vec![],
+        });
+        g.moves.push(pawn_move(Color::Black, (2, 6), (2, 5), 1));
+        g.moves.push(MoveRecord {
+            action: Action::Move(
+                Color::White,
+                MoveFrom::Hand,
+                Square::new(5, 5).unwrap(),
+                PieceType::All,
+            ),
+            time: None,
+            comments: vec![],
+        });
+
+        assert_eq!(
+            g.validate(),
+            vec![
+                ValidationIssue::OutOfTurn {
+                    move_index: 1,
+                    expected: Color::White
+                },
+                ValidationIssue::MoveAfterEnd { move_index: 4 },
+                ValidationIssue::MoveAfterEnd { move_index: 5 },
+                ValidationIssue::MisplacedAll {
+                    move_index: Some(5)
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn time_exceeded() {
+        let mut g = GameRecord {
+            time_limit: Some(TimeLimit {
+                main_time: Duration::from_secs(60),
+                byoyomi: Duration::from_secs(10),
+                increment: Duration::ZERO,
+            }),
+            ..GameRecord::default()
+        };
+        g.moves.push(pawn_move(Color::Black, (7, 7), (7, 6), 30));
+        g.moves.push(pawn_move(Color::White, (3, 3), (3, 4), 65));
+        g.moves.push(pawn_move(Color::Black, (2, 7), (2, 6), 35));
+        g.moves.push(pawn_move(Color::White, (3, 4), (3, 5), 10));
+
+        let issues = g.validate();
+        assert_eq!(
+            issues,
+            vec![ValidationIssue::TimeExceeded {
+                move_index: 3,
+                color: Color::White
+            }]
+        );
+        assert_eq!(issues[0].severity(), Severity::Warning);
+    }
+
+    #[test]
+    fn end_before_start() {
+        let csa = "\
+$START_TIME:2003/05/03 10:30:00
+$END_TIME:2003/05/03 09:11:05
+PI
++
+";
+        let g = crate::parse_csa(csa).unwrap();
+        assert_eq!(g.validate(), vec![ValidationIssue::EndBeforeStart]);
+
+        let csa = "\
+$START_TIME:2003/05/03 10:30:00+09:00
+$END_TIME:2003/05/03 02:11:05+00:00
+PI
++
+";
+        let g = crate::parse_csa(csa).unwrap();
+        assert_eq!(g.validate(), vec![]);
+
+        let csa = "\
+$START_TIME:2003/05/03 10:30:00
+$END_TIME:2003/05/03
+PI
++
+";
+        let g = crate::parse_csa(csa).unwrap();
+        assert_eq!(g.validate(), vec![]);
+    }
+}
@@ -1,8 +1,11 @@
 use std::fmt;
 use std::time::Duration;
-use time::{Date as NativeDate, Time as NativeTime};
+use time::{Date as NativeDate, OffsetDateTime, PrimitiveDateTime, Time as NativeTime, UtcOffset};
 
-#[derive(Default, Debug, PartialEq, Eq)]
+use crate::parser::{parse_evaluation, CsaError};
+use crate::variation::Variation;
+
+#[derive(Default, Debug, PartialEq, Eq, Clone)]
 pub struct GameRecord {
     pub black_player: Option<String>,
     pub white_player: Option<String>,
@@ -13,12 +16,58 @@ pub struct GameRecord {
     pub time_limit: Option<TimeLimit>,
     pub opening: Option<String>,
     pub start_pos: Position,
+    /// The mainline.
     pub moves: Vec<MoveRecord>,
+    /// Alternative lines branching off from the mainline. Not written in CSA, but kept in KIF.
+    pub variations: Vec<Variation>,
 }
 
 impl fmt::Display for GameRecord {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        writeln!(f, "V2.2")?;
+        self.write_csa(f, TimeFormat::Plain)
+    }
+}
+
+/// How `GameRecord::write_csa` writes times.
+#[derive(Debug, Clone, Copy)]
+enum TimeFormat {
+    /// As they are, without the offset.
+    Plain,
+    /// Converted to the offset, without it.
+    Convert(UtcOffset),
+    /// As they are, with the offset as a suffix.
+    Suffix,
+}
+
+impl GameRecord {
+    /// Returns a value which formats the record with `$START_TIME` and `$END_TIME` in the given offset.
+    ///
+    /// Times carrying an offset are converted and written without the offset suffix,
+    /// as readers are expected to assume `offset`. Times without an offset are written as they are.
+    pub fn display_with_offset(&self, offset: UtcOffset) -> DisplayWithOffset<'_> {
+        DisplayWithOffset {
+            record: self,
+            offset,
+        }
+    }
+
+    /// Returns a value which formats the record with the offsets of `$START_TIME` and
+    /// `$END_TIME` written as a `+HH:MM` suffix.
+    ///
+    /// The suffix is an extension to CSA V2.2, read back by this crate but not by every reader.
+    pub fn display_with_offset_suffix(&self) -> DisplayWithOffsetSuffix<'_> {
+        DisplayWithOffsetSuffix { record: self }
+    }
+
+    fn write_csa(&self, f: &mut fmt::Formatter, time_format: TimeFormat) -> fmt::Result {
+        let format_time = |time: &Time| match time_format {
+            TimeFormat::Convert(offset) => time.to_offset(offset).to_string(),
+            TimeFormat::Suffix => time.display_with_offset_suffix().to_string(),
+            TimeFormat::Plain => time.to_string(),
+        };
+
+        let v3 = self.time_limit.as_ref().is_some_and(TimeLimit::requires_v3);
+        writeln!(f, "{}", if v3 { "V3.0" } else { "V2.2" })?;
 
         // Metadata
         let metadata = [
@@ -26,13 +75,10 @@ impl fmt::Display for GameRecord {
             ("N-", self.white_player.as_ref().map(|x| x.to_string())),
             ("$EVENT:", self.event.as_ref().map(|x| x.to_string())),
             ("$SITE:", self.site.as_ref().map(|x| x.to_string())),
+            ("$START_TIME:", self.start_time.as_ref().map(format_time)),
+            ("$END_TIME:", self.end_time.as_ref().map(format_time)),
             (
-                "$START_TIME:",
-                self.start_time.as_ref().map(|x| x.to_string()),
-            ),
-            ("$END_TIME:", self.end_time.as_ref().map(|x| x.to_string())),
-            (
-                "$TIME_LIMIT:",
+                if v3 { "$TIME:" } else { "$TIME_LIMIT:" },
                 self.time_limit.as_ref().map(|x| x.to_string()),
             ),
             ("$OPENING:", self.opening.as_ref().map(|x| x.to_string())),
@@ -53,6 +99,79 @@ impl fmt::Display for GameRecord {
 
         Ok(())
     }
+
+    /// Returns the result of the game, determined by the first terminating special move.
+    ///
+    /// Returns `None` if the game has not ended yet.
+    pub fn result(&self) -> Option<GameResult> {
+        let mut side_to_move = self.start_pos.side_to_move;
+
+        for record in &self.moves {
+            match record.action {
+                Action::Move(color, ..) => side_to_move = color.flip(),
+                action if action.is_terminal() => {
+                    return GameResult::from_action(action, side_to_move);
+                }
+                _ => {}
+            }
+        }
+
+        None
+    }
+
+    /// Replaces the terminating special moves with the one representing the given result.
+    ///
+    /// Returns an error if no special move can express the result at the current position,
+    /// e.g. the side to move winning by resignation.
+    pub fn set_result(&mut self, result: GameResult) -> Result<(), CsaError> {
+        let end = self
+            .moves
+            .iter()
+            .position(|record| record.action.is_terminal())
+            .unwrap_or(self.moves.len());
+        let side_to_move = self.moves[..end]
+            .iter()
+            .rev()
+            .find_map(|record| match record.action {
+                Action::Move(color, ..) => Some(color.flip()),
+                _ => None,
+            })
+            .unwrap_or(self.start_pos.side_to_move);
+        let action = result
+            .to_action(side_to_move)
+            .ok_or(CsaError::InvalidResult())?;
+
+        self.moves.truncate(end);
+        self.moves.push(MoveRecord {
+            action,
+            time: None,
+            comments: vec![],
+        });
+        self.variations.retain(|variation| variation.start <= end);
+
+        Ok(())
+    }
+}
+
+pub struct DisplayWithOffset<'a> {
+    record: &'a GameRecord,
+    offset: UtcOffset,
+}
+
+impl fmt::Display for DisplayWithOffset<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        self.record.write_csa(f, TimeFormat::Convert(self.offset))
+    }
+}
+
+pub struct DisplayWithOffsetSuffix<'a> {
+    record: &'a GameRecord,
+}
+
+impl fmt::Display for DisplayWithOffsetSuffix<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        self.record.write_csa(f, TimeFormat::Suffix)
+    }
 }
 
 ////////////////////////////////////////////////////////////////////////////////
@@ -62,15 +181,56 @@ impl fmt::Display for GameRecord {
 pub struct Time {
     pub date: NativeDate,
     pub time: Option<NativeTime>,
+    /// Offset from UTC, `None` if the time is in an unspecified local time.
+    pub offset: Option<UtcOffset>,
 }
 
 impl Time {
     pub fn now() -> Self {
-        let now = time::OffsetDateTime::now_utc();
+        Time::from(OffsetDateTime::now_utc())
+    }
 
+    pub fn now_with_offset(offset: UtcOffset) -> Self {
+        Time::from(OffsetDateTime::now_utc().to_offset(offset))
+    }
+
+    /// Converts into `OffsetDateTime`, returning `None` if the offset is unknown.
+    ///
+    /// Midnight is assumed if the time of the day is absent.
+    pub fn to_offset_date_time(&self) -> Option<OffsetDateTime> {
+        self.offset.map(|offset| self.assume_offset(offset))
+    }
+
+    /// Converts into `OffsetDateTime`, interpreting the time in `offset` if its own offset is unknown.
+    pub fn assume_offset(&self, offset: UtcOffset) -> OffsetDateTime {
+        PrimitiveDateTime::new(self.date, self.time.unwrap_or(NativeTime::MIDNIGHT))
+            .assume_offset(self.offset.unwrap_or(offset))
+    }
+
+    /// Returns the same instant expressed in `offset`.
+    ///
+    /// Times without an offset or without the time of the day are returned as they are,
+    /// since the instant they represent is unknown.
+    pub fn to_offset(&self, offset: UtcOffset) -> Time {
+        match (self.time, self.to_offset_date_time()) {
+            (Some(_), Some(datetime)) => Time::from(datetime.to_offset(offset)),
+            _ => self.clone(),
+        }
+    }
+
+    /// Returns a value which formats the time followed by its offset, e.g.
+    /// `2003/05/03 10:30:00+09:00`, an extension to the form of CSA V2.2.
+    pub fn display_with_offset_suffix(&self) -> TimeWithOffsetSuffix<'_> {
+        TimeWithOffsetSuffix { time: self }
+    }
+}
+
+impl From<OffsetDateTime> for Time {
+    fn from(datetime: OffsetDateTime) -> Self {
         Time {
-            date: now.date(),
-            time: Some(now.time()),
+            date: datetime.date(),
+            time: Some(datetime.time()),
+            offset: Some(datetime.offset()),
         }
     }
 }
@@ -98,30 +258,89 @@ impl fmt::Display for Time {
     }
 }
 
+pub struct TimeWithOffsetSuffix<'a> {
+    time: &'a Time,
+}
+
+impl fmt::Display for TimeWithOffsetSuffix<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.time)?;
+        if let (Some(_), Some(offset)) = (self.time.time, self.time.offset) {
+            write!(
+                f,
+                "{}{:02}:{:02}",
+                if offset.is_negative() { '-' } else { '+' },
+                offset.whole_hours().abs(),
+                offset.minutes_past_hour().abs()
+            )?;
+        }
+
+        Ok(())
+    }
+}
+
 ////////////////////////////////////////////////////////////////////////////////
 
 #[derive(Debug, PartialEq, Eq, Clone)]
 pub struct TimeLimit {
     pub main_time: Duration,
     pub byoyomi: Duration,
+    pub increment: Duration,
+}
+
+impl TimeLimit {
+    /// Returns `true` if the time limit cannot be expressed in the `$TIME_LIMIT` form
+    /// and has to be written as `$TIME` introduced in CSA V3.0, under a `V3.0` header.
+    ///
+    /// `$TIME_LIMIT` only has whole minutes of main time and whole seconds of byoyomi.
+    pub fn requires_v3(&self) -> bool {
+        !self.increment.is_zero()
+            || self.main_time.subsec_nanos() != 0
+            || !self.main_time.as_secs().is_multiple_of(60)
+            || self.byoyomi.subsec_nanos() != 0
+    }
 }
 
 impl fmt::Display for TimeLimit {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        let secs = self.main_time.as_secs();
-        let hours = secs / 3600;
-        let minutes = (secs % 3600) / 60;
+        if self.requires_v3() {
+            return write!(
+                f,
+                "{}+{}+{}",
+                Seconds(self.main_time),
+                Seconds(self.byoyomi),
+                Seconds(self.increment)
+            );
+        }
 
+        let minutes = self.main_time.as_secs() / 60;
         write!(
             f,
             "{:02}:{:02}+{:02}",
-            hours,
-            minutes,
+            minutes / 60,
+            minutes % 60,
             self.byoyomi.as_secs()
         )
     }
 }
 
+/// Formats a duration in seconds with as many fractional digits as needed.
+struct Seconds(Duration);
+
+impl fmt::Display for Seconds {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.0.as_secs())?;
+
+        let nanos = self.0.subsec_nanos();
+        if nanos > 0 {
+            let fraction = format!("{nanos:09}");
+            write!(f, ".{}", fraction.trim_end_matches('0'))?;
+        }
+
+        Ok(())
+    }
+}
+
 ////////////////////////////////////////////////////////////////////////////////
 
 #[derive(Debug, PartialEq, Eq)]
@@ -143,13 +362,23 @@ impl fmt::Display for GameAttribute {
 
 ////////////////////////////////////////////////////////////////////////////////
 
-#[derive(Debug, PartialEq, Eq, Clone, Copy, Default)]
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Default, Hash)]
 pub enum Color {
     #[default]
     Black,
     White,
 }
 
+impl Color {
+    /// Returns the opponent's color.
+    pub fn flip(self) -> Color {
+        match self {
+            Color::Black => Color::White,
+            Color::White => Color::Black,
+        }
+    }
+}
+
 impl fmt::Display for Color {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         match *self {
@@ -161,15 +390,94 @@ impl fmt::Display for Color {
 
 ////////////////////////////////////////////////////////////////////////////////
 
-#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+/// A square on the board, with both file and rank in 1..=9.
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Hash, PartialOrd, Ord)]
 pub struct Square {
-    pub file: u8,
-    pub rank: u8,
+    file: u8,
+    rank: u8,
 }
 
 impl Square {
-    pub fn new(file: u8, rank: u8) -> Square {
-        Square { file, rank }
+    /// Creates a square, returning `None` if `file` or `rank` is outside 1..=9.
+    pub fn new(file: u8, rank: u8) -> Option<Square> {
+        if (1..=9).contains(&file) && (1..=9).contains(&rank) {
+            Some(Square { file, rank })
+        } else {
+            None
+        }
+    }
+
+    /// Creates a square from its index in 0..81, ordered by file and then by rank.
+    pub fn from_index(index: usize) -> Option<Square> {
+        if index < 81 {
+            Some(Square {
+                file: (index / 9) as u8 + 1,
+                rank: (index % 9) as u8 + 1,
+            })
+        } else {
+            None
+        }
+    }
+
+    /// Returns an iterator over all squares in the order of their indices.
+    pub fn iter() -> impl Iterator<Item = Square> {
+        (0..81).filter_map(Square::from_index)
+    }
+
+    pub fn file(self) -> u8 {
+        self.file
+    }
+
+    pub fn rank(self) -> u8 {
+        self.rank
+    }
+
+    pub fn index(self) -> usize {
+        (self.file as usize - 1) * 9 + (self.rank as usize - 1)
+    }
+
+    /// Returns the square as seen from the opponent, rotating the board by 180 degrees.
+    pub fn flip(self) -> Square {
+        Square {
+            file: 10 - self.file,
+            rank: 10 - self.rank,
+        }
+    }
+
+    /// Returns the rank counted from the far side of the given color, i.e. 1 for the opponent's back rank.
+    pub fn relative_rank(self, color: Color) -> u8 {
+        match color {
+            Color::Black => self.rank,
+            Color::White => 10 - self.rank,
+        }
+    }
+
+    /// Returns `true` if the square is in the promotion zone of the given color.
+    pub fn in_promotion_zone(self, color: Color) -> bool {
+        self.relative_rank(color) <= 3
+    }
+
+    /// Returns the square moved by the given offsets, or `None` if it goes off the board.
+    pub fn shift(self, file: i8, rank: i8) -> Option<Square> {
+        let file = self.file as i8 + file;
+        let rank = self.rank as i8 + rank;
+        if file < 1 || rank < 1 {
+            return None;
+        }
+
+        Square::new(file as u8, rank as u8)
+    }
+
+    /// Returns the number of king moves between two squares.
+    pub fn distance(self, other: Square) -> u8 {
+        self.file
+            .abs_diff(other.file)
+            .max(self.rank.abs_diff(other.rank))
+    }
+
+    /// Returns the sum of file and rank differences between two squares.
+    pub fn manhattan_distance(self, other: Square) -> u8 {
+        self.file.abs_diff(other.file) + self.rank.abs_diff(other.rank)
     }
 }
 
@@ -181,7 +489,31 @@ impl fmt::Display for Square {
 
 ////////////////////////////////////////////////////////////////////////////////
 
-#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+/// Origin of a move, written as `00` for drops in CSA.
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Hash)]
+pub enum MoveFrom {
+    Board(Square),
+    Hand,
+}
+
+impl From<Square> for MoveFrom {
+    fn from(sq: Square) -> Self {
+        MoveFrom::Board(sq)
+    }
+}
+
+impl fmt::Display for MoveFrom {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            MoveFrom::Board(ref sq) => write!(f, "{sq}"),
+            MoveFrom::Hand => write!(f, "00"),
+        }
+    }
+}
+
+////////////////////////////////////////////////////////////////////////////////
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Hash)]
 pub enum PieceType {
     Pawn,
     Lance,
@@ -200,6 +532,209 @@ pub enum PieceType {
     All,
 }
 
+const FORWARD: (i8, i8) = (0, -1);
+const BACKWARD: (i8, i8) = (0, 1);
+const LEFT: (i8, i8) = (1, 0);
+const RIGHT: (i8, i8) = (-1, 0);
+const FORWARD_LEFT: (i8, i8) = (1, -1);
+const FORWARD_RIGHT: (i8, i8) = (-1, -1);
+const BACKWARD_LEFT: (i8, i8) = (1, 1);
+const BACKWARD_RIGHT: (i8, i8) = (-1, 1);
+
+const GOLD_STEPS: &[(i8, i8)] = &[FORWARD, FORWARD_LEFT, FORWARD_RIGHT, LEFT, RIGHT, BACKWARD];
+const ORTHOGONALS: &[(i8, i8)] = &[FORWARD, BACKWARD, LEFT, RIGHT];
+const DIAGONALS: &[(i8, i8)] = &[FORWARD_LEFT, FORWARD_RIGHT, BACKWARD_LEFT, BACKWARD_RIGHT];
+
+impl PieceType {
+    /// Returns the promoted piece type, or `None` if the piece cannot promote.
+    pub fn promote(self) -> Option<PieceType> {
+        match self {
+            PieceType::Pawn => Some(PieceType::ProPawn),
+            PieceType::Lance => Some(PieceType::ProLance),
+            PieceType::Knight => Some(PieceType::ProKnight),
+            PieceType::Silver => Some(PieceType::ProSilver),
+            PieceType::Bishop => Some(PieceType::Horse),
+            PieceType::Rook => Some(PieceType::Dragon),
+            _ => None,
+        }
+    }
+
+    /// Returns the original piece type, or `None` if the piece is not promoted.
+    pub fn unpromote(self) -> Option<PieceType> {
+        match self {
+            PieceType::ProPawn => Some(PieceType::Pawn),
+            PieceType::ProLance => Some(PieceType::Lance),
+            PieceType::ProKnight => Some(PieceType::Knight),
+            PieceType::ProSilver => Some(PieceType::Silver),
+            PieceType::Horse => Some(PieceType::Bishop),
+            PieceType::Dragon => Some(PieceType::Rook),
+            _ => None,
+        }
+    }
+
+    pub fn is_promoted(self) -> bool {
+        self.unpromote().is_some()
+    }
+
+    pub fn can_promote(self) -> bool {
+        self.promote().is_some()
+    }
+
+    /// Returns the piece type held in hand after capturing this piece.
+    ///
+    /// Returns `None` for `King` and `All`, which never go to hand.
+    pub fn hand_type(self) -> Option<PieceType> {
+        match self {
+            PieceType::King | PieceType::All => None,
+            _ => Some(self.unpromote().unwrap_or(self)),
+        }
+    }
+
+    /// Returns the material value commonly used by shogi engines, in centipawns.
+    pub fn value(self) -> i32 {
+        match self {
+            PieceType::Pawn => 90,
+            PieceType::Lance => 315,
+            PieceType::Knight => 405,
+            PieceType::Silver => 495,
+            PieceType::Gold => 540,
+            PieceType::Bishop => 855,
+            PieceType::Rook => 990,
+            PieceType::ProPawn => 540,
+            PieceType::ProLance => 540,
+            PieceType::ProKnight => 540,
+            PieceType::ProSilver => 540,
+            PieceType::Horse => 945,
+            PieceType::Dragon => 1395,
+            PieceType::King | PieceType::All => 0,
+        }
+    }
+
+    /// Returns the points counted for the impasse (jishogi) rules: 5 for bishops and rooks,
+    /// 1 for the other pieces and 0 for kings.
+    pub fn impasse_points(self) -> u8 {
+        match self {
+            PieceType::Bishop | PieceType::Rook | PieceType::Horse | PieceType::Dragon => 5,
+            PieceType::King | PieceType::All => 0,
+            _ => 1,
+        }
+    }
+
+    /// Returns the `(file, rank)` offsets the piece moves by a single step, from Black's point of view.
+    ///
+    /// Negate the offsets for White.
+    pub fn steps(self) -> &'static [(i8, i8)] {
+        match self {
+            PieceType::Pawn => &[FORWARD],
+            PieceType::Knight => &[(1, -2), (-1, -2)],
+            PieceType::Silver => &[
+                FORWARD,
+                FORWARD_LEFT,
+                FORWARD_RIGHT,
+                BACKWARD_LEFT,
+                BACKWARD_RIGHT,
+            ],
+            PieceType::Gold
+            | PieceType::ProPawn
+            | PieceType::ProLance
+            | PieceType::ProKnight
+            | PieceType::ProSilver => GOLD_STEPS,
+            PieceType::King => &[
+                FORWARD,
+                FORWARD_LEFT,
+                FORWARD_RIGHT,
+                LEFT,
+                RIGHT,
+                BACKWARD,
+                BACKWARD_LEFT,
+                BACKWARD_RIGHT,
+            ],
+            PieceType::Horse => ORTHOGONALS,
+            PieceType::Dragon => DIAGONALS,
+            PieceType::Lance | PieceType::Bishop | PieceType::Rook | PieceType::All => &[],
+        }
+    }
+
+    /// Returns the directions the piece slides any number of squares along, from Black's point of view.
+    ///
+    /// Negate the offsets for White.
+    pub fn slides(self) -> &'static [(i8, i8)] {
+        match self {
+            PieceType::Lance => &[FORWARD],
+            PieceType::Bishop | PieceType::Horse => DIAGONALS,
+            PieceType::Rook | PieceType::Dragon => ORTHOGONALS,
+            _ => &[],
+        }
+    }
+
+    /// Returns the name in Japanese, e.g. `歩` or `成銀`.
+    pub fn kanji(self) -> Option<&'static str> {
+        let name = match self {
+            PieceType::Pawn => "歩",
+            PieceType::Lance => "香",
+            PieceType::Knight => "桂",
+            PieceType::Silver => "銀",
+            PieceType::Gold => "金",
+            PieceType::Bishop => "角",
+            PieceType::Rook => "飛",
+            PieceType::King => "玉",
+            PieceType::ProPawn => "と",
+            PieceType::ProLance => "成香",
+            PieceType::ProKnight => "成桂",
+            PieceType::ProSilver => "成銀",
+            PieceType::Horse => "馬",
+            PieceType::Dragon => "龍",
+            PieceType::All => return None,
+        };
+        Some(name)
+    }
+
+    /// Returns the single-character Japanese name used in board diagrams, e.g. `全` for `成銀`.
+    pub fn kanji_char(self) -> Option<char> {
+        let c = match self {
+            PieceType::ProLance => '杏',
+            PieceType::ProKnight => '圭',
+            PieceType::ProSilver => '全',
+            _ => return self.kanji().and_then(|s| s.chars().next()),
+        };
+        Some(c)
+    }
+
+    /// Returns the USI letter of the piece for Black.
+    ///
+    /// Promoted pieces share the letter of their original type and are prefixed by `+` in USI.
+    pub fn usi_char(self) -> Option<char> {
+        let c = match self.unpromote().unwrap_or(self) {
+            PieceType::Pawn => 'P',
+            PieceType::Lance => 'L',
+            PieceType::Knight => 'N',
+            PieceType::Silver => 'S',
+            PieceType::Gold => 'G',
+            PieceType::Bishop => 'B',
+            PieceType::Rook => 'R',
+            PieceType::King => 'K',
+            _ => return None,
+        };
+        Some(c)
+    }
+
+    /// Returns the unpromoted piece type of a USI letter, in either case.
+    pub fn from_usi_char(c: char) -> Option<PieceType> {
+        let pt = match c.to_ascii_uppercase() {
+            'P' => PieceType::Pawn,
+            'L' => PieceType::Lance,
+            'N' => PieceType::Knight,
+            'S' => PieceType::Silver,
+            'G' => PieceType::Gold,
+            'B' => PieceType::Bishop,
+            'R' => PieceType::Rook,
+            'K' => PieceType::King,
+            _ => return None,
+        };
+        Some(pt)
+    }
+}
+
 impl fmt::Display for PieceType {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         let pt = match *self {
@@ -225,13 +760,14 @@ impl fmt::Display for PieceType {
 
 ////////////////////////////////////////////////////////////////////////////////
 
-type Board = [[Option<(Color, PieceType)>; 9]; 9];
+pub(crate) type Grid = [[Option<(Color, PieceType)>; 9]; 9];
 
-#[derive(Debug, Default, PartialEq, Eq)]
+#[derive(Debug, Default, PartialEq, Eq, Clone)]
 pub struct Position {
     pub drop_pieces: Vec<(Square, PieceType)>,
-    pub bulk: Option<Board>,
-    pub add_pieces: Vec<(Color, Square, PieceType)>,
+    pub bulk: Option<Grid>,
+    /// Pieces placed by `P+` and `P-` lines, where `None` denotes the piece stand (`00`).
+    pub add_pieces: Vec<(Color, Option<Square>, PieceType)>,
     pub side_to_move: Color,
 }
 
@@ -259,7 +795,10 @@ impl fmt::Display for Position {
         }
 
         for (color, sq, pt) in &self.add_pieces {
-            writeln!(f, "P{color}{sq}{pt}")?;
+            match sq {
+                Some(sq) => writeln!(f, "P{color}{sq}{pt}")?,
+                None => writeln!(f, "P{color}00{pt}")?,
+            }
         }
 
         writeln!(f, "{}", self.side_to_move)?;
@@ -268,11 +807,234 @@ impl fmt::Display for Position {
     }
 }
 
+impl Position {
+    /// Creates the starting position of the given handicap, with the handicap giver (White) to move.
+    pub fn from_handicap(handicap: Handicap) -> Position {
+        Position {
+            drop_pieces: handicap.removed_pieces(),
+            bulk: None,
+            add_pieces: Vec::new(),
+            side_to_move: handicap.side_to_move(),
+        }
+    }
+
+    /// Returns the handicap preset which this position corresponds to, ignoring the side to move.
+    pub fn handicap(&self) -> Option<Handicap> {
+        Handicap::ALL
+            .iter()
+            .copied()
+            .find(|&handicap| self.is_handicap(handicap))
+    }
+
+    fn is_handicap(&self, handicap: Handicap) -> bool {
+        if !self.add_pieces.is_empty() {
+            return false;
+        }
+
+        let removed = handicap.removed_pieces();
+        match self.bulk {
+            Some(ref bulk) => {
+                let mut expected = hirate_grid();
+                for (sq, _) in &removed {
+                    expected[sq.rank() as usize - 1][9 - sq.file() as usize] = None;
+                }
+
+                self.drop_pieces.is_empty() && *bulk == expected
+            }
+            None => {
+                self.drop_pieces.len() == removed.len()
+                    && removed.iter().all(|pc| self.drop_pieces.contains(pc))
+            }
+        }
+    }
+}
+
+pub(crate) fn hirate_grid() -> Grid {
+    let back_rank = |color| {
+        [
+            PieceType::Lance,
+            PieceType::Knight,
+            PieceType::Silver,
+            PieceType::Gold,
+            PieceType::King,
+            PieceType::Gold,
+            PieceType::Silver,
+            PieceType::Knight,
+            PieceType::Lance,
+        ]
+        .map(|pt| Some((color, pt)))
+    };
+
+    let mut board: Grid = [[None; 9]; 9];
+    board[0] = back_rank(Color::White);
+    board[1][1] = Some((Color::White, PieceType::Rook));
+    board[1][7] = Some((Color::White, PieceType::Bishop));
+    board[2] = [Some((Color::White, PieceType::Pawn)); 9];
+    board[6] = [Some((Color::Black, PieceType::Pawn)); 9];
+    board[7][1] = Some((Color::Black, PieceType::Bishop));
+    board[7][7] = Some((Color::Black, PieceType::Rook));
+    board[8] = back_rank(Color::Black);
+    board
+}
+
+////////////////////////////////////////////////////////////////////////////////
+
+/// Handicap presets, named after the pieces removed from the handicap giver (White).
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Hash)]
+pub enum Handicap {
+    /// Even game (平手).
+    Even,
+    /// Left lance (香落ち).
+    Lance,
+    /// Right lance (右香落ち).
+    RightLance,
+    /// Bishop (角落ち).
+    Bishop,
+    /// Rook (飛車落ち).
+    Rook,
+    /// Rook and left lance (飛香落ち).
+    RookLance,
+    /// Rook and bishop (二枚落ち).
+    TwoPieces,
+    /// Two pieces and left lance (三枚落ち).
+    ThreePieces,
+    /// Two pieces and both lances (四枚落ち).
+    FourPieces,
+    /// Four pieces and left knight (五枚落ち).
+    FivePieces,
+    /// Four pieces and both knights (六枚落ち).
+    SixPieces,
+    /// Six pieces and left silver (七枚落ち).
+    SevenPieces,
+    /// Six pieces and both silvers (八枚落ち).
+    EightPieces,
+    /// Eight pieces and both golds (十枚落ち).
+    TenPieces,
+}
+
+impl Handicap {
+    pub const ALL: [Handicap; 14] = [
+        Handicap::Even,
+        Handicap::Lance,
+        Handicap::RightLance,
+        Handicap::Bishop,
+        Handicap::Rook,
+        Handicap::RookLance,
+        Handicap::TwoPieces,
+        Handicap::ThreePieces,
+        Handicap::FourPieces,
+        Handicap::FivePieces,
+        Handicap::SixPieces,
+        Handicap::SevenPieces,
+        Handicap::EightPieces,
+        Handicap::TenPieces,
+    ];
+
+    /// Returns the pieces removed from the even position, in the order written after `PI`.
+    pub fn removed_pieces(self) -> Vec<(Square, PieceType)> {
+        const ROOK: (u8, u8, PieceType) = (8, 2, PieceType::Rook);
+        const BISHOP: (u8, u8, PieceType) = (2, 2, PieceType::Bishop);
+        const LEFT_LANCE: (u8, u8, PieceType) = (1, 1, PieceType::Lance);
+        const RIGHT_LANCE: (u8, u8, PieceType) = (9, 1, PieceType::Lance);
+        const LEFT_KNIGHT: (u8, u8, PieceType) = (2, 1, PieceType::Knight);
+        const RIGHT_KNIGHT: (u8, u8, PieceType) = (8, 1, PieceType::Knight);
+        const LEFT_SILVER: (u8, u8, PieceType) = (3, 1, PieceType::Silver);
+        const RIGHT_SILVER: (u8, u8, PieceType) = (7, 1, PieceType::Silver);
+        const LEFT_GOLD: (u8, u8, PieceType) = (4, 1, PieceType::Gold);
+        const RIGHT_GOLD: (u8, u8, PieceType) = (6, 1, PieceType::Gold);
+
+        let pieces: &[(u8, u8, PieceType)] = match self {
+            Handicap::Even => &[],
+            Handicap::Lance => &[LEFT_LANCE],
+            Handicap::RightLance => &[RIGHT_LANCE],
+            Handicap::Bishop => &[BISHOP],
+            Handicap::Rook => &[ROOK],
+            Handicap::RookLance => &[ROOK, LEFT_LANCE],
+            Handicap::TwoPieces => &[ROOK, BISHOP],
+            Handicap::ThreePieces => &[ROOK, BISHOP, LEFT_LANCE],
+            Handicap::FourPieces => &[ROOK, BISHOP, LEFT_LANCE, RIGHT_LANCE],
+            Handicap::FivePieces => &[ROOK, BISHOP, LEFT_LANCE, RIGHT_LANCE, LEFT_KNIGHT],
+            Handicap::SixPieces => &[
+                ROOK,
+                BISHOP,
+                LEFT_LANCE,
+                RIGHT_LANCE,
+                LEFT_KNIGHT,
+                RIGHT_KNIGHT,
+            ],
+            Handicap::SevenPieces => &[
+                ROOK,
+                BISHOP,
+                LEFT_LANCE,
+                RIGHT_LANCE,
+                LEFT_KNIGHT,
+                RIGHT_KNIGHT,
+                LEFT_SILVER,
+            ],
+            Handicap::EightPieces => &[
+                ROOK,
+                BISHOP,
+                LEFT_LANCE,
+                RIGHT_LANCE,
+                LEFT_KNIGHT,
+                RIGHT_KNIGHT,
+                LEFT_SILVER,
+                RIGHT_SILVER,
+            ],
+            Handicap::TenPieces => &[
+                ROOK,
+                BISHOP,
+                LEFT_LANCE,
+                RIGHT_LANCE,
+                LEFT_KNIGHT,
+                RIGHT_KNIGHT,
+                LEFT_SILVER,
+                RIGHT_SILVER,
+                LEFT_GOLD,
+                RIGHT_GOLD,
+            ],
+        };
+
+        pieces
+            .iter()
+            .map(|&(file, rank, pt)| (Square::new(file, rank).unwrap(), pt))
+            .collect()
+    }
+
+    /// Returns the Japanese name used for `手合割` in KIF, e.g. `香落ち`.
+    pub fn kanji(self) -> &'static str {
+        match self {
+            Handicap::Even => "平手",
+            Handicap::Lance => "香落ち",
+            Handicap::RightLance => "右香落ち",
+            Handicap::Bishop => "角落ち",
+            Handicap::Rook => "飛車落ち",
+            Handicap::RookLance => "飛香落ち",
+            Handicap::TwoPieces => "二枚落ち",
+            Handicap::ThreePieces => "三枚落ち",
+            Handicap::FourPieces => "四枚落ち",
+            Handicap::FivePieces => "五枚落ち",
+            Handicap::SixPieces => "六枚落ち",
+            Handicap::SevenPieces => "七枚落ち",
+            Handicap::EightPieces => "八枚落ち",
+            Handicap::TenPieces => "十枚落ち",
+        }
+    }
+
+    /// Returns the side which moves first: Black in even games, otherwise the handicap giver (White).
+    pub fn side_to_move(self) -> Color {
+        match self {
+            Handicap::Even => Color::Black,
+            _ => Color::White,
+        }
+    }
+}
+
 ////////////////////////////////////////////////////////////////////////////////
 
 #[derive(Debug, PartialEq, Eq, Clone, Copy)]
 pub enum Action {
-    Move(Color, Square, Square, PieceType),
+    Move(Color, MoveFrom, Square, PieceType),
     Toryo,
     Chudan,
     Sennichite,
@@ -288,6 +1050,15 @@ pub enum Action {
     Error,
 }
 
+impl Action {
+    /// Returns `true` if the action is a special move which ends the game.
+    ///
+    /// `%MATTA` does not end the game and is not considered as terminal.
+    pub fn is_terminal(&self) -> bool {
+        !matches!(*self, Action::Move(..) | Action::Matta)
+    }
+}
+
 impl fmt::Display for Action {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         match *self {
@@ -313,10 +1084,132 @@ impl fmt::Display for Action {
 
 ////////////////////////////////////////////////////////////////////////////////
 
-#[derive(Debug, PartialEq, Eq)]
+#[derive(Debug, PartialEq, Eq, PartialOrd, Ord, Hash, Clone, Copy)]
+pub enum EndReason {
+    /// `%TORYO`
+    Resign,
+    /// `%TSUMI`
+    Checkmate,
+    /// `%TIME_UP`
+    TimeUp,
+    /// `%ILLEGAL_MOVE`
+    IllegalMove,
+    /// `%+ILLEGAL_ACTION` or `%-ILLEGAL_ACTION`
+    IllegalAction,
+    /// `%SENNICHITE`
+    Sennichite,
+    /// `%JISHOGI`
+    Jishogi,
+    /// `%KACHI`
+    Kachi,
+    /// `%HIKIWAKE`
+    Hikiwake,
+    /// `%CHUDAN`
+    Chudan,
+    /// `%FUZUMI`
+    Fuzumi,
+    /// `%ERROR`
+    Error,
+}
+
+impl EndReason {
+    /// Returns `true` if the game ended in a draw for this reason.
+    pub fn is_draw(self) -> bool {
+        matches!(
+            self,
+            EndReason::Sennichite | EndReason::Jishogi | EndReason::Hikiwake
+        )
+    }
+}
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub struct GameResult {
+    pub winner: Option<Color>,
+    pub reason: EndReason,
+}
+
+impl GameResult {
+    /// Interprets a terminating special move played when `side_to_move` is to move.
+    ///
+    /// Returns `None` for `Action::Move` and `Action::Matta`.
+    pub fn from_action(action: Action, side_to_move: Color) -> Option<GameResult> {
+        let loser = side_to_move;
+        let (winner, reason) = match action {
+            Action::Move(..) | Action::Matta => return None,
+            Action::Toryo => (Some(loser.flip()), EndReason::Resign),
+            Action::Tsumi => (Some(loser.flip()), EndReason::Checkmate),
+            Action::TimeUp => (Some(loser.flip()), EndReason::TimeUp),
+            Action::IllegalMove => (Some(loser.flip()), EndReason::IllegalMove),
+            Action::IllegalAction(color) => (Some(color.flip()), EndReason::IllegalAction),
+            Action::Kachi => (Some(side_to_move), EndReason::Kachi),
+            Action::Sennichite => (None, EndReason::Sennichite),
+            Action::Jishogi => (None, EndReason::Jishogi),
+            Action::Hikiwake => (None, EndReason::Hikiwake),
+            Action::Chudan => (None, EndReason::Chudan),
+            Action::Fuzumi => (None, EndReason::Fuzumi),
+            Action::Error => (None, EndReason::Error),
+        };
+
+        Some(GameResult { winner, reason })
+    }
+
+    /// Returns the special move expressing this result when `side_to_move` is to move.
+    ///
+    /// Returns `None` if the winner is inconsistent with the reason.
+    pub fn to_action(&self, side_to_move: Color) -> Option<Action> {
+        let opponent = side_to_move.flip();
+        let action = match (self.reason, self.winner) {
+            (EndReason::Resign, Some(c)) if c == opponent => Action::Toryo,
+            (EndReason::Checkmate, Some(c)) if c == opponent => Action::Tsumi,
+            (EndReason::TimeUp, Some(c)) if c == opponent => Action::TimeUp,
+            (EndReason::IllegalMove, Some(c)) if c == opponent => Action::IllegalMove,
+            (EndReason::IllegalAction, Some(c)) => Action::IllegalAction(c.flip()),
+            (EndReason::Kachi, Some(c)) if c == side_to_move => Action::Kachi,
+            (EndReason::Sennichite, None) => Action::Sennichite,
+            (EndReason::Jishogi, None) => Action::Jishogi,
+            (EndReason::Hikiwake, None) => Action::Hikiwake,
+            (EndReason::Chudan, None) => Action::Chudan,
+            (EndReason::Fuzumi, None) => Action::Fuzumi,
+            (EndReason::Error, None) => Action::Error,
+            _ => return None,
+        };
+
+        Some(action)
+    }
+}
+
+////////////////////////////////////////////////////////////////////////////////
+
+#[derive(Debug, PartialEq, Eq, Clone)]
 pub struct MoveRecord {
     pub action: Action,
     pub time: Option<Duration>,
+    /// Comment lines following the move, without the leading `'`.
+    pub comments: Vec<String>,
+}
+
+impl MoveRecord {
+    /// Formats the record as a single protocol line such as `+7776FU,T12`.
+    ///
+    /// A line can hold one comment, so only the last comment is written, as in `+7776FU,'* 30 -3334FU`.
+    pub fn to_line(&self) -> String {
+        let mut line = self.action.to_string();
+        if let Some(ref time) = self.time {
+            line.push_str(&format!(",T{}", time.as_secs()));
+        }
+        if let Some(comment) = self.comments.last() {
+            line.push_str(&format!(",'{comment}"));
+        }
+        line
+    }
+
+    /// Returns the last engine evaluation among the comments, e.g. `** 30 -3334FU`.
+    pub fn evaluation(&self) -> Option<Evaluation> {
+        self.comments
+            .iter()
+            .rev()
+            .find_map(|comment| parse_evaluation(comment).ok())
+    }
 }
 
 impl fmt::Display for MoveRecord {
@@ -327,6 +1220,31 @@ impl fmt::Display for MoveRecord {
             writeln!(f, "T{}", time.as_secs())?;
         }
 
+        for comment in &self.comments {
+            writeln!(f, "'{comment}")?;
+        }
+
+        Ok(())
+    }
+}
+
+/// An engine evaluation sent in a move comment as `* <score> <pv>...`.
+///
+/// Servers write it to records with an extra `*`, as in `'** 30 -3334FU +2726FU`.
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct Evaluation {
+    /// The score in centipawns, conventionally from Black's point of view.
+    pub score: i32,
+    /// The expected continuation.
+    pub pv: Vec<Action>,
+}
+
+impl fmt::Display for Evaluation {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "* {}", self.score)?;
+        for action in &self.pv {
+            write!(f, " {action}")?;
+        }
         Ok(())
     }
 }
@@ -356,13 +1274,261 @@ mod tests {
         assert_eq!(&PieceType::All.to_string(), "AL");
     }
 
+    #[test]
+    fn square() {
+        assert_eq!(Square::new(0, 1), None);
+        assert_eq!(Square::new(1, 10), None);
+
+        let sq = Square::new(7, 6).unwrap();
+        assert_eq!((sq.file(), sq.rank()), (7, 6));
+        assert_eq!(&sq.to_string(), "76");
+        assert_eq!(Square::from_index(sq.index()), Some(sq));
+        assert_eq!(Square::from_index(81), None);
+
+        let squares = Square::iter().collect::<Vec<_>>();
+        assert_eq!(squares.len(), 81);
+        assert!(squares.iter().enumerate().all(|(i, sq)| sq.index() == i));
+
+        assert_eq!(sq.flip(), Square::new(3, 4).unwrap());
+        assert_eq!(sq.flip().flip(), sq);
+        assert_eq!(sq.relative_rank(Color::Black), 6);
+        assert_eq!(sq.relative_rank(Color::White), 4);
+        assert!(Square::new(1, 3).unwrap().in_promotion_zone(Color::Black));
+        assert!(!Square::new(1, 3).unwrap().in_promotion_zone(Color::White));
+        assert!(Square::new(1, 7).unwrap().in_promotion_zone(Color::White));
+
+        assert_eq!(sq.shift(1, -1), Square::new(8, 5));
+        assert_eq!(sq.shift(3, 0), None);
+        assert_eq!(Square::new(1, 1).unwrap().shift(-1, 0), None);
+
+        let other = Square::new(5, 9).unwrap();
+        assert_eq!(sq.distance(other), 3);
+        assert_eq!(sq.manhattan_distance(other), 5);
+    }
+
+    #[test]
+    fn move_from() {
+        assert_eq!(&MoveFrom::Hand.to_string(), "00");
+        assert_eq!(
+            &MoveFrom::from(Square::new(2, 7).unwrap()).to_string(),
+            "27"
+        );
+        assert_eq!(
+            &Action::Move(
+                Color::White,
+                MoveFrom::Hand,
+                Square::new(5, 5).unwrap(),
+                PieceType::Bishop
+            )
+            .to_string(),
+            "-0055KA"
+        );
+    }
+
+    const PIECE_TYPES: [PieceType; 15] = [
+        PieceType::Pawn,
+        PieceType::Lance,
+        PieceType::Knight,
+        PieceType::Silver,
+        PieceType::Gold,
+        PieceType::Bishop,
+        PieceType::Rook,
+        PieceType::King,
+        PieceType::ProPawn,
+        PieceType::ProLance,
+        PieceType::ProKnight,
+        PieceType::ProSilver,
+        PieceType::Horse,
+        PieceType::Dragon,
+        PieceType::All,
+    ];
+
+    #[test]
+    fn piece_type_promotion() {
+        let promotions = [
+            (PieceType::Pawn, Some(PieceType::ProPawn)),
+            (PieceType::Lance, Some(PieceType::ProLance)),
+            (PieceType::Knight, Some(PieceType::ProKnight)),
+            (PieceType::Silver, Some(PieceType::ProSilver)),
+            (PieceType::Gold, None),
+            (PieceType::Bishop, Some(PieceType::Horse)),
+            (PieceType::Rook, Some(PieceType::Dragon)),
+            (PieceType::King, None),
+            (PieceType::ProPawn, None),
+            (PieceType::ProLance, None),
+            (PieceType::ProKnight, None),
+            (PieceType::ProSilver, None),
+            (PieceType::Horse, None),
+            (PieceType::Dragon, None),
+            (PieceType::All, None),
+        ];
+
+        for (pt, promoted) in promotions {
+            assert_eq!(pt.promote(), promoted);
+            assert_eq!(pt.can_promote(), promoted.is_some());
+            if let Some(promoted) = promoted {
+                assert!(promoted.is_promoted());
+                assert_eq!(promoted.unpromote(), Some(pt));
+            }
+        }
+
+        for pt in PIECE_TYPES {
+            assert_eq!(
+                pt.is_promoted(),
+                PIECE_TYPES.iter().any(|p| p.promote() == Some(pt))
+            );
+        }
+    }
+
+    #[test]
+    fn piece_type_hand_type() {
+        let hand_types = [
+            Some(PieceType::Pawn),
+            Some(PieceType::Lance),
+            Some(PieceType::Knight),
+            Some(PieceType::Silver),
+            Some(PieceType::Gold),
+            Some(PieceType::Bishop),
+            Some(PieceType::Rook),
+            None,
+            Some(PieceType::Pawn),
+            Some(PieceType::Lance),
+            Some(PieceType::Knight),
+            Some(PieceType::Silver),
+            Some(PieceType::Bishop),
+            Some(PieceType::Rook),
+            None,
+        ];
+
+        for (pt, hand_type) in PIECE_TYPES.iter().zip(hand_types) {
+            assert_eq!(pt.hand_type(), hand_type);
+        }
+    }
+
+    #[test]
+    fn piece_type_values() {
+        let values = [
+            90, 315, 405, 495, 540, 855, 990, 0, 540, 540, 540, 540, 945, 1395, 0,
+        ];
+        let points = [1, 1, 1, 1, 1, 5, 5, 0, 1, 1, 1, 1, 5, 5, 0];
+
+        for ((pt, value), points) in PIECE_TYPES.iter().zip(values).zip(points) {
+            assert_eq!(pt.value(), value);
+            assert_eq!(pt.impasse_points(), points);
+        }
+    }
+
+    #[test]
+    fn piece_type_movements() {
+        let counts = [
+            (1, 0),
+            (0, 1),
+            (2, 0),
+            (5, 0),
+            (6, 0),
+            (0, 4),
+            (0, 4),
+            (8, 0),
+            (6, 0),
+            (6, 0),
+            (6, 0),
+            (6, 0),
+            (4, 4),
+            (4, 4),
+            (0, 0),
+        ];
+
+        for (pt, (steps, slides)) in PIECE_TYPES.iter().zip(counts) {
+            assert_eq!(pt.steps().len(), steps, "{pt:?}");
+            assert_eq!(pt.slides().len(), slides, "{pt:?}");
+        }
+
+        assert_eq!(PieceType::Pawn.steps(), &[(0, -1)]);
+        assert_eq!(PieceType::Lance.slides(), &[(0, -1)]);
+        assert!(PieceType::Gold.steps().contains(&(0, 1)));
+        assert!(!PieceType::Silver.steps().contains(&(0, 1)));
+        assert!(PieceType::Horse.steps().contains(&(0, 1)));
+        assert!(PieceType::Dragon.steps().contains(&(1, 1)));
+    }
+
+    #[test]
+    fn piece_type_names() {
+        let kanji = [
+            Some("歩"),
+            Some("香"),
+            Some("桂"),
+            Some("銀"),
+            Some("金"),
+            Some("角"),
+            Some("飛"),
+            Some("玉"),
+            Some("と"),
+            Some("成香"),
+            Some("成桂"),
+            Some("成銀"),
+            Some("馬"),
+            Some("龍"),
+            None,
+        ];
+        let kanji_chars = [
+            Some('歩'),
+            Some('香'),
+            Some('桂'),
+            Some('銀'),
+            Some('金'),
+            Some('角'),
+            Some('飛'),
+            Some('玉'),
+            Some('と'),
+            Some('杏'),
+            Some('圭'),
+            Some('全'),
+            Some('馬'),
+            Some('龍'),
+            None,
+        ];
+        let usi_chars = [
+            Some('P'),
+            Some('L'),
+            Some('N'),
+            Some('S'),
+            Some('G'),
+            Some('B'),
+            Some('R'),
+            Some('K'),
+            Some('P'),
+            Some('L'),
+            Some('N'),
+            Some('S'),
+            Some('B'),
+            Some('R'),
+            None,
+        ];
+
+        for (i, pt) in PIECE_TYPES.iter().enumerate() {
+            assert_eq!(pt.kanji(), kanji[i]);
+            assert_eq!(pt.kanji_char(), kanji_chars[i]);
+            assert_eq!(pt.usi_char(), usi_chars[i]);
+
+            if let Some(c) = usi_chars[i] {
+                let original = pt.unpromote().unwrap_or(*pt);
+                assert_eq!(PieceType::from_usi_char(c), Some(original));
+                assert_eq!(
+                    PieceType::from_usi_char(c.to_ascii_lowercase()),
+                    Some(original)
+                );
+            }
+        }
+        assert_eq!(PieceType::from_usi_char('X'), None);
+    }
+
     #[test]
     fn action() {
         assert_eq!(
             &Action::Move(
                 Color::Black,
-                Square::new(7, 7),
-                Square::new(7, 6),
+                MoveFrom::Board(Square::new(7, 7).unwrap()),
+                Square::new(7, 6).unwrap(),
                 PieceType::Pawn,
             )
             .to_string(),
@@ -391,6 +1557,210 @@ mod tests {
         assert_eq!(&Action::Error.to_string(), "%ERROR");
     }
 
+    #[test]
+    fn time_limit() {
+        let time_limit = |main_time, byoyomi, increment| TimeLimit {
+            main_time,
+            byoyomi,
+            increment,
+        };
+
+        assert_eq!(
+            &time_limit(
+                Duration::from_secs(600),
+                Duration::from_secs(10),
+                Duration::ZERO
+            )
+            .to_string(),
+            "00:10+10"
+        );
+        assert_eq!(
+            &time_limit(Duration::from_secs(5400), Duration::ZERO, Duration::ZERO).to_string(),
+            "01:30+00"
+        );
+        // Leftover seconds of main time cannot be written in the V2.2 form.
+        let odd = time_limit(
+            Duration::from_secs(90),
+            Duration::from_secs(10),
+            Duration::ZERO,
+        );
+        assert!(odd.requires_v3());
+        assert_eq!(&odd.to_string(), "90+10+0");
+        assert_eq!(
+            &time_limit(
+                Duration::from_secs(600),
+                Duration::ZERO,
+                Duration::from_secs(10)
+            )
+            .to_string(),
+            "600+0+10"
+        );
+        assert_eq!(
+            &time_limit(
+                Duration::from_millis(600_500),
+                Duration::from_secs(10),
+                Duration::ZERO
+            )
+            .to_string(),
+            "600.5+10+0"
+        );
+
+        let g = GameRecord {
+            time_limit: Some(time_limit(
+                Duration::from_secs(600),
+                Duration::ZERO,
+                Duration::from_secs(10),
+            )),
+            ..GameRecord::default()
+        };
+        let csa = g.to_string();
+        assert!(csa.starts_with("V3.0\n"));
+        assert!(csa.contains("\n$TIME:600+0+10\n"));
+        assert_eq!(crate::parse_csa(&csa).unwrap().time_limit, g.time_limit);
+        assert!(GameRecord::default().to_string().starts_with("V2.2\n"));
+    }
+
+    #[test]
+    fn time() {
+        let jst = UtcOffset::from_hms(9, 0, 0).unwrap();
+        let t = Time {
+            date: time::Date::from_calendar_date(2003, time::Month::May, 3).unwrap(),
+            time: Some(time::Time::from_hms(1, 30, 0).unwrap()),
+            offset: Some(UtcOffset::UTC),
+        };
+        assert_eq!(&t.to_string(), "2003/05/03 1:30:00");
+        assert_eq!(
+            &t.display_with_offset_suffix().to_string(),
+            "2003/05/03 1:30:00+00:00"
+        );
+
+        let local = t.to_offset(jst);
+        assert_eq!(
+            &local.display_with_offset_suffix().to_string(),
+            "2003/05/03 10:30:00+09:00"
+        );
+        assert_eq!(local.to_offset_date_time(), t.to_offset_date_time());
+
+        let naive = Time { offset: None, ..t };
+        assert_eq!(
+            &naive.display_with_offset_suffix().to_string(),
+            "2003/05/03 1:30:00"
+        );
+        assert_eq!(naive.to_offset_date_time(), None);
+        assert_eq!(naive.to_offset(jst), naive);
+        assert_eq!(
+            naive.assume_offset(jst).to_offset(UtcOffset::UTC).hour(),
+            16
+        );
+
+        let negative = t.to_offset(UtcOffset::from_hms(-3, -30, 0).unwrap());
+        assert_eq!(
+            &negative.display_with_offset_suffix().to_string(),
+            "2003/05/02 22:00:00-03:30"
+        );
+    }
+
+    #[test]
+    fn display_with_offset() {
+        let jst = UtcOffset::from_hms(9, 0, 0).unwrap();
+        let g = GameRecord {
+            start_time: Some(Time {
+                date: time::Date::from_calendar_date(2003, time::Month::May, 3).unwrap(),
+                time: Some(time::Time::from_hms(1, 30, 0).unwrap()),
+                offset: Some(UtcOffset::UTC),
+            }),
+            end_time: Some(Time {
+                date: time::Date::from_calendar_date(2003, time::Month::May, 3).unwrap(),
+                time: Some(time::Time::from_hms(11, 11, 5).unwrap()),
+                offset: None,
+            }),
+            ..GameRecord::default()
+        };
+
+        let csa = g.display_with_offset(jst).to_string();
+        assert!(csa.contains("$START_TIME:2003/05/03 10:30:00\n"));
+        assert!(csa.contains("$END_TIME:2003/05/03 11:11:05\n"));
+
+        let csa = g.to_string();
+        assert!(csa.contains("$START_TIME:2003/05/03 1:30:00\n"));
+
+        let csa = g.display_with_offset_suffix().to_string();
+        assert!(csa.contains("$START_TIME:2003/05/03 1:30:00+00:00\n"));
+        assert!(csa.contains("$END_TIME:2003/05/03 11:11:05\n"));
+    }
+
+    #[test]
+    fn handicap() {
+        for handicap in Handicap::ALL {
+            let pos = Position::from_handicap(handicap);
+            assert_eq!(pos.handicap(), Some(handicap));
+            assert_eq!(pos.side_to_move, handicap.side_to_move());
+        }
+
+        let pos = Position::from_handicap(Handicap::TwoPieces);
+        assert_eq!(&pos.to_string(), "PI82HI22KA\n-\n");
+        assert_eq!(
+            Position::from_handicap(Handicap::Even).side_to_move,
+            Color::Black
+        );
+
+        let swapped = Position {
+            drop_pieces: vec![
+                (Square::new(2, 2).unwrap(), PieceType::Bishop),
+                (Square::new(8, 2).unwrap(), PieceType::Rook),
+            ],
+            ..Position::default()
+        };
+        assert_eq!(swapped.handicap(), Some(Handicap::TwoPieces));
+
+        let mut bulk = hirate_grid();
+        assert_eq!(
+            Position {
+                bulk: Some(bulk),
+                ..Position::default()
+            }
+            .handicap(),
+            Some(Handicap::Even)
+        );
+
+        bulk[0][8] = None;
+        assert_eq!(
+            Position {
+                bulk: Some(bulk),
+                ..Position::default()
+            }
+            .handicap(),
+            Some(Handicap::Lance)
+        );
+
+        let custom = Position {
+            drop_pieces: vec![(Square::new(5, 1).unwrap(), PieceType::King)],
+            ..Position::default()
+        };
+        assert_eq!(custom.handicap(), None);
+
+        let grid = "\
+P1-KY-KE-GI-KI-OU-KI-GI-KE-KY
+P2 * -HI *  *  *  *  * -KA * 
+P3-FU-FU-FU-FU-FU-FU-FU-FU-FU
+P4 *  *  *  *  *  *  *  *  * 
+P5 *  *  *  *  *  *  *  *  * 
+P6 *  *  *  *  *  *  *  *  * 
+P7+FU+FU+FU+FU+FU+FU+FU+FU+FU
+P8 * +KA *  *  *  *  * +HI * 
+P9+KY+KE+GI+KI+OU+KI+GI+KE+KY
++
+";
+        let parsed = crate::parse_csa(grid).unwrap();
+        assert_eq!(parsed.start_pos.handicap(), Some(Handicap::Even));
+
+        let with_hand = Position {
+            add_pieces: vec![(Color::Black, None, PieceType::Gold)],
+            ..Position::default()
+        };
+        assert_eq!(with_hand.handicap(), None);
+    }
+
     #[test]
     fn game_record() {
         let mut g = GameRecord {
@@ -401,14 +1771,17 @@ mod tests {
             start_time: Some(Time {
                 date: time::Date::from_calendar_date(2003, time::Month::May, 3).unwrap(),
                 time: Some(time::Time::from_hms(10, 30, 0).unwrap()),
+                offset: None,
             }),
             end_time: Some(Time {
                 date: time::Date::from_calendar_date(2003, time::Month::May, 3).unwrap(),
                 time: Some(time::Time::from_hms(11, 11, 5).unwrap()),
+                offset: None,
             }),
             time_limit: Some(TimeLimit {
                 main_time: Duration::from_secs(1500),
                 byoyomi: Duration::from_secs(0),
+                increment: Duration::from_secs(0),
             }),
             opening: Some("YAGURA".to_string()),
             ..GameRecord::default()
@@ -416,15 +1789,17 @@ mod tests {
         g.moves.push(MoveRecord {
             action: Action::Move(
                 Color::Black,
-                Square::new(8, 7),
-                Square::new(8, 6),
+                MoveFrom::Board(Square::new(8, 7).unwrap()),
+                Square::new(8, 6).unwrap(),
                 PieceType::Pawn,
             ),
             time: Some(Duration::from_secs(5)),
+            comments: vec!["** 30 -3334FU".to_string()],
         });
         g.moves.push(MoveRecord {
             action: Action::Toryo,
             time: None,
+            comments: vec![],
         });
 
         let csa = "\
@@ -441,9 +1816,163 @@ PI
 +
 +8786FU
 T5
+'** 30 -3334FU
 %TORYO
 ";
 
         assert_eq!(csa, g.to_string());
     }
+
+    #[test]
+    fn game_result() {
+        let pawn_move = |color, from: (u8, u8), to: (u8, u8)| MoveRecord {
+            action: Action::Move(
+                color,
+                MoveFrom::Board(Square::new(from.0, from.1).unwrap()),
+                Square::new(to.0, to.1).unwrap(),
+                PieceType::Pawn,
+            ),
+            time: None,
+            comments: vec![],
+        };
+        let special = |action| MoveRecord {
+            action,
+            time: None,
+            comments: vec![],
+        };
+
+        let mut g = GameRecord::default();
+        assert_eq!(g.result(), None);
+
+        g.moves.push(pawn_move(Color::Black, (7, 7), (7, 6)));
+        g.moves.push(special(Action::Toryo));
+        assert_eq!(
+            g.result(),
+            Some(GameResult {
+                winner: Some(Color::Black),
+                reason: EndReason::Resign
+            })
+        );
+
+        g.moves[1] = special(Action::Kachi);
+        assert_eq!(
+            g.result(),
+            Some(GameResult {
+                winner: Some(Color::White),
+                reason: EndReason::Kachi
+            })
+        );
+
+        g.moves[1] = special(Action::IllegalAction(Color::Black));
+        assert_eq!(
+            g.result(),
+            Some(GameResult {
+                winner: Some(Color::White),
+                reason: EndReason::IllegalAction
+            })
+        );
+
+        g.moves[1] = special(Action::Sennichite);
+        let result = g.result().unwrap();
+        assert_eq!(result.winner, None);
+        assert!(result.reason.is_draw());
+
+        g.moves[1] = special(Action::Chudan);
+        let result = g.result().unwrap();
+        assert_eq!(result.winner, None);
+        assert!(!result.reason.is_draw());
+
+        g.moves[1] = special(Action::Matta);
+        assert_eq!(g.result(), None);
+
+        g.start_pos.side_to_move = Color::White;
+        g.moves.clear();
+        g.moves.push(special(Action::TimeUp));
+        assert_eq!(
+            g.result(),
+            Some(GameResult {
+                winner: Some(Color::Black),
+                reason: EndReason::TimeUp
+            })
+        );
+    }
+
+    #[test]
+    fn set_game_result() {
+        let mut g = GameRecord::default();
+        g.moves.push(MoveRecord {
+            action: Action::Move(
+                Color::Black,
+                MoveFrom::Board(Square::new(7, 7).unwrap()),
+                Square::new(7, 6).unwrap(),
+                PieceType::Pawn,
+            ),
+            time: None,
+            comments: vec![],
+        });
+
+        let resign = GameResult {
+            winner: Some(Color::Black),
+            reason: EndReason::Resign,
+        };
+        g.set_result(resign).unwrap();
+        assert_eq!(g.moves.last().unwrap().action, Action::Toryo);
+        assert_eq!(g.result(), Some(resign));
+
+        let kachi = GameResult {
+            winner: Some(Color::White),
+            reason: EndReason::Kachi,
+        };
+        g.set_result(kachi).unwrap();
+        assert_eq!(g.moves.len(), 2);
+        assert_eq!(g.moves.last().unwrap().action, Action::Kachi);
+        assert_eq!(g.result(), Some(kachi));
+
+        let illegal_action = GameResult {
+            winner: Some(Color::White),
+            reason: EndReason::IllegalAction,
+        };
+        g.set_result(illegal_action).unwrap();
+        assert_eq!(
+            g.moves.last().unwrap().action,
+            Action::IllegalAction(Color::Black)
+        );
+
+        let invalid = GameResult {
+            winner: Some(Color::White),
+            reason: EndReason::Resign,
+        };
+        assert!(g.set_result(invalid).is_err());
+        assert_eq!(g.result(), Some(illegal_action));
+    }
+
+    #[test]
+    fn move_line() {
+        let mut record = MoveRecord {
+            action: Action::Move(
+                Color::Black,
+                MoveFrom::Board(Square::new(7, 7).unwrap()),
+                Square::new(7, 6).unwrap(),
+                PieceType::Pawn,
+            ),
+            time: Some(Duration::from_secs(12)),
+            comments: vec![],
+        };
+        assert_eq!(record.to_line(), "+7776FU,T12");
+        assert_eq!(record.evaluation(), None);
+
+        let evaluation = Evaluation {
+            score: -45,
+            pv: vec![Action::Move(
+                Color::White,
+                MoveFrom::Board(Square::new(3, 3).unwrap()),
+                Square::new(3, 4).unwrap(),
+                PieceType::Pawn,
+            )],
+        };
+        assert_eq!(evaluation.to_string(), "* -45 -3334FU");
+        record.comments = vec!["jouseki".to_string(), format!("*{evaluation}")];
+        assert_eq!(record.to_line(), "+7776FU,T12,'** -45 -3334FU");
+        assert_eq!(record.evaluation(), Some(evaluation));
+    }
 }
@@ -0,0 +1,345 @@
+use crate::value::{GameRecord, MoveRecord};
+
+/// An alternative line branching off from a sequence of moves.
+#[derive(Debug, Default, PartialEq, Eq, Clone)]
+pub struct Variation {
+    /// Index of the move in the parent line which this variation replaces.
+    pub start: usize,
+    pub moves: Vec<MoveRecord>,
+    /// Variations branching off from `moves`.
+    pub variations: Vec<Variation>,
+}
+
+impl Variation {
+    pub fn new(start: usize, moves: Vec<MoveRecord>) -> Variation {
+        Variation {
+            start,
+            moves,
+            variations: Vec::new(),
+        }
+    }
+
+    /// Adds a variation replacing the moves from `start`, and returns it.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `start > self.moves.len()`.
+    pub fn add_variation(&mut self, start: usize, moves: Vec<MoveRecord>) -> &mut Variation {
+        add_variation(&self.moves, &mut self.variations, start, moves)
+    }
+
+    /// Swaps the variation at `index` with the moves of this variation it replaces.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `index` is out of bounds.
+    pub fn promote_variation(&mut self, index: usize) {
+        promote_variation(&mut self.moves, &mut self.variations, index);
+    }
+
+    /// Removes the variation at `index` and returns it.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `index` is out of bounds.
+    pub fn remove_variation(&mut self, index: usize) -> Variation {
+        self.variations.remove(index)
+    }
+}
+
+impl GameRecord {
+    /// Returns the mainline, which is the same as `moves`.
+    pub fn mainline(&self) -> &[MoveRecord] {
+        &self.moves
+    }
+
+    /// Adds a variation replacing the mainline from `start`, and returns it.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `start > self.moves.len()`.
+    pub fn add_variation(&mut self, start: usize, moves: Vec<MoveRecord>) -> &mut Variation {
+        add_variation(&self.moves, &mut self.variations, start, moves)
+    }
+
+    /// Makes the variation at `index` the mainline, keeping the previous mainline as a variation.
+    ///
+    /// Variations branching off from the replaced moves move along with them.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `index` is out of bounds.
+    pub fn promote_variation(&mut self, index: usize) {
+        promote_variation(&mut self.moves, &mut self.variations, index);
+    }
+
+    /// Removes the variation at `index` and returns it.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `index` is out of bounds.
+    pub fn remove_variation(&mut self, index: usize) -> Variation {
+        self.variations.remove(index)
+    }
+
+    /// Returns the variation at `path`, where each element indexes `variations` of the previous one.
+    pub fn variation(&self, path: &[usize]) -> Option<&Variation> {
+        let (&first, rest) = path.split_first()?;
+        rest.iter()
+            .try_fold(self.variations.get(first)?, |variation, &i| {
+                variation.variations.get(i)
+            })
+    }
+
+    pub fn variation_mut(&mut self, path: &[usize]) -> Option<&mut Variation> {
+        let (&first, rest) = path.split_first()?;
+        rest.iter()
+            .try_fold(self.variations.get_mut(first)?, |variation, &i| {
+                variation.variations.get_mut(i)
+            })
+    }
+
+    /// Returns all the moves from the starting position following the variations at `path`.
+    ///
+    /// An empty path gives the mainline.
+    pub fn line(&self, path: &[usize]) -> Option<Vec<MoveRecord>> {
+        let mut moves = self.moves.clone();
+        let mut variations = &self.variations;
+        let mut offset = 0;
+
+        for &i in path {
+            let variation = variations.get(i)?;
+            offset += variation.start;
+            if offset > moves.len() {
+                return None;
+            }
+
+            moves.truncate(offset);
+            moves.extend(variation.moves.iter().cloned());
+            variations = &variation.variations;
+        }
+
+        Some(moves)
+    }
+
+    /// Returns a record whose mainline is the line at `path`, without any variations.
+    pub fn line_record(&self, path: &[usize]) -> Option<GameRecord> {
+        let moves = self.line(path)?;
+
+        Some(GameRecord {
+            black_player: self.black_player.clone(),
+            white_player: self.white_player.clone(),
+            event: self.event.clone(),
+            site: self.site.clone(),
+            start_time: self.start_time.clone(),
+            end_time: self.end_time.clone(),
+            time_limit: self.time_limit.clone(),
+            opening: self.opening.clone(),
+            start_pos: self.start_pos.clone(),
+            moves,
+            variations: Vec::new(),
+        })
+    }
+}
+
+fn add_variation<'a>(
+    moves: &[MoveRecord],
+    variations: &'a mut Vec<Variation>,
+    start: usize,
+    new_moves: Vec<MoveRecord>,
+) -> &'a mut Variation {
+    assert!(
+        start <= moves.len(),
+        "variation start {start} is out of the line of length {}",
+        moves.len()
+    );
+
+    variations.push(Variation::new(start, new_moves));
+    variations.last_mut().unwrap()
+}
+
+fn promote_variation(moves: &mut Vec<MoveRecord>, variations: &mut Vec<Variation>, index: usize) {
+    let promoted = variations.remove(index);
+    let start = promoted.start;
+
+    let mut demoted = Variation::new(start, moves.split_off(start));
+    let mut kept = Vec::with_capacity(variations.len() + promoted.variations.len() + 1);
+    // The demoted line takes the place of the promoted one among the kept siblings.
+    let mut position = 0;
+    for (i, variation) in variations.drain(..).enumerate() {
+        if variation.start > start {
+            demoted.variations.push(Variation {
+                start: variation.start - start,
+                ..variation
+            });
+        } else {
+            if i < index {
+                position += 1;
+            }
+            kept.push(variation);
+        }
+    }
+
+    kept.insert(position, demoted);
+    kept.extend(promoted.variations.into_iter().map(|variation| Variation {
+        start: variation.start + start,
+        ..variation
+    }));
+
+    moves.extend(promoted.moves);
+    *variations = kept;
+}
+
+////////////////////////////////////////////////////////////////////////////////
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::value::{Action, Color, EndReason, GameResult, MoveFrom, PieceType, Square};
+
+    fn pawn(color: Color, file: u8, rank: u8) -> MoveRecord {
+        let to = match color {
+            Color::Black => rank - 1,
+            Color::White => rank + 1,
+        };
+        MoveRecord {
+            action: Action::Move(
+                color,
+                MoveFrom::Board(Square::new(file, rank).unwrap()),
+                Square::new(file, to).unwrap(),
+                PieceType::Pawn,
+            ),
+            time: None,
+            comments: vec![],
+        }
+    }
+
+    fn sample() -> GameRecord {
+        let mut g = GameRecord {
+            moves: vec![
+                pawn(Color::Black, 7, 7),
+                pawn(Color::White, 3, 3),
+                pawn(Color::Black, 2, 7),
+            ],
+            ..GameRecord::default()
+        };
+
+        let v = g.add_variation(1, vec![pawn(Color::White, 8, 3), pawn(Color::Black, 2, 7)]);
+        v.add_variation(1, vec![pawn(Color::Black, 6, 7)]);
+        g.add_variation(2, vec![pawn(Color::Black, 6, 7)]);
+        g
+    }
+
+    #[test]
+    fn line() {
+        let g = sample();
+
+        assert_eq!(g.line(&[]).unwrap(), g.mainline());
+        assert_eq!(
+            g.line(&[0]).unwrap(),
+            vec![
+                pawn(Color::Black, 7, 7),
+                pawn(Color::White, 8, 3),
+                pawn(Color::Black, 2, 7),
+            ]
+        );
+        assert_eq!(
+            g.line(&[0, 0]).unwrap(),
+            vec![
+                pawn(Color::Black, 7, 7),
+                pawn(Color::White, 8, 3),
+                pawn(Color::Black, 6, 7),
+            ]
+        );
+        assert_eq!(g.line(&[2]), None);
+        assert_eq!(g.variation(&[0, 0]).unwrap().start, 1);
+        assert_eq!(g.variation(&[]), None);
+
+        let record = g.line_record(&[1]).unwrap();
+        assert_eq!(record.moves[2], pawn(Color::Black, 6, 7));
+        assert!(record.variations.is_empty());
+        assert_eq!(record.to_string(), g.to_string().replace("2726", "6766"));
+    }
+
+    #[test]
+    fn promote_variation() {
+        let mut g = sample();
+        g.promote_variation(0);
+
+        assert_eq!(
+            g.moves,
+            vec![
+                pawn(Color::Black, 7, 7),
+                pawn(Color::White, 8, 3),
+                pawn(Color::Black, 2, 7),
+            ]
+        );
+        assert_eq!(g.variations.len(), 2);
+        assert_eq!(g.variations[0].start, 1);
+        assert_eq!(
+            g.variations[0].moves,
+            vec![pawn(Color::White, 3, 3), pawn(Color::Black, 2, 7)]
+        );
+        assert_eq!(g.variations[0].variations[0].start, 1);
+        assert_eq!(
+            g.variations[0].variations[0].moves,
+            vec![pawn(Color::Black, 6, 7)]
+        );
+        assert_eq!(g.variations[1].start, 2);
+        assert_eq!(g.variations[1].moves, vec![pawn(Color::Black, 6, 7)]);
+
+        g.promote_variation(0);
+        assert_eq!(g, sample());
+
+        // A deeper sibling before the promoted line moves into the demoted one.
+        let mut g = GameRecord {
+            moves: sample().moves,
+            ..GameRecord::default()
+        };
+        g.add_variation(2, vec![pawn(Color::Black, 6, 7)]);
+        g.add_variation(1, vec![pawn(Color::White, 8, 3)]);
+        g.add_variation(0, vec![pawn(Color::Black, 2, 7)]);
+        g.promote_variation(1);
+
+        assert_eq!(g.moves[1], pawn(Color::White, 8, 3));
+        assert_eq!(g.variations.len(), 2);
+        assert_eq!(g.variations[0].start, 1);
+        assert_eq!(g.variations[0].moves[0], pawn(Color::White, 3, 3));
+        assert_eq!(g.variations[0].variations[0].start, 1);
+        assert_eq!(g.variations[1].start, 0);
+    }
+
+    #[test]
+    fn remove_variation() {
+        let mut g = sample();
+
+        let removed = g.variation_mut(&[0]).unwrap().remove_variation(0);
+        assert_eq!(removed.moves, vec![pawn(Color::Black, 6, 7)]);
+        assert!(g.variations[0].variations.is_empty());
+
+        g.remove_variation(0);
+        assert_eq!(g.variations.len(), 1);
+        assert_eq!(g.line(&[0]).unwrap()[2], pawn(Color::Black, 6, 7));
+    }
+
+    #[test]
+    fn set_result() {
+        let mut g = sample();
+        g.set_result(GameResult {
+            winner: Some(Color::Black),
+            reason: EndReason::Resign,
+        })
+        .unwrap();
+
+        assert_eq!(g.moves.len(), 4);
+        assert_eq!(g.variations.len(), 2);
+
+        g.moves.truncate(1);
+        g.set_result(GameResult {
+            winner: Some(Color::Black),
+            reason: EndReason::Resign,
+        })
+        .unwrap();
+        assert_eq!(g.variations.len(), 1);
+    }
+}